@@ -9,15 +9,29 @@
 //! ## Usage:
 //! The `read_file` function reads the content of a file and returns it as a string.
 //!
+//! The `read_bytes` function reads the raw content of a file, so binary files can be handled.
+//!
 //! The `read_struct` function reads the content of a file and deserializes it into a struct.
 //!
+//! Symbolic links are never followed. The `check_file` function accepts them as files, the
+//! `check_link` function recognizes them, the `read_link` and `write_link` functions read and
+//! atomically write their targets, and the `read_entry` function reads the target of a link or
+//! the content of any other file.
+//!
 //! The `write_file` function writes content to a file.
 //!
+//! The `write_bytes` function writes raw content to a file. Every write goes to a temporary
+//! file that is synced and then renamed over the destination, so a crash never leaves a
+//! half-written file behind. The `is_temp_file` function recognizes temporary files left over
+//! from interrupted writes. The `write_stream` function writes a file piece by piece in the
+//! same way, for content too large to hold in memory. The `create_file` function writes a file
+//! only if it does not exist yet, and the file appears with its full content or not at all.
+//!
 //! The `write_struct` function serializes a struct and writes it to a file.
 //!
 //! The `append_file` function appends content to a file.
 //!
-//! The `copy_file` function copies a file from one location to another.
+//! The `copy_file` function copies a file from one location to another, atomically.
 //!
 //! The `delete_file` function deletes a file.
 //!
@@ -28,18 +42,25 @@
 //! Date: 11/14/2024
 
 use serde::de::{DeserializeOwned, Error};
-use std::fs::{self, rename, OpenOptions};
+use std::fs::{self, rename, File, OpenOptions};
 use std::io::{self, Read, Write};
+use std::os::unix::fs::symlink;
 use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
 pub fn check_file(path: &str) -> bool {
-    Path::new(path).is_file()
+    Path::new(path).is_file() || check_link(path)
 }
 
-pub fn is_binary_file(content: &str) -> bool {
-    content
-        .bytes()
-        .any(|b| b < 0x20 && b != b'\n' && b != b'\r')
+pub fn check_link(path: &str) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+pub fn is_binary_file(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|b| *b == 0) || std::str::from_utf8(content).is_err()
 }
 
 pub fn get_filename(path: &str) -> String {
@@ -98,9 +119,19 @@ pub fn rename_file(old_path: &str, new_path: &str) -> Result<(), io::Error> {
 }
 
 pub fn read_file(path: &str) -> Result<String, io::Error> {
+    match String::from_utf8(read_bytes(path)?) {
+        Ok(content) => Ok(content),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to read file: '{}' is not valid UTF-8", path),
+        )),
+    }
+}
+
+pub fn read_bytes(path: &str) -> Result<Vec<u8>, io::Error> {
     if let Ok(mut file) = OpenOptions::new().read(true).open(path) {
-        let mut content = String::new();
-        if let Ok(_) = file.read_to_string(&mut content) {
+        let mut content = Vec::new();
+        if file.read_to_end(&mut content).is_ok() {
             Ok(content)
         } else {
             Err(io::Error::new(
@@ -116,6 +147,25 @@ pub fn read_file(path: &str) -> Result<String, io::Error> {
     }
 }
 
+pub fn read_link(path: &str) -> Result<String, io::Error> {
+    match fs::read_link(path) {
+        Ok(target) => Ok(target.to_string_lossy().into_owned()),
+        Err(e) => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Failed to read link: '{}': {}", path, e),
+        )),
+    }
+}
+
+// Reads what a revision stores for a file: the target of a link, or the content of a file
+pub fn read_entry(path: &str) -> Result<Vec<u8>, io::Error> {
+    if check_link(path) {
+        Ok(read_link(path)?.into_bytes())
+    } else {
+        read_bytes(path)
+    }
+}
+
 pub fn read_struct<T>(path: &str) -> Result<T, serde_json::Error>
 where
     T: DeserializeOwned,
@@ -132,15 +182,22 @@ where
 }
 
 pub fn write_file(path: &str, content: &str) -> Result<(), io::Error> {
+    write_bytes(path, content.as_bytes())
+}
+
+pub fn write_bytes(path: &str, content: &[u8]) -> Result<(), io::Error> {
+    let temp_path = get_temp_path(path);
+
     if let Ok(mut file) = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true) // Ensure truncation
-        .open(path)
+        .open(&temp_path)
     {
-        if let Ok(_) = file.write_all(content.as_bytes()) {
-            Ok(())
+        if file.write_all(content).is_ok() && file.sync_all().is_ok() {
+            commit_temp_file(&temp_path, path)
         } else {
+            let _ = fs::remove_file(&temp_path);
             Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!("Failed to write file: '{}'", path),
@@ -154,6 +211,108 @@ pub fn write_file(path: &str, content: &str) -> Result<(), io::Error> {
     }
 }
 
+// Writes a file piece by piece through `write`, atomically like `write_bytes`
+pub fn write_stream<F>(path: &str, write: F) -> Result<(), io::Error>
+where
+    F: FnOnce(&mut File) -> Result<(), io::Error>,
+{
+    let temp_path = get_temp_path(path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temp_path)
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to open file: '{}': {}", path, e)))?;
+
+    if let Err(e) = write(&mut file).and_then(|_| file.sync_all()) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(io::Error::new(
+            e.kind(),
+            format!("Failed to write file: '{}': {}", path, e),
+        ));
+    }
+
+    commit_temp_file(&temp_path, path)
+}
+
+// Creates a file that must not exist yet; the synced temporary file is hard-linked into place,
+// so other processes never see it empty
+pub fn create_file(path: &str, content: &str) -> Result<(), io::Error> {
+    let temp_path = get_temp_path(path);
+    write_file(&temp_path, content)?;
+
+    let result = fs::hard_link(&temp_path, path);
+    let _ = fs::remove_file(&temp_path);
+    result.map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to create file: '{}': {}", path, e),
+        )
+    })
+}
+
+fn get_temp_path(path: &str) -> String {
+    let parent = get_parent(path);
+    let temp_name = format!(
+        ".{}.tmp.{}.{}",
+        get_filename(path),
+        process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+
+    if parent.is_empty() {
+        temp_name
+    } else {
+        format!("{}/{}", parent, temp_name)
+    }
+}
+
+// Recognizes the temporary files left behind when a write is interrupted
+pub fn is_temp_file(path: &str) -> bool {
+    let file_name = get_filename(path);
+    let mut parts = file_name.rsplitn(4, '.');
+
+    file_name.starts_with('.')
+        && parts
+            .next()
+            .is_some_and(|counter| counter.parse::<usize>().is_ok())
+        && parts.next().is_some_and(|pid| pid.parse::<u32>().is_ok())
+        && parts.next() == Some("tmp")
+}
+
+// Renames a synced temporary file over its destination, then syncs the parent directory
+fn commit_temp_file(temp_path: &str, path: &str) -> Result<(), io::Error> {
+    if let Err(e) = rename(temp_path, path) {
+        let _ = fs::remove_file(temp_path);
+        return Err(io::Error::new(
+            e.kind(),
+            format!("Failed to replace file: '{}': {}", path, e),
+        ));
+    }
+
+    let parent = get_parent(path);
+
+    // Not every platform can open a directory for syncing, so this step is best effort
+    if let Ok(directory) = File::open(if parent.is_empty() { "." } else { &parent }) {
+        let _ = directory.sync_all();
+    }
+
+    Ok(())
+}
+
+pub fn write_link(path: &str, target: &str) -> Result<(), io::Error> {
+    let temp_path = get_temp_path(path);
+
+    if let Err(e) = symlink(target, &temp_path) {
+        return Err(io::Error::new(
+            e.kind(),
+            format!("Failed to write link: '{}': {}", path, e),
+        ));
+    }
+
+    commit_temp_file(&temp_path, path)
+}
+
 pub fn write_struct<T>(path: &str, s: &T) -> Result<(), serde_json::Error>
 where
     T: serde::Serialize,
@@ -171,8 +330,8 @@ where
 
 #[allow(unused)]
 pub fn append_file(path: &str, content: &str) -> Result<(), io::Error> {
-    if let Ok(mut file) = OpenOptions::new().write(true).append(true).open(path) {
-        if let Ok(_) = file.write_all(content.as_bytes()) {
+    if let Ok(mut file) = OpenOptions::new().append(true).open(path) {
+        if file.write_all(content.as_bytes()).is_ok() {
             Ok(())
         } else {
             Err(io::Error::new(
@@ -189,9 +348,19 @@ pub fn append_file(path: &str, content: &str) -> Result<(), io::Error> {
 }
 
 pub fn copy_file(src: &str, dest: &str) -> Result<(), io::Error> {
-    if Path::new(src).is_file() {
-        fs::copy(src, dest)?;
-        Ok(())
+    if check_link(src) {
+        write_link(dest, &read_link(src)?)
+    } else if Path::new(src).is_file() {
+        let temp_path = get_temp_path(dest);
+
+        if let Err(e) = fs::copy(src, &temp_path)
+            .and_then(|_| OpenOptions::new().write(true).open(&temp_path)?.sync_all())
+        {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+
+        commit_temp_file(&temp_path, dest)
     } else {
         Err(io::Error::new(
             io::ErrorKind::NotFound,
@@ -201,7 +370,7 @@ pub fn copy_file(src: &str, dest: &str) -> Result<(), io::Error> {
 }
 
 pub fn delete_file(path: &str) -> Result<(), io::Error> {
-    if Path::new(path).is_file() {
+    if check_file(path) {
         fs::remove_file(path)?;
         Ok(())
     } else {
@@ -11,7 +11,8 @@
 //!
 //! The `delete_directory` function deletes a directory.
 //!
-//! The `list_directory` function lists the contents of a directory.
+//! The `list_directory` function lists the contents of a directory. Symbolic links are listed
+//! as files and never followed, so a link to a directory cannot make a walk loop forever.
 //!
 //! ## Dependencies:
 //! - none
@@ -19,7 +20,7 @@
 //! Author: Anakin (Yuesong Huang), Yifan (Alvin) Jiang
 //! Date: 11/14/2024
 
-use super::b_1_1_file_interaction::{check_file, copy_file};
+use super::b_1_1_file_interaction::{check_file, check_link, copy_file};
 
 use std::fs::{canonicalize, create_dir_all, read_dir, remove_dir, remove_dir_all, rename};
 use std::io;
@@ -153,7 +154,7 @@ fn list_directory_helper(path: &str, full: bool, files: &mut Vec<String>) -> Res
                     Ok(entry) => {
                         let full_path = format!("{}/{}", path, entry.file_name().to_string_lossy());
 
-                        if check_directory(&full_path) {
+                        if !check_link(&full_path) && check_directory(&full_path) {
                             list_directory_helper(&full_path, full, files)?;
                         } else if check_file(&full_path) {
                             if full {
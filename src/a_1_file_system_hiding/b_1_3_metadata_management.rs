@@ -13,16 +13,44 @@
 //!
 //! The `get_last_modified` function gets the last modified time of a file.
 //!
+//! Symbolic links are not followed, so the metadata of a link describes the link itself.
+//!
+//! The `get_file_mode` function gets the mode of a file as revisions record it, either
+//! `REGULAR_FILE_MODE`, `EXECUTABLE_FILE_MODE` or `SYMLINK_MODE`, and the `set_file_mode`
+//! function applies such a mode to a file by setting or clearing its execute bits. The
+//! `write_entry` function writes stored content back with its mode, as a link for `SYMLINK_MODE`.
+//!
+//! The `StatCache` struct remembers the content hash of files together with their size, last
+//! modified time and mode. The `hash_file_cached` function only reads and hashes a file when its
+//! metadata no longer matches the cache, and the `update_stat_cache` function records a hash that
+//! is already known. A file modified shortly before it was cached is always rehashed, since a
+//! later change within the timestamp resolution of the file system would leave its metadata
+//! unchanged. The `load_stat_cache` and `save_stat_cache` functions read and write the cache.
+//!
 //! ## Dependencies:
-//! - none
+//! - serde: For serializing the stat cache.
 //!
 //! Author: Anakin (Yuesong Huang), Yifan (Alvin) Jiang
 //! Date: 11/14/2024
 
-use std::fs::{metadata, Metadata};
+use super::b_1_1_file_interaction::{
+    check_link, read_link, read_struct, write_bytes, write_link, write_struct,
+};
+use super::b_1_4_object_management::{hash_content, hash_file};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{set_permissions, symlink_metadata, Metadata};
 use std::io;
 use std::os::unix::fs::PermissionsExt;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
+
+pub const REGULAR_FILE_MODE: u32 = 0o100644;
+pub const EXECUTABLE_FILE_MODE: u32 = 0o100755;
+pub const SYMLINK_MODE: u32 = 0o120000;
+
+// Files modified this shortly before they were cached may change again without it showing
+const RACY_INTERVAL: Duration = Duration::from_secs(2);
 
 #[derive(Debug)]
 pub struct FileMetadata {
@@ -32,6 +60,20 @@ pub struct FileMetadata {
     pub mode: u32,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatEntry {
+    pub size: u64,                 // Size of the file when it was hashed
+    pub last_modified: SystemTime, // Last modified time of the file when it was hashed
+    pub mode: u32,                 // Mode of the file when it was hashed
+    pub hash: String,              // Hash of the file content
+    pub cached_at: SystemTime,     // When the entry was recorded
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatCache {
+    pub entries: HashMap<String, StatEntry>, // Maps paths to their cached metadata and hash
+}
+
 pub fn get_file_metadata(path: &str) -> Result<FileMetadata, io::Error> {
     let metadata = get_metadata(path)?;
     let file_metadata = FileMetadata {
@@ -52,7 +94,7 @@ pub fn get_file_metadata(path: &str) -> Result<FileMetadata, io::Error> {
 }
 
 fn get_metadata(path: &str) -> Result<Metadata, io::Error> {
-    match metadata(path) {
+    match symlink_metadata(path) {
         Ok(metadata) => Ok(metadata),
         Err(e) => Err(io::Error::new(
             io::ErrorKind::NotFound,
@@ -74,3 +116,120 @@ fn get_last_modified(metadata: &Metadata) -> Result<SystemTime, io::Error> {
 fn get_mode(metadata: &Metadata) -> Result<u32, io::Error> {
     Ok(metadata.permissions().mode() & 0o777_777)
 }
+
+pub fn get_file_mode(path: &str) -> Result<u32, io::Error> {
+    let mode = get_file_metadata(path)?.mode;
+
+    if mode & 0o170000 == SYMLINK_MODE {
+        Ok(SYMLINK_MODE)
+    } else if mode & 0o111 != 0 {
+        Ok(EXECUTABLE_FILE_MODE)
+    } else {
+        Ok(REGULAR_FILE_MODE)
+    }
+}
+
+pub fn set_file_mode(path: &str, mode: u32) -> Result<(), io::Error> {
+    // Links have no permissions of their own
+    if check_link(path) {
+        return Ok(());
+    }
+
+    let mut permissions = get_metadata(path)?.permissions();
+    let current = permissions.mode();
+
+    // Only the execute bits change, and only for those who can read the file
+    let new = if mode & 0o111 != 0 {
+        current | ((current & 0o444) >> 2)
+    } else {
+        current & !0o111
+    };
+
+    if new == current {
+        return Ok(());
+    }
+
+    permissions.set_mode(new);
+    set_permissions(path, permissions).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to set mode of '{}' to {:o}: {}", path, mode, e),
+        )
+    })
+}
+
+// Writes content as stored in a revision: the target of a link, or the content of a file
+pub fn write_entry(path: &str, content: &[u8], mode: u32) -> Result<(), io::Error> {
+    if mode == SYMLINK_MODE {
+        write_link(path, &String::from_utf8_lossy(content))
+    } else {
+        write_bytes(path, content)?;
+        set_file_mode(path, mode)
+    }
+}
+
+pub fn load_stat_cache(cache_path: &str) -> StatCache {
+    // A missing or unreadable cache only means every file is hashed again
+    read_struct(cache_path).unwrap_or_default()
+}
+
+pub fn save_stat_cache(cache_path: &str, cache: &StatCache) -> Result<(), io::Error> {
+    write_struct(cache_path, cache)?;
+    Ok(())
+}
+
+fn is_cached(entry: &StatEntry, metadata: &FileMetadata) -> bool {
+    entry.size == metadata.size
+        && entry.last_modified == metadata.last_modified
+        && entry.mode == metadata.mode
+        && entry
+            .cached_at
+            .duration_since(metadata.last_modified)
+            .is_ok_and(|elapsed| elapsed >= RACY_INTERVAL)
+}
+
+// Returns the hash of the file at `file_path`, cached under `key`, rehashing it only if needed
+pub fn hash_file_cached(
+    cache: &mut StatCache,
+    file_path: &str,
+    key: &str,
+) -> Result<String, io::Error> {
+    let metadata = get_file_metadata(file_path)?;
+
+    if let Some(entry) = cache.entries.get(key) {
+        if is_cached(entry, &metadata) {
+            return Ok(entry.hash.clone());
+        }
+    }
+
+    let hash = if check_link(file_path) {
+        hash_content(read_link(file_path)?.as_bytes())
+    } else {
+        hash_file(file_path)? // Large files are hashed without reading them at once
+    };
+    insert_stat_entry(cache, key, metadata, &hash);
+    Ok(hash)
+}
+
+// Records `hash` as the content of the file at `file_path`, or forgets the file if it is gone
+pub fn update_stat_cache(cache: &mut StatCache, file_path: &str, key: &str, hash: &str) {
+    match get_file_metadata(file_path) {
+        Ok(metadata) => insert_stat_entry(cache, key, metadata, hash),
+        Err(_) => {
+            cache.entries.remove(key);
+        }
+    }
+}
+
+fn insert_stat_entry(cache: &mut StatCache, key: &str, metadata: FileMetadata, hash: &str) {
+    cache.entries.insert(
+        key.to_string(),
+        StatEntry {
+            size: metadata.size,
+            last_modified: metadata.last_modified,
+            mode: metadata.mode,
+            hash: hash.to_string(),
+            cached_at: SystemTime::now(),
+        },
+    );
+}
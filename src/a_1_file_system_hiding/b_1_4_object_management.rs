@@ -0,0 +1,230 @@
+// days_dvcs/src/a_1_file_system_hiding/b_1_4_object_management.rs
+//
+//! B.1.4 Object Management
+//! This component is responsible for storing file contents in a content-addressed
+//! object store, where every blob is keyed by the hash of its content.
+//!
+//! Parent Module: A.1 File System Hiding
+//!
+//! ## Usage:
+//! The `hash_content` function computes the hash used as the key of a blob, and the `hash_file`
+//! function computes the same hash for a file without reading it into memory at once.
+//!
+//! The `write_object` function stores a blob once and returns its hash. Blobs are compressed
+//! with zlib unless compression would not make them smaller.
+//!
+//! The `read_object` function reads the blob stored under a hash, decompressing it if needed.
+//! Blobs written uncompressed, including those of older repositories, are read as they are.
+//! Large files stored as chunks (see `B.1.7 Chunk Management`) are reassembled, while the
+//! `read_stored_object` function returns any blob exactly as it is stored, so a chunked one
+//! reads as its manifest.
+//!
+//! The `verify_object` function checks that a stored blob still matches its hash. The
+//! `check_stored_object` function does the same for a blob already read, and
+//! `write_stored_object` stores such a blob again as a loose object.
+//!
+//! Blobs that were moved into a pack (see `B.1.6 Pack Management`) are found by `check_object`
+//! and `read_object` as well. The `list_loose_objects` function lists the blobs that are stored
+//! as individual files, and `get_object_location` describes where a blob is stored.
+//!
+//! ## Dependencies:
+//! - sha2: For SHA-256 content hashing.
+//! - flate2: For zlib compression of stored blobs.
+
+use super::b_1_1_file_interaction::{
+    check_file, get_parent, is_temp_file, read_bytes, write_bytes,
+};
+use super::b_1_2_directory_interaction::{check_directory, create_directory, list_directory};
+use super::b_1_6_pack_management::{find_packed_object, get_pack_path, read_packed_object};
+use super::b_1_7_chunk_management::{
+    decode_chunk_manifest, hash_chunked_object, read_chunked_object,
+};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+pub fn hash_content(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+pub fn hash_file(path: &str) -> Result<String, io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn get_object_path(objects_path: &str, hash: &str) -> String {
+    if hash.len() > 2 {
+        format!("{}/{}/{}", objects_path, &hash[..2], &hash[2..])
+    } else {
+        format!("{}/{}", objects_path, hash)
+    }
+}
+
+fn compress_content(content: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(content)?;
+    encoder.finish()
+}
+
+// Returns the original content of a stored blob, which is either compressed or stored as is
+fn decompress_content(stored: Vec<u8>, hash: &str) -> Vec<u8> {
+    let mut content = Vec::new();
+
+    if ZlibDecoder::new(stored.as_slice())
+        .read_to_end(&mut content)
+        .is_ok()
+        && hash_content(&content) == hash
+    {
+        content
+    } else {
+        stored
+    }
+}
+
+pub fn check_object(objects_path: &str, hash: &str) -> bool {
+    check_file(&get_object_path(objects_path, hash))
+        || find_packed_object(objects_path, hash).is_some()
+}
+
+// Lists the hashes of blobs stored as individual files, leaving out packs and temporary files
+pub fn list_loose_objects(objects_path: &str) -> Result<Vec<String>, io::Error> {
+    let mut hashes = Vec::new();
+
+    if !check_directory(objects_path) {
+        return Ok(hashes);
+    }
+
+    for prefix in list_directory(objects_path, false, false)? {
+        let prefix_path = format!("{}/{}", objects_path, prefix);
+
+        if prefix.len() != 2 || !check_directory(&prefix_path) {
+            continue;
+        }
+
+        for object_file in list_directory(&prefix_path, false, false)? {
+            if !is_temp_file(&object_file) {
+                hashes.push(format!("{}{}", prefix, object_file));
+            }
+        }
+    }
+
+    hashes.sort();
+    Ok(hashes)
+}
+
+// Describes where a blob is stored, for messages that must point at the data on disk
+pub fn get_object_location(objects_path: &str, hash: &str) -> String {
+    let object_path = get_object_path(objects_path, hash);
+
+    if check_file(&object_path) {
+        return object_path;
+    }
+
+    match find_packed_object(objects_path, hash) {
+        Some(pack) => format!("{}#{}", get_pack_path(objects_path, &pack), hash),
+        None => object_path,
+    }
+}
+
+pub fn write_object(objects_path: &str, content: &[u8]) -> Result<String, io::Error> {
+    let hash = hash_content(content);
+    let object_path = get_object_path(objects_path, &hash);
+
+    if !check_file(&object_path) {
+        let object_dir = get_parent(&object_path);
+
+        if !check_directory(&object_dir) {
+            create_directory(&object_dir)?;
+        }
+
+        let compressed = compress_content(content)?;
+
+        if compressed.len() < content.len() {
+            write_bytes(&object_path, &compressed)?;
+        } else {
+            write_bytes(&object_path, content)?;
+        }
+    }
+
+    Ok(hash)
+}
+
+// Checks a blob as `read_stored_object` returns it against its hash. A chunked blob only has its
+// manifest checked, as its chunks are blobs of their own
+pub fn check_stored_object(hash: &str, stored: &[u8]) -> bool {
+    hash_content(stored) == hash
+        || decode_chunk_manifest(stored).is_some_and(|manifest| manifest.hash == hash)
+}
+
+// Stores a blob as `read_stored_object` returned it as a loose object, e.g. to move it out of a
+// pack. A chunk manifest is stored as it is, which is how manifests are written
+pub fn write_stored_object(objects_path: &str, hash: &str, stored: &[u8]) -> Result<(), io::Error> {
+    if !check_stored_object(hash, stored) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Content does not match object '{}'", hash),
+        ));
+    }
+
+    if hash_content(stored) == hash {
+        write_object(objects_path, stored)?;
+    } else {
+        let object_path = get_object_path(objects_path, hash);
+        create_directory(&get_parent(&object_path))?;
+        write_bytes(&object_path, stored)?;
+    }
+
+    Ok(())
+}
+
+pub fn read_stored_object(objects_path: &str, hash: &str) -> Result<Vec<u8>, io::Error> {
+    let object_path = get_object_path(objects_path, hash);
+
+    if check_file(&object_path) {
+        Ok(decompress_content(read_bytes(&object_path)?, hash))
+    } else if find_packed_object(objects_path, hash).is_some() {
+        read_packed_object(objects_path, hash)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Object '{}' not found in '{}'", hash, objects_path),
+        ))
+    }
+}
+
+pub fn read_object(objects_path: &str, hash: &str) -> Result<Vec<u8>, io::Error> {
+    let stored = read_stored_object(objects_path, hash)?;
+
+    match decode_chunk_manifest(&stored).filter(|manifest| manifest.hash == hash) {
+        Some(manifest) => read_chunked_object(objects_path, &manifest),
+        None => Ok(stored),
+    }
+}
+
+pub fn verify_object(objects_path: &str, hash: &str) -> Result<(), io::Error> {
+    let stored = read_stored_object(objects_path, hash)?;
+    let actual_hash = match decode_chunk_manifest(&stored).filter(|manifest| manifest.hash == hash)
+    {
+        Some(manifest) => hash_chunked_object(objects_path, &manifest)?,
+        None => hash_content(&stored),
+    };
+
+    if actual_hash == hash {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Object '{}' is corrupt: content hashes to '{}'",
+                get_object_location(objects_path, hash),
+                actual_hash
+            ),
+        ))
+    }
+}
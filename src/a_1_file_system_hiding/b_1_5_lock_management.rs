@@ -0,0 +1,143 @@
+// days_dvcs/src/a_1_file_system_hiding/b_1_5_lock_management.rs
+//
+//! B.1.5 Lock Management
+//! This component is responsible for advisory lock files that keep two processes from
+//! modifying the same repository at the same time.
+//!
+//! Parent Module: A.1 File System Hiding
+//!
+//! ## Usage:
+//! The `acquire_lock` function creates a lock file holding the current process ID and returns
+//! a `LockGuard` that removes it when dropped. A process that already holds the lock can acquire
+//! it again, so nested operations (e.g. a merge that commits) do not deadlock; only the outermost
+//! guard `is_owned` and removes the lock.
+//!
+//! The `get_lock_holder` function reads the process ID stored in a lock file.
+//!
+//! The `is_process_alive` function checks whether the process holding a lock is still running.
+//!
+//! The `clear_lock` function removes a stale lock file left behind by a process that died.
+//!
+//! ## Dependencies:
+//! - none
+
+use super::b_1_1_file_interaction::{check_file, create_file, delete_file, read_file};
+use super::b_1_2_directory_interaction::check_directory;
+
+use std::io;
+use std::process::{self, Command, Stdio};
+
+#[derive(Debug)]
+pub struct LockGuard {
+    path: String, // Path of the lock file
+    owned: bool,  // Whether this guard created the lock and must remove it
+}
+
+impl LockGuard {
+    pub fn is_owned(&self) -> bool {
+        self.owned
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        if self.owned && check_file(&self.path) {
+            let _ = delete_file(&self.path);
+        }
+    }
+}
+
+pub fn acquire_lock(lock_path: &str) -> Result<LockGuard, io::Error> {
+    // The lock file appears with the process ID already in it, so it never looks unowned
+    match create_file(lock_path, &process::id().to_string()) {
+        Ok(()) => Ok(LockGuard {
+            path: lock_path.to_string(),
+            owned: true,
+        }),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => match get_lock_holder(lock_path) {
+            Some(pid) if pid == process::id() => Ok(LockGuard {
+                path: lock_path.to_string(),
+                owned: false,
+            }),
+            Some(pid) => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!(
+                    "Repository is locked by process {}{} ('{}'). If no other days_dvcs process is running, clear the lock with 'unlock'",
+                    pid,
+                    if is_process_alive(pid) { "" } else { ", which is no longer running" },
+                    lock_path
+                ),
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!(
+                    "Repository is locked by an unknown process ('{}'). If no other days_dvcs process is running, clear the lock with 'unlock --force'",
+                    lock_path
+                ),
+            )),
+        },
+        Err(e) => Err(io::Error::new(
+            e.kind(),
+            format!("Failed to create lock file '{}': {}", lock_path, e),
+        )),
+    }
+}
+
+pub fn get_lock_holder(lock_path: &str) -> Option<u32> {
+    read_file(lock_path).ok()?.trim().parse().ok()
+}
+
+pub fn is_process_alive(pid: u32) -> bool {
+    // Linux lists every running process under /proc; elsewhere ask `kill -0`
+    if check_directory("/proc/self") {
+        return check_directory(&format!("/proc/{}", pid));
+    }
+
+    let status = Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success());
+
+    // If liveness cannot be determined, assume the holder is still running
+    status.unwrap_or(true)
+}
+
+pub fn clear_lock(lock_path: &str, force: bool) -> Result<Option<u32>, io::Error> {
+    if !check_file(lock_path) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No lock file found at '{}'", lock_path),
+        ));
+    }
+
+    let holder = get_lock_holder(lock_path);
+
+    if !force {
+        match holder {
+            Some(pid) if pid != process::id() && is_process_alive(pid) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!(
+                        "Lock '{}' is held by running process {}. Use '--force' to clear it anyway",
+                        lock_path, pid
+                    ),
+                ));
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Lock '{}' does not name a process. Use '--force' to clear it anyway",
+                        lock_path
+                    ),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    delete_file(lock_path)?;
+    Ok(holder)
+}
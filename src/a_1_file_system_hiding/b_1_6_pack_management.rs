@@ -0,0 +1,443 @@
+// days_dvcs/src/a_1_file_system_hiding/b_1_6_pack_management.rs
+//
+//! B.1.6 Pack Management
+//! This component is responsible for pack files, which store many blobs of the object store in
+//! a single file, each one either in full or as a delta against another blob in the same pack.
+//!
+//! Parent Module: A.1 File System Hiding
+//!
+//! ## Usage:
+//! Packs live in the `pack` directory of an object store. Every `pack-<hash>.pack` file, named
+//! after the hashes of the blobs it holds, has a `pack-<hash>.idx` index that maps each blob hash to its offset, length and delta base, so any
+//! blob can be read without scanning the pack. The index is written after the pack, so a pack
+//! without an index is never read.
+//!
+//! The `write_pack` function reads blobs from one object store and writes them as a pack into
+//! another (or the same) store. Each blob may name a base blob, typically an earlier version of
+//! the same file; it is stored as a delta when the base comes earlier in the same pack and the
+//! delta is much smaller than the blob itself. Blobs are packed as they are stored, so a large
+//! file stored as chunks is packed as its manifest, next to whichever of its chunks are listed.
+//! The pack is streamed to disk entry by entry, holding only the blobs later entries use as bases.
+//!
+//! The `list_packs`, `load_pack_index` and `delete_pack` functions manage the packs of a store,
+//! and `get_pack_path` and `get_index_path` name the files of a pack.
+//!
+//! The `find_packed_object` function locates a blob in the packs of a store, and the
+//! `read_packed_object` function reads it back, applying deltas as needed. Both parse each index
+//! once per process and reuse it until the index file changes. The `read_pack_object` function
+//! reads a blob from one given pack, e.g. to check every entry of a pack.
+//!
+//! The `create_delta` and `apply_delta` functions implement the copy/insert delta encoding.
+//!
+//! ## Dependencies:
+//! - flate2: For zlib compression of pack entries.
+//! - serde: For serializing pack indexes.
+
+use super::b_1_1_file_interaction::{
+    check_file, delete_file, read_struct, write_stream, write_struct,
+};
+use super::b_1_2_directory_interaction::{check_directory, create_directory, list_directory};
+use super::b_1_4_object_management::{hash_content, read_stored_object};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+const PACK_HEADER: &[u8] = b"DVCSPACK";
+const BLOCK_SIZE: usize = 16; // Smallest run of bytes a delta copies from its base
+const MAX_DELTA_DEPTH: usize = 16; // Longest chain of deltas a read has to apply
+
+const DELTA_INSERT: u8 = 0;
+const DELTA_COPY: u8 = 1;
+
+// Parsed indexes by path, with the modification time and size of the file they were read from
+type IndexCache = HashMap<String, ((SystemTime, u64), Arc<PackIndex>)>;
+
+static INDEX_CACHE: OnceLock<Mutex<IndexCache>> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackEntry {
+    pub offset: u64,          // Position of the compressed entry in the pack
+    pub length: u64,          // Length of the compressed entry
+    pub base: Option<String>, // Blob the entry is a delta against, if any
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackIndex {
+    pub entries: HashMap<String, PackEntry>, // Maps blob hashes to their entries
+}
+
+pub fn get_packs_path(objects_path: &str) -> String {
+    format!("{}/pack", objects_path)
+}
+
+pub fn get_pack_path(objects_path: &str, pack: &str) -> String {
+    format!("{}/{}.pack", get_packs_path(objects_path), pack)
+}
+
+pub fn get_index_path(objects_path: &str, pack: &str) -> String {
+    format!("{}/{}.idx", get_packs_path(objects_path), pack)
+}
+
+pub fn list_packs(objects_path: &str) -> Result<Vec<String>, io::Error> {
+    let packs_path = get_packs_path(objects_path);
+
+    if !check_directory(&packs_path) {
+        return Ok(Vec::new());
+    }
+
+    let mut packs: Vec<String> = list_directory(&packs_path, false, false)?
+        .iter()
+        .filter_map(|file| file.strip_suffix(".idx"))
+        .filter(|pack| check_file(&get_pack_path(objects_path, pack)))
+        .map(|pack| pack.to_string())
+        .collect();
+    packs.sort();
+    Ok(packs)
+}
+
+pub fn load_pack_index(objects_path: &str, pack: &str) -> Result<PackIndex, io::Error> {
+    let index: PackIndex = read_struct(&get_index_path(objects_path, pack))?;
+    Ok(index)
+}
+
+// Loads an index through a cache kept for the life of the process, so looking up many objects
+// parses each index once. An entry is reloaded when its index file is replaced or removed
+fn load_cached_pack_index(objects_path: &str, pack: &str) -> Result<Arc<PackIndex>, io::Error> {
+    let index_path = get_index_path(objects_path, pack);
+    let metadata = fs::metadata(&index_path)?;
+    let stamp = (metadata.modified()?, metadata.len());
+    let mut cache = INDEX_CACHE
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if let Some((cached_stamp, index)) = cache.get(&index_path) {
+        if *cached_stamp == stamp {
+            return Ok(Arc::clone(index));
+        }
+    }
+
+    let index = Arc::new(load_pack_index(objects_path, pack)?);
+    cache.insert(index_path, (stamp, Arc::clone(&index)));
+    Ok(index)
+}
+
+pub fn delete_pack(objects_path: &str, pack: &str) -> Result<(), io::Error> {
+    // The index goes first so the pack is never read while it is being removed
+    delete_file(&get_index_path(objects_path, pack))?;
+    delete_file(&get_pack_path(objects_path, pack))
+}
+
+pub fn find_packed_object(objects_path: &str, hash: &str) -> Option<String> {
+    list_packs(objects_path).ok()?.into_iter().find(|pack| {
+        load_cached_pack_index(objects_path, pack)
+            .is_ok_and(|index| index.entries.contains_key(hash))
+    })
+}
+
+pub fn read_packed_object(objects_path: &str, hash: &str) -> Result<Vec<u8>, io::Error> {
+    let pack = find_packed_object(objects_path, hash).ok_or(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Object '{}' not found in '{}'", hash, objects_path),
+    ))?;
+    read_pack_object(objects_path, &pack, hash)
+}
+
+// Reads a blob from one given pack, even if another pack or a loose object also holds it
+pub fn read_pack_object(objects_path: &str, pack: &str, hash: &str) -> Result<Vec<u8>, io::Error> {
+    let index = load_cached_pack_index(objects_path, pack)?;
+    let mut file = File::open(get_pack_path(objects_path, pack))?;
+
+    read_pack_entry(&mut file, &index, hash, 0).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Failed to read object '{}' from pack '{}': {}",
+                hash,
+                get_pack_path(objects_path, pack),
+                e
+            ),
+        )
+    })
+}
+
+fn read_pack_entry(
+    file: &mut File,
+    index: &PackIndex,
+    hash: &str,
+    depth: usize,
+) -> Result<Vec<u8>, io::Error> {
+    let entry = index.entries.get(hash).ok_or(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Delta base '{}' is missing", hash),
+    ))?;
+
+    if depth > MAX_DELTA_DEPTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Delta chain is too long",
+        ));
+    }
+
+    let mut compressed = vec![0; entry.length as usize];
+    file.seek(SeekFrom::Start(entry.offset))?;
+    file.read_exact(&mut compressed)?;
+
+    let mut content = Vec::new();
+    ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut content)?;
+
+    match &entry.base {
+        Some(base) => {
+            let base_content = read_pack_entry(file, index, base, depth + 1)?;
+            apply_delta(&base_content, &content)
+        }
+        None => Ok(content),
+    }
+}
+
+// Writes the given blobs, each with an optional delta base, as a single pack and returns its name.
+// Entries are compressed and written one at a time, and a blob is only kept in memory while a
+// later entry may still be stored as a delta against it
+pub fn write_pack(
+    src: &str,
+    dest: &str,
+    objects: &[(String, Option<String>)],
+) -> Result<Option<String>, io::Error> {
+    let mut listed = HashSet::new();
+    let objects: Vec<&(String, Option<String>)> = objects
+        .iter()
+        .filter(|(hash, _)| listed.insert(hash.as_str()))
+        .collect();
+
+    if objects.is_empty() {
+        return Ok(None);
+    }
+
+    // Deltas only refer to blobs earlier in the same pack, so only those are candidate bases
+    let mut base_uses: HashMap<&str, usize> = HashMap::new();
+    let mut earlier = HashSet::new();
+
+    for (hash, base) in objects.iter() {
+        if let Some(base) = base.as_deref().filter(|base| earlier.contains(base)) {
+            *base_uses.entry(base).or_default() += 1;
+        }
+
+        earlier.insert(hash.as_str());
+    }
+
+    // A pack is named after the blobs it holds, which determine its content
+    let hashes: Vec<&str> = objects.iter().map(|(hash, _)| hash.as_str()).collect();
+    let name = format!("pack-{}", hash_content(hashes.join("\n").as_bytes()));
+    let packs_path = get_packs_path(dest);
+
+    if !check_directory(&packs_path) {
+        create_directory(&packs_path)?;
+    }
+
+    let mut index = PackIndex {
+        entries: HashMap::new(),
+    };
+    let mut bases: HashMap<&str, (Vec<u8>, usize)> = HashMap::new();
+
+    write_stream(&get_pack_path(dest, &name), |file| {
+        file.write_all(PACK_HEADER)?;
+        let mut offset = PACK_HEADER.len() as u64;
+
+        for (hash, base) in objects.iter() {
+            let content = read_stored_object(src, hash)?;
+            let mut delta = None;
+            let mut depth = 0;
+
+            if let Some(base) = base.as_deref() {
+                if let Some((base_content, base_depth)) = bases.get(base) {
+                    // Chains stay short, and a delta must be worth it
+                    if *base_depth < MAX_DELTA_DEPTH {
+                        let candidate = create_delta(base_content, &content);
+
+                        if candidate.len() < content.len() / 2 {
+                            delta = Some(candidate);
+                            depth = base_depth + 1;
+                        }
+                    }
+
+                    let uses = base_uses.get_mut(base).map_or(0, |uses| {
+                        *uses -= 1;
+                        *uses
+                    });
+
+                    if uses == 0 {
+                        bases.remove(base);
+                    }
+                }
+            }
+
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(delta.as_deref().unwrap_or(&content))?;
+            let compressed = encoder.finish()?;
+            file.write_all(&compressed)?;
+
+            index.entries.insert(
+                hash.clone(),
+                PackEntry {
+                    offset,
+                    length: compressed.len() as u64,
+                    base: delta.and(base.clone()),
+                },
+            );
+            offset += compressed.len() as u64;
+
+            if base_uses.contains_key(hash.as_str()) {
+                bases.insert(hash, (content, depth));
+            }
+        }
+
+        Ok(())
+    })?;
+
+    write_struct(&get_index_path(dest, &name), &index)?;
+    Ok(Some(name))
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        output.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+
+    output.push(value as u8);
+}
+
+fn read_varint(input: &[u8], position: &mut usize) -> Result<usize, io::Error> {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = *input.get(*position).ok_or(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Delta ends unexpectedly",
+        ))?;
+        *position += 1;
+
+        if shift >= usize::BITS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Delta contains an invalid length",
+            ));
+        }
+
+        value |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn flush_insert(delta: &mut Vec<u8>, insert: &mut Vec<u8>) {
+    if !insert.is_empty() {
+        delta.push(DELTA_INSERT);
+        write_varint(delta, insert.len());
+        delta.append(insert);
+    }
+}
+
+// Encodes `target` as instructions that copy ranges of `base` or insert new bytes
+pub fn create_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, base.len());
+    write_varint(&mut delta, target.len());
+
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+
+    for offset in (0..base.len().saturating_sub(BLOCK_SIZE - 1)).step_by(BLOCK_SIZE) {
+        blocks
+            .entry(&base[offset..offset + BLOCK_SIZE])
+            .or_insert(offset);
+    }
+
+    let mut insert = Vec::new();
+    let mut position = 0;
+
+    while position < target.len() {
+        let matched = target
+            .get(position..position + BLOCK_SIZE)
+            .and_then(|block| blocks.get(block));
+
+        match matched {
+            Some(&offset) => {
+                let mut length = BLOCK_SIZE;
+
+                while offset + length < base.len()
+                    && position + length < target.len()
+                    && base[offset + length] == target[position + length]
+                {
+                    length += 1;
+                }
+
+                flush_insert(&mut delta, &mut insert);
+                delta.push(DELTA_COPY);
+                write_varint(&mut delta, offset);
+                write_varint(&mut delta, length);
+                position += length;
+            }
+            None => {
+                insert.push(target[position]);
+                position += 1;
+            }
+        }
+    }
+
+    flush_insert(&mut delta, &mut insert);
+    delta
+}
+
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut position = 0;
+
+    if read_varint(delta, &mut position)? != base.len() {
+        return Err(invalid("Delta does not apply to this base"));
+    }
+
+    let target_length = read_varint(delta, &mut position)?;
+    let mut target = Vec::with_capacity(target_length);
+
+    while position < delta.len() {
+        let instruction = delta[position];
+        position += 1;
+
+        match instruction {
+            DELTA_COPY => {
+                let offset = read_varint(delta, &mut position)?;
+                let length = read_varint(delta, &mut position)?;
+                let range = base
+                    .get(offset..offset.saturating_add(length))
+                    .ok_or(invalid("Delta copies past the end of its base"))?;
+                target.extend_from_slice(range);
+            }
+            DELTA_INSERT => {
+                let length = read_varint(delta, &mut position)?;
+                let range = delta
+                    .get(position..position.saturating_add(length))
+                    .ok_or(invalid("Delta ends unexpectedly"))?;
+                target.extend_from_slice(range);
+                position += length;
+            }
+            _ => return Err(invalid("Delta contains an unknown instruction")),
+        }
+    }
+
+    if target.len() != target_length {
+        return Err(invalid("Delta produced the wrong length"));
+    }
+
+    Ok(target)
+}
@@ -0,0 +1,282 @@
+// days_dvcs/src/a_1_file_system_hiding/b_1_7_chunk_management.rs
+//
+//! B.1.7 Chunk Management
+//! This component is responsible for storing large files as content-defined chunks, so that a
+//! file is never held in memory as a whole and unchanged parts of it are stored only once.
+//!
+//! Parent Module: A.1 File System Hiding
+//!
+//! ## Usage:
+//! A chunked file is split where a rolling hash of its content hits a boundary pattern, so an
+//! edit only changes the chunks around it. Every chunk is an ordinary blob of the object store
+//! (see `B.1.4 Object Management`), and the file itself is stored under the hash of its whole
+//! content as a `ChunkManifest` that lists its chunks in order. Manifests are found by hash like
+//! any other blob, and `read_object` reassembles them transparently.
+//!
+//! The `write_chunked_object` function streams a file into the object store as chunks and
+//! returns its manifest, and the `write_object_file` function streams any blob back into a file.
+//!
+//! The `write_chunk_manifest` and `read_chunk_manifest` functions write and read a manifest as a
+//! file on its own, which stands in for the content of a large file (e.g. in a staging area).
+//! The `read_object_manifest` function reads the manifest stored under a hash, if the blob is
+//! chunked, and `decode_chunk_manifest` recognizes a manifest among stored bytes.
+//!
+//! The `read_chunked_object` and `hash_chunked_object` functions reassemble and rehash the
+//! content of a manifest, checking every chunk on the way.
+//!
+//! ## Dependencies:
+//! - serde: For serializing chunk manifests.
+//! - sha2: For hashing the content of a file while it is chunked.
+
+use super::b_1_1_file_interaction::{check_file, get_parent, write_bytes, write_stream};
+use super::b_1_2_directory_interaction::{check_directory, create_directory};
+use super::b_1_3_metadata_management::{set_file_mode, write_entry, SYMLINK_MODE};
+use super::b_1_4_object_management::{
+    check_object, get_object_path, hash_content, read_object, write_object,
+};
+use super::b_1_6_pack_management::read_packed_object;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+pub const DEFAULT_LARGE_FILE_THRESHOLD: u64 = 8 * 1024 * 1024; // Files this large are chunked
+
+const CHUNK_MANIFEST_HEADER: &[u8] = b"DVCSCHUNKS\n";
+const MIN_CHUNK_SIZE: usize = 64 * 1024;
+const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+const CHUNK_BOUNDARY_MASK: u64 = (1 << 18) - 1; // Boundaries fall every 256 KiB on average
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+// Random values the rolling hash mixes in for each byte, generated with SplitMix64
+const GEAR: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+
+    while i < 256 {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut value = state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+        table[i] = value ^ (value >> 31);
+        i += 1;
+    }
+
+    table
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    pub hash: String,        // Hash of the whole content
+    pub size: u64,           // Size of the whole content
+    pub chunks: Vec<String>, // Hashes of the chunks, in order
+}
+
+fn encode_chunk_manifest(manifest: &ChunkManifest) -> Result<Vec<u8>, io::Error> {
+    let mut encoded = CHUNK_MANIFEST_HEADER.to_vec();
+    encoded.extend(serde_json::to_vec(manifest)?);
+    Ok(encoded)
+}
+
+pub fn decode_chunk_manifest(stored: &[u8]) -> Option<ChunkManifest> {
+    serde_json::from_slice(stored.strip_prefix(CHUNK_MANIFEST_HEADER)?).ok()
+}
+
+pub fn write_chunk_manifest(path: &str, manifest: &ChunkManifest) -> Result<(), io::Error> {
+    write_bytes(path, &encode_chunk_manifest(manifest)?)
+}
+
+// Reads the manifest a file holds, looking at no more than its header if it holds none
+pub fn read_chunk_manifest(path: &str) -> Option<ChunkManifest> {
+    let mut file = File::open(path).ok()?;
+    let mut header = [0; CHUNK_MANIFEST_HEADER.len()];
+    file.read_exact(&mut header).ok()?;
+
+    if header != CHUNK_MANIFEST_HEADER {
+        return None;
+    }
+
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+// Returns the manifest stored under a hash, or None if the blob is not chunked
+pub fn read_object_manifest(objects_path: &str, hash: &str) -> Option<ChunkManifest> {
+    let object_path = get_object_path(objects_path, hash);
+    let manifest = if check_file(&object_path) {
+        read_chunk_manifest(&object_path)
+    } else {
+        decode_chunk_manifest(&read_packed_object(objects_path, hash).ok()?)
+    };
+
+    manifest.filter(|manifest| manifest.hash == hash)
+}
+
+// Splits content into chunks at the positions where the rolling hash hits the boundary mask
+struct Chunker {
+    chunk: Vec<u8>,
+    fingerprint: u64,
+}
+
+impl Chunker {
+    fn new() -> Self {
+        Chunker {
+            chunk: Vec::with_capacity(MAX_CHUNK_SIZE),
+            fingerprint: 0,
+        }
+    }
+
+    // Adds a byte to the current chunk and returns whether the chunk ends after it
+    fn push(&mut self, byte: u8) -> bool {
+        self.chunk.push(byte);
+        self.fingerprint = (self.fingerprint << 1).wrapping_add(GEAR[byte as usize]);
+
+        self.chunk.len() >= MAX_CHUNK_SIZE
+            || (self.chunk.len() >= MIN_CHUNK_SIZE && self.fingerprint & CHUNK_BOUNDARY_MASK == 0)
+    }
+
+    fn take(&mut self) -> Vec<u8> {
+        self.fingerprint = 0;
+        std::mem::replace(&mut self.chunk, Vec::with_capacity(MAX_CHUNK_SIZE))
+    }
+}
+
+// Stores a file as chunks and a manifest under the hash of its content, reading it piece by piece
+pub fn write_chunked_object(
+    objects_path: &str,
+    file_path: &str,
+) -> Result<ChunkManifest, io::Error> {
+    let mut file = File::open(file_path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to open file: '{}': {}", file_path, e),
+        )
+    })?;
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    let mut hasher = Sha256::new();
+    let mut chunker = Chunker::new();
+    let mut chunks = Vec::new();
+    let mut size = 0;
+
+    loop {
+        let length = file.read(&mut buffer)?;
+
+        if length == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..length]);
+        size += length as u64;
+
+        for &byte in &buffer[..length] {
+            if chunker.push(byte) {
+                chunks.push(write_object(objects_path, &chunker.take())?);
+            }
+        }
+    }
+
+    let last_chunk = chunker.take();
+
+    if !last_chunk.is_empty() {
+        chunks.push(write_object(objects_path, &last_chunk)?);
+    }
+
+    let manifest = ChunkManifest {
+        hash: format!("{:x}", hasher.finalize()),
+        size,
+        chunks,
+    };
+
+    // The manifest is stored as is, so it is recognized without decompressing it
+    if !check_object(objects_path, &manifest.hash) {
+        let object_path = get_object_path(objects_path, &manifest.hash);
+        let object_dir = get_parent(&object_path);
+
+        if !check_directory(&object_dir) {
+            create_directory(&object_dir)?;
+        }
+
+        write_chunk_manifest(&object_path, &manifest)?;
+    }
+
+    Ok(manifest)
+}
+
+// Reads every chunk of a manifest, checking it against its hash, and hands it to `consume`
+fn read_chunks<F>(
+    objects_path: &str,
+    manifest: &ChunkManifest,
+    mut consume: F,
+) -> Result<(), io::Error>
+where
+    F: FnMut(&[u8]) -> Result<(), io::Error>,
+{
+    for chunk_hash in manifest.chunks.iter() {
+        let chunk = read_object(objects_path, chunk_hash)?;
+
+        if hash_content(&chunk) != *chunk_hash {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Chunk '{}' of object '{}' is corrupt",
+                    chunk_hash, manifest.hash
+                ),
+            ));
+        }
+
+        consume(&chunk)?;
+    }
+
+    Ok(())
+}
+
+pub fn read_chunked_object(
+    objects_path: &str,
+    manifest: &ChunkManifest,
+) -> Result<Vec<u8>, io::Error> {
+    let mut content = Vec::with_capacity(manifest.size as usize);
+
+    read_chunks(objects_path, manifest, |chunk| {
+        content.extend_from_slice(chunk);
+        Ok(())
+    })?;
+
+    Ok(content)
+}
+
+// Returns the hash of the content a manifest describes, reading one chunk at a time
+pub fn hash_chunked_object(
+    objects_path: &str,
+    manifest: &ChunkManifest,
+) -> Result<String, io::Error> {
+    let mut hasher = Sha256::new();
+
+    read_chunks(objects_path, manifest, |chunk| {
+        hasher.update(chunk);
+        Ok(())
+    })?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Writes the blob stored under a hash into a file with the given mode, one chunk at a time if
+// the blob is chunked
+pub fn write_object_file(
+    objects_path: &str,
+    hash: &str,
+    path: &str,
+    mode: u32,
+) -> Result<(), io::Error> {
+    match read_object_manifest(objects_path, hash) {
+        Some(manifest) if mode != SYMLINK_MODE => {
+            write_stream(path, |file| {
+                read_chunks(objects_path, &manifest, |chunk| file.write_all(chunk))
+            })?;
+            set_file_mode(path, mode)
+        }
+        _ => write_entry(path, &read_object(objects_path, hash)?, mode),
+    }
+}
@@ -7,4 +7,12 @@ pub mod b_1_2_directory_interaction;
 #[allow(unused)]
 pub mod b_1_3_metadata_management;
 
+pub mod b_1_4_object_management;
+
+pub mod b_1_5_lock_management;
+
+pub mod b_1_6_pack_management;
+
+pub mod b_1_7_chunk_management;
+
 pub const REMOTE: &str = ".remote";
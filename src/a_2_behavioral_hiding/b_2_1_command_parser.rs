@@ -21,6 +21,60 @@
 //! Additionally, the `push` command accepts an optional `branch` argument to specify the branch
 //! to push to. If not provided, the default branch is set to `main`.
 //!
+//! The `commit` command accepts `--amend` to replace the last commit of the current branch with
+//! one that also holds the staged changes, keeping the old message if no new one is given. A
+//! commit that was already pushed to the remote repository is only amended with `--force`, which
+//! is only accepted together with `--amend`.
+//!
+//! The `unlock` command clears a stale repository lock left behind by a process that died. It
+//! refuses to clear a lock held by a running process unless `--force` is given.
+//!
+//! The `recover` command finishes or undoes a `commit`, `commit --amend`, `merge`, `squash`,
+//! `push` or `pull` that was interrupted, using the journal the operation left behind.
+//!
+//! The `fsck` command verifies revisions, objects and branch metadata and reports every problem
+//! it finds. With `--remote`, the remote repository named by `remote.path` is verified as well.
+//! With `--repair`, branch and repository metadata are first rebuilt from the revisions on disk
+//! and corrupt revisions, objects and packs are moved to `.dvcs/quarantine`.
+//!
+//! The `gc` command removes revisions that no branch can reach, objects that no remaining
+//! revision references, and temporary files left by interrupted operations. With `--dry-run`, it
+//! only reports what would be removed.
+//!
+//! The `pack` command moves the stored contents of every reachable revision into a pack file,
+//! storing each version of a file as a delta against the previous one where that is smaller.
+//!
+//! The `migrate` command upgrades a repository created by an older version of days_dvcs to the
+//! current on-disk format in place. Other commands refuse to work on such a repository.
+//!
+//! The `revert` command undoes the changes of a revision with a new commit on the current branch.
+//! Changes that conflict with later ones are left in the working tree with conflict markers; once
+//! they are resolved and added, `--continue` commits the revert with its usual message, and
+//! `--abort` restores the branch as it was before.
+//!
+//! The `cherry-pick` command applies the changes of one or more revisions, in order, as new commits
+//! on the current branch, keeping their messages and authors. It stops on conflicts; once they are
+//! resolved and added, `--continue` commits them and picks the remaining revisions, and `--abort`
+//! restores the branch as it was before. Until then, commands that switch branches or move the
+//! branch, such as `checkout`, `branch`, `merge` and `pull`, are refused.
+//!
+//! The `rebase` command moves the current branch onto the head of another branch or revision and
+//! replays the commits it made since they forked on top, one at a time, keeping history linear.
+//! It stops on conflicts like `cherry-pick`; `--continue` commits the resolution, `--skip` drops
+//! the conflicting commit and `--abort` restores the branch as it was before.
+//!
+//! The `squash` command collapses consecutive commits of the current branch into one, either
+//! `<from>..<to>` with both ends included (`<from>..` reaches the head) or the last `N` with
+//! `--last N`. The new commit keeps the tree of the newest and gets the `-m` message, or the
+//! squashed messages in order. Later commits are rewritten on top of it. Commits that were already
+//! pushed are only rewritten with `--force`.
+//!
+//! The `config` command gets, sets, lists or unsets configuration values such as `user.name`.
+//! `set` and `unset` change the repository's `.dvcs/config`, or the user's `~/.dvcsconfig` with
+//! `--user`. A value is looked up in the environment (`DVCS_USER_NAME` for `user.name`), then the
+//! repository, then the user configuration, then the built-in defaults. Only known keys can be set;
+//! `unset` also removes keys left over from older versions.
+//!
 //! ## Dependencies:
 //! - clap: For command-line argument parsing.
 //!
@@ -66,6 +120,8 @@ pub enum ValidCommand {
     },
     Commit {
         msg: String,
+        amend: bool,
+        force: bool,
     },
     Log {
         repo: String,
@@ -90,6 +146,57 @@ pub enum ValidCommand {
     Branch {
         branch: String,
     },
+    Unlock {
+        repo: String,
+        force: bool,
+    },
+    Recover {
+        repo: String,
+    },
+    Fsck {
+        repo: String,
+        remote: bool,
+        repair: bool,
+    },
+    Gc {
+        repo: String,
+        dry_run: bool,
+    },
+    Pack {
+        repo: String,
+    },
+    Migrate {
+        repo: String,
+    },
+    Revert {
+        revision: String,
+        resume: bool,
+        abort: bool,
+    },
+    CherryPick {
+        revisions: Vec<String>,
+        resume: bool,
+        abort: bool,
+    },
+    Rebase {
+        upstream: String,
+        resume: bool,
+        skip: bool,
+        abort: bool,
+    },
+    Squash {
+        range: String,
+        last: usize,
+        msg: String,
+        force: bool,
+    },
+    Config {
+        action: String,
+        key: String,
+        value: String,
+        user: bool,
+        repo: String,
+    },
 }
 
 pub fn parse_command(args: Vec<String>) -> Result<ValidCommand, clap::Error> {
@@ -165,7 +272,12 @@ pub fn parse_command(args: Vec<String>) -> Result<ValidCommand, clap::Error> {
         .subcommand(
             Command::new("commit")
                 .about("Commit the staged changes")
-                .arg(arg!([msg] "Commit message").default_value("N/A")),
+                .arg(arg!([msg] "Commit message, the configured default if omitted"))
+                .arg(arg!(--amend "Replace the last commit instead of adding a new one"))
+                .arg(
+                    arg!(-f --force "Amend the last commit even if it was already pushed")
+                        .requires("amend"),
+                ),
         )
         .subcommand(
             Command::new("log")
@@ -180,7 +292,7 @@ pub fn parse_command(args: Vec<String>) -> Result<ValidCommand, clap::Error> {
                         .default_value(REMOTE),
                 )
                 .arg(arg!(head: [branch_or_commit] "The branch or revision to merge into"))
-                .arg(arg!(-m --message [msg] "Commit message").default_value("N/A")),
+                .arg(arg!(-m --message [msg] "Commit message, the configured default if omitted")),
         )
         .subcommand(
             Command::new("pull")
@@ -204,6 +316,112 @@ pub fn parse_command(args: Vec<String>) -> Result<ValidCommand, clap::Error> {
                 .arg(arg!(<branch> "Branch name"))
                 .arg_required_else_help(true),
         )
+        .subcommand(
+            Command::new("unlock")
+                .about("Clear a stale repository lock")
+                .arg(arg!([repo] "Directory of the repository").default_value("."))
+                .arg(arg!(-f --force "Clear the lock even if its process is still running")),
+        )
+        .subcommand(
+            Command::new("recover")
+                .about("Recover from an interrupted commit, merge, push or pull")
+                .arg(arg!([repo] "Directory of the repository").default_value(".")),
+        )
+        .subcommand(
+            Command::new("fsck")
+                .about("Verify the integrity of the repository")
+                .arg(arg!([repo] "Directory of the repository").default_value("."))
+                .arg(arg!(--remote "Also verify the remote repository"))
+                .arg(arg!(--repair "Rebuild inconsistent metadata and quarantine corrupt data")),
+        )
+        .subcommand(
+            Command::new("gc")
+                .about("Remove unreachable revisions, orphaned objects and temporary files")
+                .arg(arg!([repo] "Directory of the repository").default_value("."))
+                .arg(arg!(--"dry-run" "Report what would be removed without removing it")),
+        )
+        .subcommand(
+            Command::new("pack")
+                .about("Pack stored file contents, delta-encoding successive versions")
+                .arg(arg!([repo] "Directory of the repository").default_value(".")),
+        )
+        .subcommand(
+            Command::new("migrate")
+                .about("Upgrade a repository to the current on-disk format")
+                .arg(arg!([repo] "Directory of the repository").default_value(".")),
+        )
+        .subcommand(
+            Command::new("revert")
+                .about("Commit the inverse of a revision's changes on the current branch")
+                .arg(arg!([revision] "Revision ID to revert"))
+                .arg(
+                    arg!(--"continue" "Commit the revert once its conflicts are resolved")
+                        .conflicts_with_all(["revision", "abort"]),
+                )
+                .arg(
+                    arg!(--abort "Undo the revert and restore the branch")
+                        .conflicts_with("revision"),
+                )
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("cherry-pick")
+                .about("Apply the changes of revisions as new commits on the current branch")
+                .arg(arg!([revisions]... "Revision IDs to cherry-pick, in order"))
+                .arg(
+                    arg!(--"continue" "Commit the resolved conflicts and pick the remaining revisions")
+                        .conflicts_with_all(["revisions", "abort"]),
+                )
+                .arg(
+                    arg!(--abort "Undo the cherry-pick and restore the branch")
+                        .conflicts_with("revisions"),
+                )
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("rebase")
+                .about("Replay the commits of the current branch on top of another branch")
+                .arg(arg!([upstream] "Branch name or revision ID to rebase onto"))
+                .arg(
+                    arg!(--"continue" "Commit the resolved conflicts and replay the remaining commits")
+                        .conflicts_with_all(["upstream", "skip", "abort"]),
+                )
+                .arg(
+                    arg!(--skip "Drop the commit that stopped on conflicts and replay the rest")
+                        .conflicts_with_all(["upstream", "abort"]),
+                )
+                .arg(
+                    arg!(--abort "Undo the rebase and restore the branch")
+                        .conflicts_with("upstream"),
+                )
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("squash")
+                .about("Collapse consecutive commits of the current branch into one")
+                .arg(arg!([range] "Commits to squash as '<from>..<to>', both included"))
+                .arg(
+                    arg!(--last <N> "Squash the last N commits instead of a range")
+                        .value_parser(clap::value_parser!(usize))
+                        .conflicts_with("range"),
+                )
+                .arg(arg!(-m --message <MSG> "Message of the squashed commit, the combined messages if omitted"))
+                .arg(arg!(-f --force "Squash commits even if they were already pushed"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Get, set, list or unset configuration values")
+                .arg(
+                    arg!(<action> "Action to perform")
+                        .value_parser(["get", "set", "list", "unset"]),
+                )
+                .arg(arg!([key] "Configuration key, e.g. 'user.name'"))
+                .arg(arg!([value] "Value to set the key to"))
+                .arg(arg!(--user "Use the user configuration instead of the repository's"))
+                .arg(arg!(--repo <repo> "Directory of the repository").default_value("."))
+                .arg_required_else_help(true),
+        )
         .get_matches_from(args);
 
     match matches.subcommand() {
@@ -222,6 +440,17 @@ pub fn parse_command(args: Vec<String>) -> Result<ValidCommand, clap::Error> {
         Some(("pull", sub_m)) => parse_pull(sub_m),
         Some(("push", sub_m)) => parse_push(sub_m),
         Some(("branch", sub_m)) => parse_branch(sub_m),
+        Some(("unlock", sub_m)) => parse_unlock(sub_m),
+        Some(("recover", sub_m)) => parse_recover(sub_m),
+        Some(("fsck", sub_m)) => parse_fsck(sub_m),
+        Some(("gc", sub_m)) => parse_gc(sub_m),
+        Some(("pack", sub_m)) => parse_pack(sub_m),
+        Some(("migrate", sub_m)) => parse_migrate(sub_m),
+        Some(("revert", sub_m)) => parse_revert(sub_m),
+        Some(("cherry-pick", sub_m)) => parse_cherry_pick(sub_m),
+        Some(("rebase", sub_m)) => parse_rebase(sub_m),
+        Some(("squash", sub_m)) => parse_squash(sub_m),
+        Some(("config", sub_m)) => parse_config(sub_m),
         _ => Err(clap::Error::new(InvalidSubcommand)),
     }
 }
@@ -292,8 +521,13 @@ fn parse_checkout(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
 }
 
 fn parse_commit(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
-    let msg = matches.get_one::<String>("msg").unwrap().to_string();
-    Ok(ValidCommand::Commit { msg })
+    let msg = matches
+        .get_one::<String>("msg")
+        .unwrap_or(&String::new())
+        .to_string();
+    let amend = matches.get_flag("amend");
+    let force = matches.get_flag("force");
+    Ok(ValidCommand::Commit { msg, amend, force })
 }
 
 fn parse_log(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
@@ -307,7 +541,10 @@ fn parse_merge(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
         .get_one::<String>("head")
         .unwrap_or(&String::new())
         .to_string();
-    let msg = matches.get_one::<String>("message").unwrap().to_string();
+    let msg = matches
+        .get_one::<String>("message")
+        .unwrap_or(&String::new())
+        .to_string();
     Ok(ValidCommand::Merge {
         branch_or_revision_from,
         branch_or_revision_into,
@@ -351,3 +588,126 @@ fn parse_branch(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
     let branch = matches.get_one::<String>("branch").unwrap().to_string();
     Ok(ValidCommand::Branch { branch })
 }
+
+fn parse_unlock(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let repo = matches.get_one::<String>("repo").unwrap().to_string();
+    let force = matches.get_flag("force");
+    Ok(ValidCommand::Unlock { repo, force })
+}
+
+fn parse_recover(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let repo = matches.get_one::<String>("repo").unwrap().to_string();
+    Ok(ValidCommand::Recover { repo })
+}
+
+fn parse_fsck(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let repo = matches.get_one::<String>("repo").unwrap().to_string();
+    let remote = matches.get_flag("remote");
+    let repair = matches.get_flag("repair");
+    Ok(ValidCommand::Fsck {
+        repo,
+        remote,
+        repair,
+    })
+}
+
+fn parse_gc(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let repo = matches.get_one::<String>("repo").unwrap().to_string();
+    let dry_run = matches.get_flag("dry-run");
+    Ok(ValidCommand::Gc { repo, dry_run })
+}
+
+fn parse_pack(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let repo = matches.get_one::<String>("repo").unwrap().to_string();
+    Ok(ValidCommand::Pack { repo })
+}
+
+fn parse_migrate(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let repo = matches.get_one::<String>("repo").unwrap().to_string();
+    Ok(ValidCommand::Migrate { repo })
+}
+
+fn parse_revert(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let revision = matches
+        .get_one::<String>("revision")
+        .unwrap_or(&String::new())
+        .to_string();
+    let resume = matches.get_flag("continue");
+    let abort = matches.get_flag("abort");
+    Ok(ValidCommand::Revert {
+        revision,
+        resume,
+        abort,
+    })
+}
+
+fn parse_cherry_pick(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let revisions: Vec<String> = matches
+        .get_many::<String>("revisions")
+        .unwrap_or_default()
+        .map(|s| s.to_string())
+        .collect();
+    let resume = matches.get_flag("continue");
+    let abort = matches.get_flag("abort");
+    Ok(ValidCommand::CherryPick {
+        revisions,
+        resume,
+        abort,
+    })
+}
+
+fn parse_rebase(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let upstream = matches
+        .get_one::<String>("upstream")
+        .unwrap_or(&String::new())
+        .to_string();
+    let resume = matches.get_flag("continue");
+    let skip = matches.get_flag("skip");
+    let abort = matches.get_flag("abort");
+    Ok(ValidCommand::Rebase {
+        upstream,
+        resume,
+        skip,
+        abort,
+    })
+}
+
+fn parse_squash(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let range = matches
+        .get_one::<String>("range")
+        .unwrap_or(&String::new())
+        .to_string();
+    let last = *matches.get_one::<usize>("last").unwrap_or(&0);
+    let msg = matches
+        .get_one::<String>("message")
+        .unwrap_or(&String::new())
+        .to_string();
+    let force = matches.get_flag("force");
+    Ok(ValidCommand::Squash {
+        range,
+        last,
+        msg,
+        force,
+    })
+}
+
+fn parse_config(matches: &ArgMatches) -> Result<ValidCommand, clap::Error> {
+    let action = matches.get_one::<String>("action").unwrap().to_string();
+    let key = matches
+        .get_one::<String>("key")
+        .unwrap_or(&String::new())
+        .to_string();
+    let value = matches
+        .get_one::<String>("value")
+        .unwrap_or(&String::new())
+        .to_string();
+    let user = matches.get_flag("user");
+    let repo = matches.get_one::<String>("repo").unwrap().to_string();
+    Ok(ValidCommand::Config {
+        action,
+        key,
+        value,
+        user,
+        repo,
+    })
+}
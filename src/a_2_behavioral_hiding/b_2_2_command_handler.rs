@@ -24,15 +24,17 @@ use super::b_2_3_output_formatter::{OutputFormatter, OutputType};
 
 use crate::a_1_file_system_hiding::REMOTE;
 use crate::a_3_repository_hiding::{
-    b_3_1_repository_management::*, b_3_2_revision_management::*, b_3_3_branch_management::*,
-    b_3_4_synchronization_handler::*, b_3_5_cross_revision_management::*,
+    b_3_10_migration_management::*, b_3_11_configuration_management::*,
+    b_3_12_history_management::*, b_3_1_repository_management::*, b_3_2_revision_management::*,
+    b_3_3_branch_management::*, b_3_4_synchronization_handler::*,
+    b_3_5_cross_revision_management::*, b_3_6_journal_management::*, b_3_7_integrity_management::*,
+    b_3_8_garbage_collection::*, b_3_9_pack_management::*,
 };
 
 pub struct CommandHandler;
 
 impl CommandHandler {
     /// Executes the given command.
-
     pub fn handle_command(command: ValidCommand) {
         match command {
             ValidCommand::Init { directory } => {
@@ -41,20 +43,15 @@ impl CommandHandler {
                     format!("Initializing repository in directory: '{}'", directory),
                 );
                 let result = init_repository(&directory, true);
-                if result.is_ok() {
-                    OutputFormatter::display(
+                match result {
+                    Ok(_) => OutputFormatter::display(
                         OutputType::Success,
                         format!("Initialized repository in directory: '{}'", directory),
-                    )
-                } else {
-                    OutputFormatter::display(
+                    ),
+                    Err(e) => OutputFormatter::display(
                         OutputType::Error,
-                        format!(
-                            "Failed to initialize repository in '{}': {}",
-                            directory,
-                            result.unwrap_err()
-                        ),
-                    )
+                        format!("Failed to initialize repository in '{}': {}", directory, e),
+                    ),
                 }
             }
             ValidCommand::Clone { repo, directory } => {
@@ -63,21 +60,22 @@ impl CommandHandler {
                     format!("Cloning repository from '{}' to '{}'", repo, directory),
                 );
                 let result = clone_repository(&repo, &directory);
-                if result.is_ok() {
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        format!("Cloned repository from '{}' to '{}'", repo, directory),
-                    );
-                } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!(
-                            "Failed to clone repository from '{}' to '{}': {}",
-                            repo,
-                            directory,
-                            result.unwrap_err()
-                        ),
-                    );
+                match result {
+                    Ok(_) => {
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Cloned repository from '{}' to '{}'", repo, directory),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!(
+                                "Failed to clone repository from '{}' to '{}': {}",
+                                repo, directory, e
+                            ),
+                        );
+                    }
                 }
             }
             ValidCommand::Add { pathspec } => {
@@ -88,13 +86,19 @@ impl CommandHandler {
                     .join(" ");
                 OutputFormatter::display(OutputType::Process, format!("Adding file: {}", files));
                 let result = add(".", pathspec);
-                if result.is_ok() {
-                    OutputFormatter::display(OutputType::Success, format!("Added file: {}", files));
-                } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!("Failed to add {}: {}", files, result.unwrap_err()),
-                    );
+                match result {
+                    Ok(_) => {
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Added file: {}", files),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to add {}: {}", files, e),
+                        );
+                    }
                 }
             }
             ValidCommand::Remove { pathspec } => {
@@ -105,16 +109,19 @@ impl CommandHandler {
                     .join(" ");
                 OutputFormatter::display(OutputType::Process, format!("Removing file: {}", files));
                 let result = remove(".", pathspec);
-                if result.is_ok() {
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        format!("Removed file: {}", files),
-                    );
-                } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!("Failed to remove {}: {}", files, result.unwrap_err()),
-                    );
+                match result {
+                    Ok(_) => {
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Removed file: {}", files),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to remove {}: {}", files, e),
+                        );
+                    }
                 }
             }
             ValidCommand::Status { repo } => {
@@ -123,24 +130,23 @@ impl CommandHandler {
                     format!("Checking status of repository: '{}'", repo),
                 );
                 let result = status(&repo);
-                if result.is_ok() {
-                    OutputFormatter::display(OutputType::Success, "Status: \n".to_string());
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, "Status: \n".to_string());
 
-                    OutputFormatter::display(OutputType::Process, format!("{}", result.unwrap()));
+                        OutputFormatter::display(OutputType::Process, output.to_string());
 
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        format!("Checked status of repository: '{}'", repo),
-                    );
-                } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!(
-                            "Failed to check status in '{}': {}",
-                            repo,
-                            result.unwrap_err()
-                        ),
-                    );
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Checked status of repository: '{}'", repo),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to check status in '{}': {}", repo, e),
+                        );
+                    }
                 }
             }
             ValidCommand::Heads { repo } => {
@@ -149,24 +155,23 @@ impl CommandHandler {
                     format!("Checking heads of repository: '{}'", repo),
                 );
                 let result = heads(&repo);
-                if result.is_ok() {
-                    OutputFormatter::display(OutputType::Success, "Heads: \n".to_string());
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, "Heads: \n".to_string());
 
-                    OutputFormatter::display(OutputType::Process, format!("{}", result.unwrap()));
+                        OutputFormatter::display(OutputType::Process, output.to_string());
 
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        format!("Checked heads of repository: '{}'", repo),
-                    );
-                } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!(
-                            "Failed to check heads in '{}': {}",
-                            repo,
-                            result.unwrap_err()
-                        ),
-                    );
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Checked heads of repository: '{}'", repo),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to check heads in '{}': {}", repo, e),
+                        );
+                    }
                 }
             }
             ValidCommand::Diff {
@@ -191,28 +196,31 @@ impl CommandHandler {
                     ),
                 );
                 let result = diff(".", &commit_1, &commit_2);
-                if result.is_ok() {
-                    OutputFormatter::display(OutputType::Success, "Changes: \n".to_string());
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, "Changes: \n".to_string());
 
-                    OutputFormatter::display(OutputType::Process, format!("{}", result.unwrap()));
+                        OutputFormatter::display(OutputType::Process, output.to_string());
 
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        format!(
-                            "Checked diff between branches or revisions '{}' and '{}'",
-                            id_1, id_2
-                        ),
-                    );
-                } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!(
-                            "Failed to check diff between branches or revisions '{}' and '{}': {}",
-                            id_1,
-                            id_2,
-                            result.unwrap_err()
-                        ),
-                    );
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!(
+                                "Checked diff between branches or revisions '{}' and '{}'",
+                                id_1, id_2
+                            ),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!(
+                                "Failed to check diff between branches or revisions '{}' and '{}': {}",
+                                id_1,
+                                id_2,
+                                e
+                            ),
+                        );
+                    }
                 }
             }
             ValidCommand::Cat { commit, path } => {
@@ -221,24 +229,23 @@ impl CommandHandler {
                     format!("Displaying contents of file: '{}'", path),
                 );
                 let result = cat(".", &commit, &path);
-                if result.is_ok() {
-                    OutputFormatter::display(OutputType::Success, "Contents: \n".to_string());
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, "Contents: \n".to_string());
 
-                    OutputFormatter::display(OutputType::Process, format!("{}", result.unwrap()));
+                        OutputFormatter::display(OutputType::Process, output.to_string());
 
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        format!("Displayed contents of file: '{}'", path),
-                    );
-                } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!(
-                            "Failed to display contents of file '{}': {}",
-                            path,
-                            result.unwrap_err()
-                        ),
-                    );
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Displayed contents of file: '{}'", path),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to display contents of file '{}': {}", path, e),
+                        );
+                    }
                 }
             }
             ValidCommand::Checkout { branch_or_commit } => {
@@ -247,57 +254,83 @@ impl CommandHandler {
                     format!("Checking out branch or commit: '{}'", branch_or_commit),
                 );
                 let result = checkout(".", &branch_or_commit);
-                if result.is_ok() {
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        format!("Checked out branch or commit: '{}'", branch_or_commit),
-                    );
-                } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!(
-                            "Failed to check out '{}': {}",
-                            branch_or_commit,
-                            result.unwrap_err()
-                        ),
-                    );
+                match result {
+                    Ok(_) => {
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Checked out branch or commit: '{}'", branch_or_commit),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to check out '{}': {}", branch_or_commit, e),
+                        );
+                    }
                 }
             }
-            ValidCommand::Commit { msg } => {
+            ValidCommand::Commit { msg, amend, force } => {
+                // Without a message, the configured default (or the amended commit's) is used
+                let with_message = if msg.is_empty() {
+                    String::new()
+                } else {
+                    format!(" with message: '{}'", msg)
+                };
+                let action = if amend {
+                    "Amending last commit"
+                } else {
+                    "Committing changes"
+                };
                 OutputFormatter::display(
                     OutputType::Process,
-                    format!("Committing changes with message: '{}'", msg),
+                    format!("{}{}", action, with_message),
                 );
-                let result = commit(".", &msg);
-                if result.is_ok() {
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        format!("Committed changes with message: '{}'", msg),
-                    );
+                let result = if amend {
+                    commit_amend(".", &msg, force)
                 } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!("Failed to commit changes: {}", result.unwrap_err()),
-                    );
+                    commit(".", &msg)
+                };
+                match result {
+                    Ok(revision_id) if amend => {
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Amended last commit as '{}'{}", revision_id, with_message),
+                        );
+                    }
+                    Ok(_) => {
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Committed changes{}", with_message),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to commit changes: {}", e),
+                        );
+                    }
                 }
             }
             ValidCommand::Log { repo } => {
                 OutputFormatter::display(OutputType::Process, "Displaying commit log".to_string());
                 let result = log(&repo);
-                if result.is_ok() {
-                    OutputFormatter::display(OutputType::Success, "Log: \n".to_string());
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, "Log: \n".to_string());
 
-                    OutputFormatter::display(OutputType::Process, format!("{}", result.unwrap()));
+                        OutputFormatter::display(OutputType::Process, output.to_string());
 
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        "Displayed commit log".to_string(),
-                    );
-                } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!("Failed to display log: {}", result.unwrap_err()),
-                    );
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            "Displayed commit log".to_string(),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to display log: {}", e),
+                        );
+                    }
                 }
             }
             ValidCommand::Merge {
@@ -317,7 +350,11 @@ impl CommandHandler {
                 };
                 OutputFormatter::display(
                     OutputType::Process,
-                    format!("Merging branch from '{}' into '{}': '{}'", id_1, id_2, msg),
+                    if msg.is_empty() {
+                        format!("Merging branch from '{}' into '{}'", id_1, id_2)
+                    } else {
+                        format!("Merging branch from '{}' into '{}': '{}'", id_1, id_2, msg)
+                    },
                 );
                 let result = merge(
                     ".",
@@ -432,16 +469,281 @@ impl CommandHandler {
                     format!("Creating branch: '{}'", branch),
                 );
                 let result = init_branch(".", &branch, false);
-                if result.is_ok() {
-                    OutputFormatter::display(
-                        OutputType::Success,
-                        format!("Created branch: '{}'", branch),
-                    );
+                match result {
+                    Ok(_) => {
+                        OutputFormatter::display(
+                            OutputType::Success,
+                            format!("Created branch: '{}'", branch),
+                        );
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to create branch: '{}'", e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Unlock { repo, force } => {
+                OutputFormatter::display(
+                    OutputType::Process,
+                    format!("Clearing lock of repository: '{}'", repo),
+                );
+                let result = unlock_repository(&repo, force);
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, output);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to clear lock in '{}': {}", repo, e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Recover { repo } => {
+                OutputFormatter::display(
+                    OutputType::Process,
+                    format!("Recovering repository: '{}'", repo),
+                );
+                let result = recover(&repo);
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, output);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to recover repository '{}': {}", repo, e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Fsck {
+                repo,
+                remote,
+                repair,
+            } => {
+                OutputFormatter::display(
+                    OutputType::Process,
+                    format!(
+                        "{} repository: '{}'",
+                        if repair { "Repairing" } else { "Checking" },
+                        repo
+                    ),
+                );
+                let result = fsck(&repo, remote, repair);
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, output);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to verify repository '{}': {}", repo, e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Gc { repo, dry_run } => {
+                OutputFormatter::display(
+                    OutputType::Process,
+                    format!("Collecting garbage in repository: '{}'", repo),
+                );
+                let result = gc(&repo, dry_run);
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, output);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to collect garbage in '{}': {}", repo, e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Pack { repo } => {
+                OutputFormatter::display(
+                    OutputType::Process,
+                    format!("Packing repository: '{}'", repo),
+                );
+                let result = pack(&repo);
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, output);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to pack repository '{}': {}", repo, e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Migrate { repo } => {
+                OutputFormatter::display(
+                    OutputType::Process,
+                    format!("Migrating repository: '{}'", repo),
+                );
+                let result = migrate(&repo);
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, output);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to migrate repository '{}': {}", repo, e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Revert {
+                revision,
+                resume,
+                abort,
+            } => {
+                let process = if resume {
+                    "Continuing revert".to_string()
+                } else if abort {
+                    "Aborting revert".to_string()
                 } else {
-                    OutputFormatter::display(
-                        OutputType::Error,
-                        format!("Failed to create branch: '{}'", result.unwrap_err()),
-                    );
+                    format!("Reverting revision: '{}'", revision)
+                };
+                OutputFormatter::display(OutputType::Process, process);
+                let result = if resume {
+                    revert_continue(".")
+                } else if abort {
+                    revert_abort(".")
+                } else {
+                    revert(".", &revision)
+                };
+                match result {
+                    Ok(report) => {
+                        OutputFormatter::display(OutputType::Success, report);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to revert: {}", e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::CherryPick {
+                revisions,
+                resume,
+                abort,
+            } => {
+                let process = if resume {
+                    "Continuing cherry-pick".to_string()
+                } else if abort {
+                    "Aborting cherry-pick".to_string()
+                } else {
+                    format!("Cherry-picking revisions: {}", revisions.join(" "))
+                };
+                OutputFormatter::display(OutputType::Process, process);
+                let result = if resume {
+                    cherry_pick_continue(".")
+                } else if abort {
+                    cherry_pick_abort(".")
+                } else {
+                    cherry_pick(".", &revisions)
+                };
+                match result {
+                    Ok(report) => {
+                        OutputFormatter::display(OutputType::Success, report);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to cherry-pick: {}", e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Rebase {
+                upstream,
+                resume,
+                skip,
+                abort,
+            } => {
+                let process = if resume {
+                    "Continuing rebase".to_string()
+                } else if skip {
+                    "Skipping commit and continuing rebase".to_string()
+                } else if abort {
+                    "Aborting rebase".to_string()
+                } else {
+                    format!("Rebasing current branch onto '{}'", upstream)
+                };
+                OutputFormatter::display(OutputType::Process, process);
+                let result = if resume {
+                    rebase_continue(".")
+                } else if skip {
+                    rebase_skip(".")
+                } else if abort {
+                    rebase_abort(".")
+                } else {
+                    rebase(".", &upstream)
+                };
+                match result {
+                    Ok(report) => {
+                        OutputFormatter::display(OutputType::Success, report);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to rebase: {}", e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Squash {
+                range,
+                last,
+                msg,
+                force,
+            } => {
+                let process = if last > 0 {
+                    format!("Squashing the last {} commit(s)", last)
+                } else {
+                    format!("Squashing commits {}", range)
+                };
+                OutputFormatter::display(OutputType::Process, process);
+                let result = squash(".", &range, last, &msg, force);
+                match result {
+                    Ok(report) => {
+                        OutputFormatter::display(OutputType::Success, report);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to squash: {}", e),
+                        );
+                    }
+                }
+            }
+            ValidCommand::Config {
+                action,
+                key,
+                value,
+                user,
+                repo,
+            } => {
+                let result = config(&repo, &action, &key, &value, user);
+                match result {
+                    Ok(output) => {
+                        OutputFormatter::display(OutputType::Success, output);
+                    }
+                    Err(e) => {
+                        OutputFormatter::display(
+                            OutputType::Error,
+                            format!("Failed to {} configuration: {}", action, e),
+                        );
+                    }
                 }
             }
         }
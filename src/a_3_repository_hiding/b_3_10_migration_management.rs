@@ -0,0 +1,241 @@
+// days_dvcs/src/a_3_repository_hiding/b_3_10_migration_management.rs
+//
+
+use super::b_3_1_repository_management::{
+    find_repository, get_format_version, load_repo_metadata, lock_repository, save_repo_metadata,
+    FORMAT_VERSION, LEGACY_FORMAT_VERSION,
+};
+use super::b_3_2_revision_management::{
+    hash_revision, load_revision_metadata, save_revision_metadata, RevisionMetadata,
+};
+use super::b_3_3_branch_management::{save_branch_metadata, BranchMetadata};
+
+use crate::a_1_file_system_hiding::{
+    b_1_1_file_interaction::{
+        check_file, get_filename, get_parent, read_bytes, read_struct, write_file,
+    },
+    b_1_2_directory_interaction::{
+        check_directory, create_directory, delete_directory, list_directory,
+    },
+    b_1_4_object_management::write_object,
+};
+
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+// Branch metadata of format version 1, which also listed the branch's commits in order
+#[derive(Debug, Deserialize)]
+struct LegacyBranchMetadata {
+    head_commit: Option<String>,
+    #[serde(default)]
+    commits: Vec<String>,
+    #[serde(default)]
+    staging: Vec<String>,
+}
+
+// Finds the directory of a version 1 revision; branches created from one another hold copies of
+// the same revisions
+fn find_legacy_revision(
+    path: &str,
+    branches: &[String],
+    legacy_id: &str,
+) -> Result<String, io::Error> {
+    branches
+        .iter()
+        .map(|branch| format!("{}/.dvcs/origin/{}/commits/{}", path, branch, legacy_id))
+        .find(|revision_path| check_file(&format!("{}/.metadata/metadata.json", revision_path)))
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Revision '{}' not found in any branch of '{}'",
+                legacy_id,
+                get_filename(path)
+            ),
+        ))
+}
+
+// Rebuilds a version 1 revision (and its ancestors first) in the content-addressed layout and
+// returns its new id. The ancestors not migrated yet are collected first and rebuilt oldest
+// first, so a long history does not need a deep call stack
+fn migrate_revision(
+    path: &str,
+    branches: &[String],
+    legacy_id: &str,
+    migrated: &mut HashMap<String, String>,
+) -> Result<String, io::Error> {
+    let mut pending = Vec::new();
+    let mut seen = HashSet::new();
+    let mut current = Some(legacy_id.to_string());
+
+    while let Some(id) = current.filter(|id| !migrated.contains_key(id)) {
+        if !seen.insert(id.clone()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Revision '{}' is its own ancestor", id),
+            ));
+        }
+
+        let revision_path = find_legacy_revision(path, branches, &id)?;
+        let legacy: RevisionMetadata =
+            read_struct(&format!("{}/.metadata/metadata.json", revision_path))?;
+
+        // Version 1 revisions list every ancestor as a parent; the last one is the direct parent
+        current = legacy.parents.last().cloned();
+        pending.push((id, revision_path, legacy));
+    }
+
+    let objects_path = format!("{}/.dvcs/objects", path);
+
+    for (id, revision_path, legacy) in pending.into_iter().rev() {
+        let mut files = HashMap::new();
+        let mut parents = Vec::new();
+
+        if let Some(legacy_parent) = legacy.parents.last() {
+            let parent_id = migrated[legacy_parent].clone();
+            files = load_revision_metadata(path, &parent_id)?.files;
+            parents.push(parent_id);
+        }
+
+        // They also only stored the files committed in them, on top of the parent's files
+        for file in legacy.files.keys() {
+            let content = read_bytes(&format!("{}/{}", revision_path, file))?;
+            files.insert(file.clone(), write_object(&objects_path, &content)?);
+        }
+
+        let mut revision = RevisionMetadata {
+            id: String::new(),
+            files,
+            modes: HashMap::new(),
+            parents,
+            author: None,
+            committer: None,
+            message: legacy.message,
+            timestamp: legacy.timestamp,
+        };
+        revision.id = hash_revision(&revision);
+        save_revision_metadata(path, &revision.id, &revision)?;
+        migrated.insert(id, revision.id);
+    }
+
+    Ok(migrated[legacy_id].clone())
+}
+
+fn migrate_legacy_layout(path: &str) -> Result<String, io::Error> {
+    let dvcs_path = format!("{}/.dvcs", path);
+    let origin_path = format!("{}/origin", dvcs_path);
+
+    for directory in ["objects", "commits"] {
+        let directory_path = format!("{}/{}", dvcs_path, directory);
+
+        if !check_directory(&directory_path) {
+            create_directory(&directory_path)?;
+        }
+    }
+
+    let mut branches = list_directory(&origin_path, false, false)?;
+    branches.sort();
+
+    let mut migrated = HashMap::new();
+    let mut branch_metadata = Vec::new();
+
+    for branch in branches.iter() {
+        let legacy: LegacyBranchMetadata = read_struct(&format!(
+            "{}/{}/.metadata/metadata.json",
+            origin_path, branch
+        ))?;
+
+        for legacy_id in legacy.commits.iter().chain(legacy.head_commit.iter()) {
+            migrate_revision(path, &branches, legacy_id, &mut migrated)?;
+        }
+
+        branch_metadata.push(BranchMetadata {
+            name: branch.clone(),
+            head_commit: legacy
+                .head_commit
+                .map(|legacy_id| migrated[&legacy_id].clone()),
+            staging: legacy.staging,
+        });
+    }
+
+    let mut repo_metadata = load_repo_metadata(path)?;
+
+    for head in repo_metadata.branches.values_mut() {
+        if !head.is_empty() {
+            *head = migrate_revision(path, &branches, head, &mut migrated)?;
+        }
+    }
+
+    for metadata in branch_metadata.iter() {
+        save_branch_metadata(path, &metadata.name, metadata)?;
+    }
+
+    // The version is recorded last, so an interrupted migration can simply be run again
+    repo_metadata.version = FORMAT_VERSION;
+    save_repo_metadata(path, &repo_metadata)?;
+
+    let head_commit = repo_metadata
+        .branches
+        .get(&repo_metadata.head)
+        .filter(|revision_id| !revision_id.is_empty())
+        .cloned()
+        .unwrap_or("N/A".to_string());
+    write_file(
+        &format!("{}/HEAD", dvcs_path),
+        &format!(
+            "commit: {}\nref: {}/.dvcs/origin/{}",
+            head_commit,
+            get_parent(path),
+            repo_metadata.head
+        ),
+    )?;
+
+    for branch in branches.iter() {
+        let commits_path = format!("{}/{}/commits", origin_path, branch);
+
+        if check_directory(&commits_path) {
+            delete_directory(&commits_path, true)?;
+        }
+    }
+
+    Ok(format!(
+        "{} revision(s) on {} branch(es)",
+        migrated.len(),
+        branches.len()
+    ))
+}
+
+pub fn migrate(path: &str) -> Result<String, io::Error> {
+    let path = &find_repository(path)?;
+    let _lock = lock_repository(path)?;
+    let version = get_format_version(path)?;
+
+    if version == FORMAT_VERSION {
+        return Ok(format!(
+            "Repository '{}' already uses format version {}",
+            get_filename(path),
+            FORMAT_VERSION
+        ));
+    }
+
+    if version != LEGACY_FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "Repository '{}' uses format version {}, which this days_dvcs cannot migrate",
+                get_filename(path),
+                version
+            ),
+        ));
+    }
+
+    let migrated = migrate_legacy_layout(path)?;
+
+    Ok(format!(
+        "Migrated repository '{}' from format version {} to {}: {}",
+        get_filename(path),
+        version,
+        FORMAT_VERSION,
+        migrated
+    ))
+}
@@ -0,0 +1,416 @@
+// days_dvcs/src/a_3_repository_hiding/b_3_11_configuration_management.rs
+//
+
+use super::b_3_1_repository_management::is_repository;
+
+use crate::a_1_file_system_hiding::{
+    b_1_1_file_interaction::{
+        check_file, delete_file, get_absolute_path, read_struct, write_struct,
+    },
+    b_1_2_directory_interaction::check_directory,
+    b_1_7_chunk_management::DEFAULT_LARGE_FILE_THRESHOLD,
+    REMOTE,
+};
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::io;
+use std::str::FromStr;
+
+// Name of the user-level configuration file in the home directory
+pub const USER_CONFIG_FILE: &str = ".dvcsconfig";
+
+// Keys with a built-in default, which every layer of configuration can override
+pub const CONFIG_KEYS: [&str; 5] = [
+    "commit.default_message",
+    "core.ignore",
+    "core.large_file_threshold",
+    "diff.context_lines",
+    "remote.path",
+];
+
+// Keys without a default, which only take effect once set
+const OPTIONAL_CONFIG_KEYS: [&str; 2] = ["user.email", "user.name"];
+
+// Keys whose values must be non-negative numbers
+const NUMERIC_CONFIG_KEYS: [&str; 2] = ["core.large_file_threshold", "diff.context_lines"];
+
+// Numeric keys that must not be zero, e.g. a threshold of 0 would store every file as chunks
+const POSITIVE_CONFIG_KEYS: [&str; 1] = ["core.large_file_threshold"];
+
+pub type Config = BTreeMap<String, String>; // Maps dotted keys (e.g. 'user.name') to values
+
+fn get_default_value(key: &str) -> Option<String> {
+    match key {
+        "commit.default_message" => Some("N/A".to_string()),
+        "core.ignore" => Some(format!("{},.git,.DS_Store", REMOTE)),
+        "core.large_file_threshold" => Some(DEFAULT_LARGE_FILE_THRESHOLD.to_string()),
+        "diff.context_lines" => Some("3".to_string()),
+        "remote.path" => Some(REMOTE.to_string()),
+        _ => None,
+    }
+}
+
+pub fn get_repo_config_path(path: &str) -> String {
+    format!("{}/.dvcs/config", path)
+}
+
+pub fn get_user_config_path() -> Option<String> {
+    env::var("HOME")
+        .ok()
+        .filter(|home| !home.is_empty())
+        .map(|home| format!("{}/{}", home, USER_CONFIG_FILE))
+}
+
+// Names the environment variable that overrides a key, e.g. DVCS_USER_NAME for 'user.name'
+pub fn get_config_variable(key: &str) -> String {
+    format!("DVCS_{}", key.to_uppercase().replace(['.', '-'], "_"))
+}
+
+pub fn load_config(config_path: &str) -> Result<Config, io::Error> {
+    // A configuration file that does not exist yet is simply empty
+    if !check_file(config_path) {
+        return Ok(Config::new());
+    }
+
+    read_struct(config_path).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Configuration '{}' is unreadable: {}", config_path, e),
+        )
+    })
+}
+
+fn save_config(config_path: &str, config: &Config) -> Result<(), io::Error> {
+    if config.is_empty() {
+        return if check_file(config_path) {
+            delete_file(config_path)
+        } else {
+            Ok(())
+        };
+    }
+
+    write_struct(config_path, config)?;
+    Ok(())
+}
+
+fn check_config_key(key: &str) -> Result<(), io::Error> {
+    if CONFIG_KEYS.contains(&key) || OPTIONAL_CONFIG_KEYS.contains(&key) {
+        return Ok(());
+    }
+
+    let mut known_keys: Vec<&str> = CONFIG_KEYS
+        .iter()
+        .chain(&OPTIONAL_CONFIG_KEYS)
+        .copied()
+        .collect();
+    known_keys.sort();
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "Unknown configuration key '{}'. Known keys are: {}",
+            key,
+            known_keys.join(", ")
+        ),
+    ))
+}
+
+fn check_config_value(key: &str, value: &str) -> Result<(), io::Error> {
+    if NUMERIC_CONFIG_KEYS.contains(&key) && value.trim().parse::<u64>().is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Invalid value '{}' for '{}': expected a non-negative number",
+                value, key
+            ),
+        ));
+    }
+
+    if POSITIVE_CONFIG_KEYS.contains(&key) && value.trim().parse::<u64>() == Ok(0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Invalid value '{}' for '{}': expected a positive number",
+                value, key
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+// Finds the value of a key and where it came from: the environment, the repository
+// configuration, the user configuration or the built-in defaults, in that order
+fn resolve_config_value(
+    path: Option<&str>,
+    key: &str,
+) -> Result<Option<(String, &'static str)>, io::Error> {
+    if let Ok(value) = env::var(get_config_variable(key)) {
+        return Ok(Some((value, "environment")));
+    }
+
+    if let Some(path) = path {
+        if let Some(value) = load_config(&get_repo_config_path(path))?.remove(key) {
+            return Ok(Some((value, "repository")));
+        }
+    }
+
+    if let Some(user_config_path) = get_user_config_path() {
+        if let Some(value) = load_config(&user_config_path)?.remove(key) {
+            return Ok(Some((value, "user")));
+        }
+    }
+
+    Ok(get_default_value(key).map(|value| (value, "default")))
+}
+
+pub fn get_config_value(path: &str, key: &str) -> Result<Option<String>, io::Error> {
+    Ok(resolve_config_value(Some(path), key)?.map(|(value, _)| value))
+}
+
+pub fn get_config_string(path: &str, key: &str) -> Result<String, io::Error> {
+    get_config_value(path, key)?.ok_or(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Configuration key '{}' is not set", key),
+    ))
+}
+
+// Reads a value as any type that parses from a string, e.g. a number
+pub fn get_config<T>(path: &str, key: &str) -> Result<T, io::Error>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = get_config_string(path, key)?;
+
+    value.trim().parse().map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid value '{}' for '{}': {}", value, key, e),
+        )
+    })
+}
+
+// Reads a comma-separated value as a list, leaving out empty entries
+pub fn get_config_list(path: &str, key: &str) -> Result<Vec<String>, io::Error> {
+    Ok(get_config_string(path, key)?
+        .split(',')
+        .map(|entry| entry.trim().to_string())
+        .filter(|entry| !entry.is_empty())
+        .collect())
+}
+
+// Picks the file `set` and `unset` change: the user configuration or the repository's
+fn get_scope_config_path(path: &str, user: bool) -> Result<(String, &'static str), io::Error> {
+    if user {
+        let user_config_path = get_user_config_path().ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            "Cannot locate the user configuration: HOME is not set",
+        ))?;
+        Ok((user_config_path, "user"))
+    } else {
+        Ok((get_repo_config_path(&is_repository(path)?), "repository"))
+    }
+}
+
+pub fn set_config_value(path: &str, key: &str, value: &str, user: bool) -> Result<(), io::Error> {
+    check_config_key(key)?;
+    check_config_value(key, value)?;
+
+    let (config_path, _) = get_scope_config_path(path, user)?;
+    let mut config = load_config(&config_path)?;
+    config.insert(key.to_string(), value.to_string());
+    save_config(&config_path, &config)
+}
+
+pub fn unset_config_value(path: &str, key: &str, user: bool) -> Result<(), io::Error> {
+    let (config_path, scope) = get_scope_config_path(path, user)?;
+    let mut config = load_config(&config_path)?;
+
+    if config.remove(key).is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Configuration key '{}' is not set in the {} configuration",
+                key, scope
+            ),
+        ));
+    }
+
+    save_config(&config_path, &config)
+}
+
+// Lists the effective value of every key that is set or has a default, with where it came from
+pub fn list_config(path: Option<&str>) -> Result<Vec<(String, String, String)>, io::Error> {
+    let mut keys: Vec<String> = CONFIG_KEYS.iter().map(|key| key.to_string()).collect();
+
+    if let Some(user_config_path) = get_user_config_path() {
+        keys.extend(load_config(&user_config_path)?.into_keys());
+    }
+
+    if let Some(path) = path {
+        keys.extend(load_config(&get_repo_config_path(path))?.into_keys());
+    }
+
+    keys.sort();
+    keys.dedup();
+
+    let mut entries = Vec::new();
+
+    for key in keys {
+        if let Some((value, origin)) = resolve_config_value(path, &key)? {
+            entries.push((key, value, origin.to_string()));
+        }
+    }
+
+    Ok(entries)
+}
+
+pub fn config(
+    path: &str,
+    action: &str,
+    key: &str,
+    value: &str,
+    user: bool,
+) -> Result<String, io::Error> {
+    let scope = if user { "user" } else { "repository" };
+    let require = |argument: &str, name: &str| {
+        if argument.is_empty() {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'config {}' requires a {}", action, name),
+            ))
+        } else {
+            Ok(())
+        }
+    };
+
+    match action {
+        "get" => {
+            require(key, "key")?;
+            check_config_key(key)?;
+
+            // Outside a repository, only the environment, user configuration and defaults apply
+            let repo_path = is_repository(path).ok();
+            match resolve_config_value(repo_path.as_deref(), key)? {
+                Some((value, _)) => Ok(value),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Configuration key '{}' is not set", key),
+                )),
+            }
+        }
+        "set" => {
+            require(key, "key")?;
+            require(value, "value")?;
+            set_config_value(path, key, value, user)?;
+            Ok(format!(
+                "Set '{}' to '{}' in the {} configuration",
+                key, value, scope
+            ))
+        }
+        "unset" => {
+            require(key, "key")?;
+            unset_config_value(path, key, user)?;
+            Ok(format!("Unset '{}' in the {} configuration", key, scope))
+        }
+        "list" => {
+            let repo_path = is_repository(path).ok();
+            Ok(list_config(repo_path.as_deref())?
+                .iter()
+                .map(|(key, value, origin)| format!("{}={} ({})", key, value, origin))
+                .collect::<Vec<String>>()
+                .join("\n"))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Unknown config action '{}'. Use 'get', 'set', 'list' or 'unset'",
+                action
+            ),
+        )),
+    }
+}
+
+// Files of at least this many bytes are stored as chunks
+pub fn get_large_file_threshold(path: &str) -> Result<u64, io::Error> {
+    // The environment and hand-edited files are not checked when set, so check the value here
+    match get_config(path, "core.large_file_threshold")? {
+        0 => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid value '0' for 'core.large_file_threshold': expected a positive number",
+        )),
+        threshold => Ok(threshold),
+    }
+}
+
+// Number of unchanged lines shown around each change in a diff
+pub fn get_context_lines(path: &str) -> Result<usize, io::Error> {
+    get_config(path, "diff.context_lines")
+}
+
+// Names of files and directories never tracked; the repository's own data is always among them
+pub fn get_ignored_names(path: &str) -> Result<Vec<String>, io::Error> {
+    let mut ignored = vec![".dvcs".to_string()];
+    ignored.extend(get_config_list(path, "core.ignore")?);
+    Ok(ignored)
+}
+
+// Message of a commit made without one
+pub fn get_default_message(path: &str) -> Result<String, io::Error> {
+    get_config_string(path, "commit.default_message")
+}
+
+// Resolves the configured remote repository, relative to the repository root unless absolute
+pub fn get_remote_path(path: &str) -> Result<String, io::Error> {
+    let remote_path = find_remote_path(path)?;
+
+    // A remote with damaged metadata must not be mistaken for a repository around it
+    if is_repository(&remote_path)? != remote_path {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Remote repository '{}' is not a valid repository",
+                remote_path
+            ),
+        ));
+    }
+
+    Ok(remote_path)
+}
+
+// Locates the configured remote repository by its '.dvcs' directory alone, which must be right
+// at the configured path, so 'fsck' can reach a remote whose metadata is damaged
+pub fn find_remote_path(path: &str) -> Result<String, io::Error> {
+    let configured = get_config_string(path, "remote.path")?;
+    let remote_path = get_absolute_path(&configured, path).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Remote repository '{}' does not exist", configured),
+        )
+    })?;
+
+    if !check_directory(&format!("{}/.dvcs", remote_path)) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Remote repository '{}' has no '.dvcs' directory. Set 'remote.path' to the root of a repository",
+                remote_path
+            ),
+        ));
+    }
+
+    if remote_path == get_absolute_path(path, "")? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Remote repository '{}' is the local repository itself. Set 'remote.path' to another repository",
+                remote_path
+            ),
+        ));
+    }
+
+    Ok(remote_path)
+}
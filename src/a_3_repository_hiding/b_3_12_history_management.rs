@@ -0,0 +1,1091 @@
+// days_dvcs/src/a_3_repository_hiding/b_3_12_history_management.rs
+//
+
+use super::b_3_1_repository_management::{
+    get_stat_cache_path, is_repository, load_repo_metadata, lock_repository, save_repo_metadata,
+};
+use super::b_3_2_revision_management::{
+    commit, commit_as, get_ancestors, get_branch_or_revision_id, get_revision_file_mode,
+    get_signature, hash_revision, init_revision_metadata, is_published, load_revision_metadata,
+    save_revision_metadata, RevisionMetadata, Signature,
+};
+use super::b_3_3_branch_management::{
+    get_common_ancestor_and_count, load_branch_metadata, save_branch_metadata,
+};
+use super::b_3_5_cross_revision_management::{merge_conflict, merge_contents};
+use super::b_3_6_journal_management::begin_operation;
+
+use crate::a_1_file_system_hiding::{
+    b_1_1_file_interaction::{
+        check_file, delete_file, get_parent, is_binary_file, read_struct, write_file, write_struct,
+    },
+    b_1_2_directory_interaction::{check_directory, create_directory, delete_directory},
+    b_1_3_metadata_management::{
+        hash_file_cached, load_stat_cache, save_stat_cache, set_file_mode, update_stat_cache,
+        write_entry, SYMLINK_MODE,
+    },
+    b_1_4_object_management::{read_object, write_object},
+    b_1_7_chunk_management::{read_object_manifest, write_chunk_manifest, write_object_file},
+};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::io;
+use std::time::SystemTime;
+
+// Progress of replaying a series of revisions onto a branch, kept on disk between commands so that
+// conflicts can be resolved before continuing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Sequence {
+    operation: String,             // Command running the sequence, e.g. 'cherry-pick'
+    branch: String,                // Branch the revisions are replayed onto
+    original_head: Option<String>, // Head of the branch before the sequence, restored on abort
+    current: Option<String>,       // Revision that stopped on conflicts
+    conflicts: Vec<String>,        // Files of that revision left with conflict markers
+    pending: Vec<String>,          // Revisions still to replay, in order
+    #[serde(default)]
+    onto: Option<String>, // What a rebase replays onto, naming that side of conflicts
+}
+
+// Outcome of replaying the change between two revisions onto the head of a branch
+struct Replay {
+    changes: Vec<(String, Option<(String, u32)>)>, // File -> new hash and mode, None if deleted
+    conflicts: Vec<(String, Option<String>)>,      // File -> content with conflict markers, if any
+}
+
+// Works out how the files of `head` change when the change from `base` to `target` is applied to
+// them. Files changed on both sides are merged line by line, and what cannot be merged conflicts
+fn replay_change(
+    objects_path: &str,
+    base: &RevisionMetadata,
+    target: &RevisionMetadata,
+    head: &RevisionMetadata,
+    head_label: &str,
+    change_label: &str,
+) -> Result<Replay, io::Error> {
+    let mut replay = Replay {
+        changes: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    let files: BTreeSet<&String> = base.files.keys().chain(target.files.keys()).collect();
+
+    for file in files {
+        let base_hash = base.files.get(file);
+        let target_hash = target.files.get(file);
+        let head_hash = head.files.get(file);
+        let base_mode = get_revision_file_mode(base, file);
+        let target_mode = get_revision_file_mode(target, file);
+        let head_mode = get_revision_file_mode(head, file);
+
+        // A mode changed only by the change is taken over, like a content change
+        let mode = if head_mode == base_mode {
+            target_mode
+        } else {
+            head_mode
+        };
+
+        if base_hash == target_hash && base_mode == target_mode {
+            continue; // The change leaves the file alone
+        }
+
+        if head_hash == target_hash {
+            // The head already holds the changed content; only a changed mode may be missing
+            if let Some(hash) = head_hash.filter(|_| mode != head_mode) {
+                replay
+                    .changes
+                    .push((file.clone(), Some((hash.clone(), mode))));
+            }
+        } else if head_hash == base_hash {
+            // The head did not touch the file, so the change applies as it is
+            replay
+                .changes
+                .push((file.clone(), target_hash.map(|hash| (hash.clone(), mode))));
+        } else if let (Some(head_hash), Some(target_hash)) = (head_hash, target_hash) {
+            let head_content = read_object(objects_path, head_hash)?;
+            let target_content = read_object(objects_path, target_hash)?;
+            let base_content = match base_hash {
+                Some(base_hash) => Some(read_object(objects_path, base_hash)?),
+                None => None,
+            };
+
+            // Binary contents and links cannot be merged line by line, so the head keeps its version
+            if is_binary_file(&head_content)
+                || is_binary_file(&target_content)
+                || base_content
+                    .as_ref()
+                    .is_some_and(|base| is_binary_file(base))
+                || head_mode == SYMLINK_MODE
+                || target_mode == SYMLINK_MODE
+            {
+                replay.conflicts.push((file.clone(), None));
+                continue;
+            }
+
+            let head_text = String::from_utf8_lossy(&head_content);
+            let target_text = String::from_utf8_lossy(&target_content);
+            let mut merged_content = match base_content {
+                Some(base_content) => merge_contents(
+                    &String::from_utf8_lossy(&base_content),
+                    head_label,
+                    &head_text,
+                    change_label,
+                    &target_text,
+                ),
+                // Added on both sides with different contents
+                None => merge_conflict(head_label, &head_text, change_label, &target_text),
+            };
+
+            if head_text.ends_with('\n') {
+                merged_content.push('\n');
+            }
+
+            if merged_content
+                .lines()
+                .any(|line| line.starts_with("<<<<<<< "))
+            {
+                replay.conflicts.push((file.clone(), Some(merged_content)));
+            } else {
+                let hash = write_object(objects_path, merged_content.as_bytes())?;
+                replay.changes.push((file.clone(), Some((hash, mode))));
+            }
+        } else {
+            // Deleted on one side and changed on the other; the head keeps its version
+            replay.conflicts.push((file.clone(), None));
+        }
+    }
+
+    Ok(replay)
+}
+
+// Refuses to replay a change over staged changes, or over local changes to the files it touches
+fn check_working_files(
+    path: &str,
+    branch: &str,
+    head: &RevisionMetadata,
+    replay: &Replay,
+) -> Result<(), io::Error> {
+    let files = replay
+        .changes
+        .iter()
+        .map(|(file, _)| file)
+        .chain(replay.conflicts.iter().map(|(file, _)| file));
+
+    check_unchanged_files(path, branch, head, files)
+}
+
+// Refuses to go on if anything is staged, or if any of `files` differs from its version in `head`
+fn check_unchanged_files<'a>(
+    path: &str,
+    branch: &str,
+    head: &RevisionMetadata,
+    files: impl Iterator<Item = &'a String>,
+) -> Result<(), io::Error> {
+    if !load_branch_metadata(path, branch)?.staging.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "There are staged changes. Commit them first",
+        ));
+    }
+
+    let mut stat_cache = load_stat_cache(&get_stat_cache_path(path));
+
+    for file in files {
+        let working_path = format!("{}/{}", path, file);
+        let modified = match head.files.get(file) {
+            Some(hash) => {
+                !check_file(&working_path)
+                    || hash_file_cached(&mut stat_cache, &working_path, file)? != *hash
+            }
+            None => check_file(&working_path),
+        };
+
+        if modified {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Local changes to '{}' would be overwritten. Commit or discard them first",
+                    file
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Stages the changes of a replay and writes them into the working tree. Conflicted files are only
+// written into the working tree, with their conflict markers, for the user to resolve
+fn apply_replay(path: &str, branch: &str, replay: &Replay) -> Result<(), io::Error> {
+    let objects_path = format!("{}/.dvcs/objects", path);
+    let staging_path = format!("{}/.dvcs/origin/{}/staging", path, branch);
+    let stat_cache_path = get_stat_cache_path(path);
+    let mut stat_cache = load_stat_cache(&stat_cache_path);
+    let mut branch_metadata = load_branch_metadata(path, branch)?;
+
+    for (file, change) in replay.changes.iter() {
+        let staged_path = format!("{}/{}", staging_path, file);
+        let working_path = format!("{}/{}", path, file);
+
+        if let Some((hash, mode)) = change {
+            for directory in [get_parent(&staged_path), get_parent(&working_path)] {
+                if !check_directory(&directory) {
+                    create_directory(&directory)?;
+                }
+            }
+
+            // A large file is staged as its manifest, as `add` does
+            match read_object_manifest(&objects_path, hash) {
+                Some(manifest) if *mode != SYMLINK_MODE => {
+                    write_chunk_manifest(&staged_path, &manifest)?;
+                    set_file_mode(&staged_path, *mode)?;
+                }
+                _ => write_entry(&staged_path, &read_object(&objects_path, hash)?, *mode)?,
+            }
+
+            write_object_file(&objects_path, hash, &working_path, *mode)?;
+            update_stat_cache(&mut stat_cache, &working_path, file, hash);
+        } else {
+            // A staged file without a staged copy is committed as a deletion
+            if check_file(&working_path) {
+                delete_file(&working_path)?;
+            }
+
+            stat_cache.entries.remove(file);
+        }
+
+        if !branch_metadata.staging.contains(file) {
+            branch_metadata.staging.push(file.clone());
+        }
+    }
+
+    for (file, content) in replay.conflicts.iter() {
+        if let Some(content) = content {
+            write_file(&format!("{}/{}", path, file), content)?;
+        }
+    }
+
+    save_branch_metadata(path, branch, &branch_metadata)?;
+    save_stat_cache(&stat_cache_path, &stat_cache)?;
+    Ok(())
+}
+
+fn format_replay(replay: &Replay, head_label: &str) -> String {
+    let mut report = String::new();
+
+    for (file, change) in replay.changes.iter() {
+        report.push_str(&match change {
+            Some(_) => format!("File updated: '{}'\n", file),
+            None => format!("File removed: '{}'\n", file),
+        });
+    }
+
+    for (file, content) in replay.conflicts.iter() {
+        report.push_str(&match content {
+            Some(_) => format!("Conflict in file: '{}'\n", file),
+            None => format!(
+                "Conflict in file (kept '{}' version): '{}'\n",
+                head_label, file
+            ),
+        });
+    }
+
+    report
+}
+
+// Resolves a branch name or revision id to the id of a revision
+fn resolve_revision(path: &str, revision: &str) -> Result<String, io::Error> {
+    let (_, _, revision_id) = get_branch_or_revision_id(path, revision)?;
+
+    if revision_id.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("No commits in branch '{}' yet...", revision),
+        ));
+    }
+
+    Ok(revision_id)
+}
+
+// Commits the inverse of a revision's change, relative to its first parent, on the current branch
+pub fn revert(path: &str, revision: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    check_no_sequence(path)?;
+    let branch = &load_repo_metadata(path)?.head;
+    let head_id = load_branch_metadata(path, branch)?
+        .head_commit
+        .ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("No commits in branch '{}' yet...", branch),
+        ))?;
+    let revision_id = resolve_revision(path, revision)?;
+    let objects_path = format!("{}/.dvcs/objects", path);
+    let reverted = load_revision_metadata(path, &revision_id)?;
+    let parent = match reverted.parents.first() {
+        Some(parent_id) => load_revision_metadata(path, parent_id)?,
+        None => init_revision_metadata(), // Reverting the first revision removes its files
+    };
+    let head = load_revision_metadata(path, &head_id)?;
+    let change_label = format!("revert of {}", revision_id);
+
+    // The inverse change leads from the reverted revision back to its parent
+    let replay = replay_change(
+        &objects_path,
+        &reverted,
+        &parent,
+        &head,
+        branch,
+        &change_label,
+    )?;
+
+    if replay.changes.is_empty() && replay.conflicts.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Nothing to revert: branch '{}' does not contain the changes of revision '{}'",
+                branch, revision_id
+            ),
+        ));
+    }
+
+    check_working_files(path, branch, &head, &replay)?;
+    apply_replay(path, branch, &replay)?;
+    let mut report = format_replay(&replay, branch);
+
+    // The revert is finished by 'revert --continue', which commits it with its message
+    if !replay.conflicts.is_empty() {
+        let mut sequence = Sequence {
+            operation: "revert".to_string(),
+            branch: branch.clone(),
+            original_head: Some(head_id),
+            current: None,
+            conflicts: Vec::new(),
+            pending: Vec::new(),
+            onto: None,
+        };
+        stop_sequence(&mut sequence, &revision_id, &replay);
+        save_sequence(path, &sequence)?;
+        report.push_str(&format_stop(&sequence, &revision_id));
+        return Ok(report);
+    }
+
+    let (message, _) = get_sequence_commit(path, "revert", &revision_id)?;
+    let revert_id = commit(path, &message)?;
+
+    Ok(format!(
+        "{}Reverted revision '{}' as '{}'",
+        report, revision_id, revert_id
+    ))
+}
+
+pub fn revert_continue(path: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    continue_sequence(path, "revert")
+}
+
+pub fn revert_abort(path: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    abort_sequence(path, "revert")
+}
+
+fn get_sequence_path(path: &str) -> String {
+    format!("{}/.dvcs/sequence.json", path)
+}
+
+fn load_sequence(path: &str, operation: &str) -> Result<Sequence, io::Error> {
+    let sequence_path = get_sequence_path(path);
+
+    if !check_file(&sequence_path) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No {} in progress", operation),
+        ));
+    }
+
+    let sequence: Sequence = read_struct(&sequence_path)?;
+
+    if sequence.operation != operation {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "A {} is in progress, not a {}",
+                sequence.operation, operation
+            ),
+        ));
+    }
+
+    Ok(sequence)
+}
+
+fn save_sequence(path: &str, sequence: &Sequence) -> Result<(), io::Error> {
+    write_struct(&get_sequence_path(path), sequence)?;
+    Ok(())
+}
+
+// Refuses to go on while a sequence is stopped, since changing the branch or its working tree would
+// leave it unable to continue or abort cleanly
+pub fn check_no_sequence(path: &str) -> Result<(), io::Error> {
+    check_sequence_branch(path, None)
+}
+
+// Like `check_no_sequence`, but lets `branch` through if it is the one the sequence runs on
+pub fn check_sequence_branch(path: &str, branch: Option<&str>) -> Result<(), io::Error> {
+    let sequence_path = get_sequence_path(path);
+
+    if check_file(&sequence_path) {
+        let sequence: Sequence = read_struct(&sequence_path)?;
+
+        if branch == Some(sequence.branch.as_str()) {
+            return Ok(());
+        }
+
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "A {} is in progress. Finish it with '{} --continue' or undo it with '{} --abort'",
+                sequence.operation, sequence.operation, sequence.operation
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+// Replays the change a revision made to its first parent onto the head of the branch and commits
+// it with the revision's message and author. Returns false if it stopped on conflicts, which are
+// left in the working tree
+fn replay_revision(
+    path: &str,
+    sequence: &mut Sequence,
+    revision_id: &str,
+    report: &mut String,
+) -> Result<bool, io::Error> {
+    let branch = &sequence.branch;
+    let objects_path = format!("{}/.dvcs/objects", path);
+    let revision = load_revision_metadata(path, revision_id)?;
+    let parent = match revision.parents.first() {
+        Some(parent_id) => load_revision_metadata(path, parent_id)?,
+        None => init_revision_metadata(),
+    };
+    let head = match load_branch_metadata(path, branch)?.head_commit {
+        Some(head_id) => load_revision_metadata(path, &head_id)?,
+        None => init_revision_metadata(),
+    };
+    // The head side of conflicts is the branch, or during a rebase the upstream it now sits on
+    let head_label = sequence.onto.as_deref().unwrap_or(branch);
+    let replay = replay_change(
+        &objects_path,
+        &parent,
+        &revision,
+        &head,
+        head_label,
+        &format!("{} of {}", sequence.operation, revision_id),
+    )?;
+
+    if replay.changes.is_empty() && replay.conflicts.is_empty() {
+        report.push_str(&format!(
+            "Skipped revision '{}': its changes are already in branch '{}'\n",
+            revision_id, branch
+        ));
+        return Ok(true);
+    }
+
+    check_working_files(path, branch, &head, &replay)?;
+    apply_replay(path, branch, &replay)?;
+    report.push_str(&format_replay(&replay, head_label));
+
+    if !replay.conflicts.is_empty() {
+        stop_sequence(sequence, revision_id, &replay);
+        return Ok(false);
+    }
+
+    let new_id = commit_as(path, &revision.message, revision.author.as_ref())?;
+    report.push_str(&format!(
+        "Applied revision '{}' as '{}'\n",
+        revision_id, new_id
+    ));
+    Ok(true)
+}
+
+// Records the revision a sequence stopped on and the files left with conflict markers
+fn stop_sequence(sequence: &mut Sequence, revision_id: &str, replay: &Replay) {
+    sequence.current = Some(revision_id.to_string());
+    sequence.conflicts = replay
+        .conflicts
+        .iter()
+        .filter(|(_, content)| content.is_some())
+        .map(|(file, _)| file.clone())
+        .collect();
+}
+
+fn format_stop(sequence: &Sequence, revision_id: &str) -> String {
+    let operation = &sequence.operation;
+    let skip = if operation == "rebase" {
+        format!(", '{} --skip' to drop the revision", operation)
+    } else {
+        String::new()
+    };
+
+    format!(
+        "Stopped on conflicts in revision '{}'. Resolve them and add the files, then run '{} --continue'{}, or '{} --abort' to undo the {}",
+        revision_id, operation, skip, operation, operation
+    )
+}
+
+// Message and author of the commit a sequence makes for a revision: a revert is committed by the
+// current user with a message naming the reverted revision, other revisions keep their own
+fn get_sequence_commit(
+    path: &str,
+    operation: &str,
+    revision_id: &str,
+) -> Result<(String, Option<Signature>), io::Error> {
+    let revision = load_revision_metadata(path, revision_id)?;
+
+    if operation == "revert" {
+        Ok((
+            format!(
+                "Revert '{}' (reverts revision {})",
+                revision.message, revision_id
+            ),
+            None,
+        ))
+    } else {
+        Ok((revision.message, revision.author))
+    }
+}
+
+// Replays the pending revisions of a sequence one at a time, saving its progress, until all are
+// applied or one stops on conflicts
+fn run_sequence(path: &str, mut sequence: Sequence) -> Result<String, io::Error> {
+    let mut report = String::new();
+
+    while !sequence.pending.is_empty() {
+        let revision_id = sequence.pending.remove(0);
+
+        if !replay_revision(path, &mut sequence, &revision_id, &mut report)? {
+            save_sequence(path, &sequence)?;
+            report.push_str(&format_stop(&sequence, &revision_id));
+            return Ok(report);
+        }
+
+        save_sequence(path, &sequence)?;
+    }
+
+    delete_file(&get_sequence_path(path))?;
+    report.push_str(&format!(
+        "Finished {} on branch '{}'",
+        sequence.operation, sequence.branch
+    ));
+    Ok(report)
+}
+
+// Commits the resolution of the revision a sequence stopped on, then replays the rest
+fn continue_sequence(path: &str, operation: &str) -> Result<String, io::Error> {
+    let mut sequence = load_sequence(path, operation)?;
+
+    if load_repo_metadata(path)?.head != sequence.branch {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "The {} runs on branch '{}'. Check it out again to continue",
+                operation, sequence.branch
+            ),
+        ));
+    }
+
+    if let Some(revision_id) = sequence.current.take() {
+        let staging = load_branch_metadata(path, &sequence.branch)?.staging;
+
+        if let Some(file) = sequence
+            .conflicts
+            .iter()
+            .find(|file| !staging.contains(file))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Conflicts in '{}' are not resolved. Resolve them and add the file",
+                    file
+                ),
+            ));
+        }
+
+        // Resolving the conflicts may leave nothing to commit, which drops the revision
+        if !staging.is_empty() {
+            let (message, author) = get_sequence_commit(path, operation, &revision_id)?;
+            commit_as(path, &message, author.as_ref())?;
+        }
+
+        sequence.conflicts.clear();
+        save_sequence(path, &sequence)?;
+    }
+
+    run_sequence(path, sequence)
+}
+
+// Drops the revision a sequence stopped on, discarding its conflicts, then replays the rest
+fn skip_sequence(path: &str, operation: &str) -> Result<String, io::Error> {
+    let mut sequence = load_sequence(path, operation)?;
+
+    if load_repo_metadata(path)?.head != sequence.branch {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "The {} runs on branch '{}'. Check it out again to skip",
+                operation, sequence.branch
+            ),
+        ));
+    }
+
+    let revision_id = sequence.current.take().ok_or(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("The {} has not stopped on a revision to skip", operation),
+    ))?;
+    let head_id = load_branch_metadata(path, &sequence.branch)?.head_commit;
+    reset_branch(path, &sequence.branch, head_id.as_ref())?;
+    sequence.conflicts.clear();
+    save_sequence(path, &sequence)?;
+
+    Ok(format!(
+        "Skipped revision '{}'\n{}",
+        revision_id,
+        run_sequence(path, sequence)?
+    ))
+}
+
+// Puts the branch of a sequence back where it was before the sequence started
+fn abort_sequence(path: &str, operation: &str) -> Result<String, io::Error> {
+    let sequence = load_sequence(path, operation)?;
+
+    if load_repo_metadata(path)?.head != sequence.branch {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "The {} runs on branch '{}'. Check it out again to abort",
+                operation, sequence.branch
+            ),
+        ));
+    }
+
+    reset_branch(path, &sequence.branch, sequence.original_head.as_ref())?;
+    delete_file(&get_sequence_path(path))?;
+
+    Ok(format!(
+        "Aborted {} and restored branch '{}'",
+        operation, sequence.branch
+    ))
+}
+
+// Lists the revisions a sequence in progress still refers to, which must be kept even when no
+// branch reaches them, e.g. the original head of a branch being rebased
+pub fn get_sequence_revisions(path: &str) -> Result<Vec<String>, io::Error> {
+    let sequence_path = get_sequence_path(path);
+
+    if !check_file(&sequence_path) {
+        return Ok(Vec::new());
+    }
+
+    let sequence: Sequence = read_struct(&sequence_path)?;
+    let mut revisions: Vec<String> = sequence.original_head.into_iter().collect();
+    revisions.extend(sequence.current);
+    revisions.extend(sequence.pending);
+    Ok(revisions)
+}
+
+// Points a branch at a revision, clears its staging area and makes the working tree match the
+// revision. Files tracked or staged before that the revision does not contain are removed
+fn reset_branch(path: &str, branch: &str, revision_id: Option<&String>) -> Result<(), io::Error> {
+    let objects_path = format!("{}/.dvcs/objects", path);
+    let staging_path = format!("{}/.dvcs/origin/{}/staging", path, branch);
+    let stat_cache_path = get_stat_cache_path(path);
+    let mut stat_cache = load_stat_cache(&stat_cache_path);
+    let mut branch_metadata = load_branch_metadata(path, branch)?;
+    let revision = match revision_id {
+        Some(revision_id) => load_revision_metadata(path, revision_id)?,
+        None => init_revision_metadata(),
+    };
+
+    let mut tracked: BTreeSet<String> = branch_metadata.staging.iter().cloned().collect();
+
+    if let Some(head_id) = &branch_metadata.head_commit {
+        tracked.extend(load_revision_metadata(path, head_id)?.files.into_keys());
+    }
+
+    for file in tracked {
+        let working_path = format!("{}/{}", path, file);
+
+        if !revision.files.contains_key(&file) && check_file(&working_path) {
+            delete_file(&working_path)?;
+            stat_cache.entries.remove(&file);
+        }
+    }
+
+    for (file, hash) in revision.files.iter() {
+        let working_path = format!("{}/{}", path, file);
+        let working_dir = get_parent(&working_path);
+
+        if !check_directory(&working_dir) {
+            create_directory(&working_dir)?;
+        }
+
+        write_object_file(
+            &objects_path,
+            hash,
+            &working_path,
+            get_revision_file_mode(&revision, file),
+        )?;
+        update_stat_cache(&mut stat_cache, &working_path, file, hash);
+    }
+
+    if check_directory(&staging_path) {
+        delete_directory(&staging_path, true)?;
+    }
+
+    create_directory(&staging_path)?;
+    branch_metadata.staging.clear();
+    branch_metadata.head_commit = revision_id.cloned();
+    save_branch_metadata(path, branch, &branch_metadata)?;
+
+    let mut repo_metadata = load_repo_metadata(path)?;
+    repo_metadata
+        .branches
+        .insert(branch.to_string(), revision_id.cloned().unwrap_or_default());
+    save_repo_metadata(path, &repo_metadata)?;
+    write_file(
+        &format!("{}/.dvcs/HEAD", path),
+        &format!(
+            "commit: {}\nref: {}/.dvcs/origin/{}",
+            revision_id.map(String::as_str).unwrap_or("N/A"),
+            get_parent(path),
+            branch
+        ),
+    )?;
+    save_stat_cache(&stat_cache_path, &stat_cache)?;
+
+    Ok(())
+}
+
+// Applies the changes of each revision, in order, as new commits on the current branch
+pub fn cherry_pick(path: &str, revisions: &[String]) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    check_no_sequence(path)?;
+
+    let branch = load_repo_metadata(path)?.head;
+    let original_head = load_branch_metadata(path, &branch)?.head_commit;
+    let pending = revisions
+        .iter()
+        .map(|revision| resolve_revision(path, revision))
+        .collect::<Result<Vec<String>, io::Error>>()?;
+
+    run_sequence(
+        path,
+        Sequence {
+            operation: "cherry-pick".to_string(),
+            branch,
+            original_head,
+            current: None,
+            conflicts: Vec::new(),
+            pending,
+            onto: None,
+        },
+    )
+}
+
+pub fn cherry_pick_continue(path: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    continue_sequence(path, "cherry-pick")
+}
+
+pub fn cherry_pick_abort(path: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    abort_sequence(path, "cherry-pick")
+}
+
+// Moves the current branch onto the head of `upstream` and replays the revisions it made since
+// their fork point on top, one at a time and oldest first
+pub fn rebase(path: &str, upstream: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    check_no_sequence(path)?;
+
+    let branch = load_repo_metadata(path)?.head;
+    let head_id = load_branch_metadata(path, &branch)?
+        .head_commit
+        .ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("No commits in branch '{}' yet...", branch),
+        ))?;
+    let upstream_id = resolve_revision(path, upstream)?;
+    let (_, fork_point) = get_common_ancestor_and_count(path, &head_id, path, &upstream_id)?;
+
+    if fork_point.as_ref() == Some(&upstream_id) {
+        return Ok(format!(
+            "Branch '{}' is already up to date with '{}'",
+            branch, upstream
+        ));
+    }
+
+    // The branch's own revisions are those on its first-parent line that upstream does not reach.
+    // Merge commits are left out, as their changes come from the revisions they merge
+    let upstream_ancestors: HashSet<String> =
+        get_ancestors(path, &upstream_id)?.into_iter().collect();
+    let mut pending = Vec::new();
+    let mut current = Some(head_id.clone());
+
+    while let Some(revision_id) = current {
+        if upstream_ancestors.contains(&revision_id) {
+            break;
+        }
+
+        let revision = load_revision_metadata(path, &revision_id)?;
+
+        if revision.parents.len() < 2 {
+            pending.push(revision_id);
+        }
+
+        current = revision.parents.first().cloned();
+    }
+
+    pending.reverse();
+
+    let head = load_revision_metadata(path, &head_id)?;
+    let upstream_head = load_revision_metadata(path, &upstream_id)?;
+    check_unchanged_files(
+        path,
+        &branch,
+        &head,
+        head.files.keys().chain(upstream_head.files.keys()),
+    )?;
+
+    // The sequence is saved before the branch moves, so a replay that fails can still be aborted
+    let sequence = Sequence {
+        operation: "rebase".to_string(),
+        branch: branch.clone(),
+        original_head: Some(head_id),
+        current: None,
+        conflicts: Vec::new(),
+        pending,
+        onto: Some(upstream.to_string()),
+    };
+    save_sequence(path, &sequence)?;
+    reset_branch(path, &branch, Some(&upstream_id))?;
+
+    let report = format!(
+        "Rebasing {} revision(s) of branch '{}' onto '{}' ({})\n",
+        sequence.pending.len(),
+        branch,
+        upstream,
+        fork_point.map_or("no common ancestor".to_string(), |fork_point| format!(
+            "forked at '{}'",
+            fork_point
+        ))
+    );
+
+    Ok(format!("{}{}", report, run_sequence(path, sequence)?))
+}
+
+pub fn rebase_continue(path: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    continue_sequence(path, "rebase")
+}
+
+pub fn rebase_skip(path: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    skip_sequence(path, "rebase")
+}
+
+pub fn rebase_abort(path: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    abort_sequence(path, "rebase")
+}
+
+// Collapses a run of consecutive revisions on the current branch, `from..to` with both ends included
+// or the last `last` ones, into a single revision with the tree of the newest. Revisions made after
+// the run are rewritten on top of it with their trees unchanged, so the working tree is untouched
+pub fn squash(
+    path: &str,
+    range: &str,
+    last: usize,
+    message: &str,
+    force: bool,
+) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    check_no_sequence(path)?;
+
+    let mut repo_metadata = load_repo_metadata(path)?;
+    let branch = repo_metadata.head.clone();
+    let mut branch_metadata = load_branch_metadata(path, &branch)?;
+    let head_id = branch_metadata.head_commit.clone().ok_or(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("No commits in branch '{}' yet...", branch),
+    ))?;
+
+    // The first-parent line of the branch, newest first
+    let mut line = Vec::new();
+    let mut current = Some(head_id.clone());
+
+    while let Some(revision_id) = current {
+        let revision = load_revision_metadata(path, &revision_id)?;
+        current = revision.parents.first().cloned();
+        line.push(revision);
+    }
+
+    let find = |revision_id: &str| {
+        line.iter()
+            .position(|revision| revision.id == revision_id)
+            .ok_or(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Revision '{}' is not on the history of branch '{}'",
+                    revision_id, branch
+                ),
+            ))
+    };
+
+    // Positions in `line` of the newest and the oldest revision to squash
+    let (newest, oldest) = if last > 0 {
+        if last > line.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Branch '{}' has only {} revision(s) to squash",
+                    branch,
+                    line.len()
+                ),
+            ));
+        }
+
+        (0, last - 1)
+    } else {
+        let (from, to) = range.split_once("..").ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Invalid range '{}'. Use '<from>..<to>' or '--last <N>'",
+                range
+            ),
+        ))?;
+        // An open range `<from>..` runs up to the head of the current branch
+        let (to, to_revision) = if to.is_empty() {
+            ("HEAD", branch.as_str())
+        } else {
+            (to, to)
+        };
+        let newest = find(&resolve_revision(path, to_revision)?)?;
+        let oldest = find(&resolve_revision(path, from)?)?;
+
+        if oldest < newest {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Revision '{}' comes after '{}'. Give the older revision first",
+                    from, to
+                ),
+            ));
+        }
+
+        (newest, oldest)
+    };
+
+    if newest == oldest {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Nothing to squash: the range holds a single revision",
+        ));
+    }
+
+    // Every revision from the oldest squashed one up to the head gets a new id
+    if !force {
+        if let Some(revision) = line[..=oldest]
+            .iter()
+            .find(|revision| is_published(path, &revision.id))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Revision '{}' has already been pushed to the remote repository. Use --force to squash it anyway",
+                    revision.id
+                ),
+            ));
+        }
+    }
+
+    let journal = begin_operation(path, "squash", std::slice::from_ref(&branch))?;
+    let committer = get_signature(path, "committer")?;
+    let squashed = &line[newest..=oldest];
+    let mut revision = RevisionMetadata {
+        id: String::new(),
+        files: line[newest].files.clone(),
+        modes: line[newest].modes.clone(),
+        parents: line[oldest].parents.clone(),
+        author: line[oldest].author.clone(),
+        committer: Some(committer.clone()),
+        // Without a message of its own, the squashed revision lists theirs, oldest first
+        message: if message.is_empty() {
+            squashed
+                .iter()
+                .rev()
+                .map(|revision| revision.message.as_str())
+                .collect::<Vec<&str>>()
+                .join("\n")
+        } else {
+            message.to_string()
+        },
+        timestamp: SystemTime::now(),
+    };
+    revision.id = hash_revision(&revision);
+    save_revision_metadata(path, &revision.id, &revision)?;
+    let squash_id = revision.id.clone();
+
+    let mut report = format!(
+        "Squashed {} revision(s) into '{}'\n",
+        squashed.len(),
+        squash_id
+    );
+    let mut parent_id = squash_id.clone();
+
+    for later in line[..newest].iter().rev() {
+        let mut rewritten = later.clone();
+        rewritten.parents[0] = parent_id;
+        rewritten.committer = Some(committer.clone());
+        rewritten.id = hash_revision(&rewritten);
+        save_revision_metadata(path, &rewritten.id, &rewritten)?;
+        report.push_str(&format!(
+            "Rewrote revision '{}' as '{}'\n",
+            later.id, rewritten.id
+        ));
+        parent_id = rewritten.id;
+    }
+
+    journal.record_target(&branch, &parent_id, false)?;
+    branch_metadata.head_commit = Some(parent_id.clone());
+    save_branch_metadata(path, &branch, &branch_metadata)?;
+    repo_metadata
+        .branches
+        .insert(branch.clone(), parent_id.clone());
+    save_repo_metadata(path, &repo_metadata)?;
+    write_file(
+        &format!("{}/.dvcs/HEAD", path),
+        &format!(
+            "commit: {}\nref: {}/.dvcs/origin/{}",
+            parent_id,
+            get_parent(path),
+            branch
+        ),
+    )?;
+    journal.finish()?;
+
+    report.push_str(&format!(
+        "Branch '{}' now points to '{}'",
+        branch, parent_id
+    ));
+    Ok(report)
+}
@@ -2,14 +2,16 @@
 //
 
 use super::b_3_3_branch_management::{init_branch, is_branch};
+use super::b_3_6_journal_management::recover_operation;
 
 use crate::a_1_file_system_hiding::{
     b_1_1_file_interaction::{
-        check_file, get_absolute_path, get_parent, read_struct, write_struct,
+        check_file, delete_file, get_absolute_path, get_parent, read_struct, write_struct,
     },
     b_1_2_directory_interaction::{
-        check_directory, copy_directory, create_directory, is_empty_directory,
+        check_directory, copy_directory, create_directory, is_empty_directory, list_directory,
     },
+    b_1_5_lock_management::{acquire_lock, clear_lock, LockGuard},
     REMOTE,
 };
 
@@ -17,13 +19,95 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io;
 
+// On-disk format written by this version; version 1 is the per-branch layout of v0.1.0 and v0.2.0
+pub const FORMAT_VERSION: u32 = 2;
+pub const LEGACY_FORMAT_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RepositoryMetadata {
+    #[serde(default)]
+    pub version: u32, // On-disk format version, 0 if the repository predates it
     pub head: String,                      // Current branch name
     pub branches: HashMap<String, String>, // Branch name -> commit ID
 }
 
 pub fn is_repository(path: &str) -> Result<String, io::Error> {
+    let repo_path = find_repository(path)?;
+    check_format_version(&repo_path)?;
+    Ok(repo_path)
+}
+
+// Refuses repositories in another on-disk format. Unreadable metadata is left for the command
+// itself (or 'fsck') to report
+pub fn check_format_version(repo_path: &str) -> Result<(), io::Error> {
+    match get_format_version(repo_path) {
+        Ok(version) if version > FORMAT_VERSION => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "Repository '{}' uses format version {}, but this days_dvcs only supports up to version {}. Upgrade days_dvcs to use it",
+                repo_path, version, FORMAT_VERSION
+            ),
+        )),
+        Ok(version) if version < FORMAT_VERSION => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "Repository '{}' uses the older format version {}. Run 'migrate' to upgrade it to version {}",
+                repo_path, version, FORMAT_VERSION
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Finds the repository containing `path` by its '.dvcs' directory alone, so 'fsck' can reach a
+// repository whose metadata or main branch is missing
+pub fn find_repository_root(path: &str) -> Result<String, io::Error> {
+    let mut absolute_path = get_absolute_path(path, "")?;
+
+    loop {
+        if check_directory(&format!("{}/.dvcs", absolute_path)) {
+            return Ok(absolute_path);
+        }
+
+        let parent = get_parent(&absolute_path);
+
+        if parent.is_empty() {
+            break;
+        }
+
+        absolute_path = parent;
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "No repository found starting from '{}': no .dvcs directory",
+            path
+        ),
+    ))
+}
+
+pub fn get_format_version(path: &str) -> Result<u32, io::Error> {
+    let repo_metadata = load_repo_metadata(path)?;
+
+    if repo_metadata.version != 0 {
+        return Ok(repo_metadata.version);
+    }
+
+    // Repositories from before the version was recorded are told apart by their layout
+    let origin_path = format!("{}/.dvcs/origin", path);
+
+    for branch in list_directory(&origin_path, false, false)? {
+        if check_directory(&format!("{}/{}/commits", origin_path, branch)) {
+            return Ok(LEGACY_FORMAT_VERSION);
+        }
+    }
+
+    Ok(FORMAT_VERSION)
+}
+
+// Finds the repository containing `path` without checking its format version
+pub fn find_repository(path: &str) -> Result<String, io::Error> {
     let mut absolute_path = get_absolute_path(path, "")?;
     let mut error;
 
@@ -80,28 +164,28 @@ pub fn is_repository(path: &str) -> Result<String, io::Error> {
 }
 
 pub fn init_repository(path: &str, remote: bool) -> Result<(), io::Error> {
-    match is_repository(path) {
-        Ok(root_path) => {
-            let current_path = get_absolute_path(path, "")?;
+    if let Ok(root_path) = is_repository(path) {
+        let current_path = get_absolute_path(path, "")?;
 
-            if root_path == current_path && path != REMOTE {
-                return Err(io::Error::new(
-                    io::ErrorKind::AlreadyExists,
-                    format!("The directory '{}' is already a repository.", path),
-                ));
-            }
+        if root_path == current_path && path != REMOTE {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("The directory '{}' is already a repository.", path),
+            ));
         }
-        Err(_) => {}
     }
 
     let repo_path = format!("{}/.dvcs", path);
     create_directory(&format!("{}/.metadata", repo_path))?;
-    init_branch(&path, "main", true)?;
+    create_directory(&format!("{}/objects", repo_path))?;
+    create_directory(&format!("{}/commits", repo_path))?;
+    init_branch(path, "main", true)?;
     let init_metadata = RepositoryMetadata {
+        version: FORMAT_VERSION,
         head: "main".to_string(),
         branches: HashMap::from([("main".to_string(), String::new())]),
     };
-    save_repo_metadata(&path, &init_metadata)?;
+    save_repo_metadata(path, &init_metadata)?;
     if remote {
         init_repository(&format!("{}/{}", path, REMOTE), false)?;
     }
@@ -128,19 +212,54 @@ pub fn clone_repository(src: &str, dest: &str) -> Result<(), io::Error> {
         }
     }
 
-    if check_directory(dest) {
-        if is_empty_directory(dest).is_err() {
-            return Err(io::Error::new(
-                io::ErrorKind::AlreadyExists,
-                format!("The directory '{}' already exists and is not empty.", dest),
-            ));
-        }
+    if check_directory(dest) && is_empty_directory(dest).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("The directory '{}' already exists and is not empty.", dest),
+        ));
     }
 
+    // Hold the source lock so the copy is a consistent snapshot, but do not copy the lock itself
+    let _lock = lock_repository(src)?;
     copy_directory(src, dest)?;
+
+    let dest_lock_path = get_lock_path(dest);
+
+    if check_file(&dest_lock_path) {
+        delete_file(&dest_lock_path)?;
+    }
+
     Ok(())
 }
 
+pub fn get_lock_path(path: &str) -> String {
+    format!("{}/.dvcs/index.lock", path)
+}
+
+pub fn get_stat_cache_path(path: &str) -> String {
+    format!("{}/.dvcs/index.json", path)
+}
+
+pub fn lock_repository(path: &str) -> Result<LockGuard, io::Error> {
+    let lock = acquire_lock(&get_lock_path(path))?;
+
+    // A journal left without a lock holder belongs to an operation that was interrupted
+    if lock.is_owned() {
+        recover_operation(path)?;
+    }
+
+    Ok(lock)
+}
+
+pub fn unlock_repository(path: &str, force: bool) -> Result<String, io::Error> {
+    let path = &find_repository(path)?;
+
+    match clear_lock(&get_lock_path(path), force)? {
+        Some(pid) => Ok(format!("Removed lock held by process {}", pid)),
+        None => Ok("Removed lock held by an unknown process".to_string()),
+    }
+}
+
 pub fn save_repo_metadata(path: &str, metadata: &RepositoryMetadata) -> Result<(), io::Error> {
     let metadata_path = format!("{}/.dvcs/.metadata/metadata.json", path);
     write_struct(&metadata_path, metadata)?;
@@ -1,41 +1,74 @@
 // days_dvcs/src/a_3_repository_hiding/b_3_2_revision_management.rs
 //
 
+use super::b_3_11_configuration_management::{
+    get_config_value, get_default_message, get_remote_path,
+};
+use super::b_3_12_history_management::{check_no_sequence, check_sequence_branch};
 use super::b_3_1_repository_management::{
-    is_repository, load_repo_metadata, save_repo_metadata, RepositoryMetadata,
+    get_stat_cache_path, is_repository, load_repo_metadata, lock_repository, save_repo_metadata,
+    RepositoryMetadata,
 };
 use super::b_3_3_branch_management::{load_branch_metadata, save_branch_metadata};
+use super::b_3_6_journal_management::begin_operation;
+use super::b_3_9_pack_management::get_pack_objects;
 
 use crate::a_1_file_system_hiding::{
     b_1_1_file_interaction::{
-        check_file, get_filename, get_parent, read_file, read_struct, write_file, write_struct,
+        check_file, delete_file, get_filename, get_parent, is_binary_file, read_entry, read_struct,
+        write_file, write_struct,
     },
     b_1_2_directory_interaction::{check_directory, create_directory, delete_directory},
+    b_1_3_metadata_management::{
+        get_file_mode, load_stat_cache, save_stat_cache, update_stat_cache, REGULAR_FILE_MODE,
+    },
+    b_1_4_object_management::{
+        check_object, hash_content, read_object, verify_object, write_object,
+    },
+    b_1_6_pack_management::{delete_pack, write_pack},
+    b_1_7_chunk_management::{read_chunk_manifest, write_object_file},
 };
 
 use chrono::DateTime;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fmt::Debug;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt::{self, Debug};
 use std::io;
-use std::time::SystemTime;
-use uuid::Uuid;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RevisionMetadata {
     pub id: String,                     // Unique identifier for the revision
     pub files: HashMap<String, String>, // Maps file paths to their content hashes
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub modes: HashMap<String, u32>, // Modes of the files that are not regular files
     pub parents: Vec<String>,           // Parent revisions (supports merges)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<Signature>, // Who made the change, if recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub committer: Option<Signature>, // Who recorded the revision, if recorded
     pub message: String,                // Commit message
     pub timestamp: SystemTime,          // Timestamp of the commit
 }
 
-#[allow(unused)]
-pub fn is_revision(path: &str, branch: &str, revision_id: &str) -> Result<(), io::Error> {
-    let revision_path = format!("{}/.dvcs/origin/{}/commits/{}", path, branch, revision_id);
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub name: String,  // Name of the person
+    pub email: String, // Email address of the person
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
+    }
+}
+
+pub fn is_revision(path: &str, revision_id: &str) -> Result<(), io::Error> {
+    let revision_path = format!("{}/.dvcs/commits/{}", path, revision_id);
     let metadata_path = format!("{}/.metadata/metadata.json", revision_path);
 
-    if !check_file(&revision_path) {
+    if revision_id.is_empty() || !check_directory(&revision_path) {
         return Err(io::Error::new(
             io::ErrorKind::NotFound,
             format!("Revision does not exist '{}'", revision_path),
@@ -56,20 +89,61 @@ pub fn init_revision_metadata() -> RevisionMetadata {
     RevisionMetadata {
         id: String::new(),
         files: HashMap::new(),
+        modes: HashMap::new(),
         parents: Vec::new(),
+        author: None,
+        committer: None,
         message: String::new(),
         timestamp: SystemTime::now(),
     }
 }
 
+// Identifies the author or committer of a new revision. DVCS_AUTHOR_NAME, DVCS_AUTHOR_EMAIL and
+// their DVCS_COMMITTER_ counterparts take precedence over 'user.name' and 'user.email' in the
+// repository and then the user configuration; the login name is the last resort
+pub fn get_signature(path: &str, role: &str) -> Result<Signature, io::Error> {
+    let lookup = |field: &str| match env::var(format!(
+        "DVCS_{}_{}",
+        role.to_uppercase(),
+        field.to_uppercase()
+    )) {
+        Ok(value) if !value.is_empty() => Ok(Some(value)),
+        _ => get_config_value(path, &format!("user.{}", field)),
+    };
+    let login = env::var("USER").unwrap_or("unknown".to_string());
+
+    Ok(Signature {
+        name: lookup("name")?.unwrap_or(login.clone()),
+        email: lookup("email")?.unwrap_or(format!("{}@localhost", login)),
+    })
+}
+
+// Lists the author, and the committer if it is someone else, as shown by 'log' and 'heads'
+pub fn format_signatures(revision: &RevisionMetadata) -> String {
+    let mut signatures = String::new();
+
+    if let Some(author) = &revision.author {
+        signatures.push_str(&format!("Author: {}\n", author));
+    }
+
+    if let Some(committer) = revision
+        .committer
+        .as_ref()
+        .filter(|committer| revision.author.as_ref() != Some(*committer))
+    {
+        signatures.push_str(&format!("Committer: {}\n", committer));
+    }
+
+    signatures
+}
+
 pub fn load_revision_metadata(
     path: &str,
-    branch: &str,
     revision_id: &str,
 ) -> Result<RevisionMetadata, io::Error> {
     let metadata_path = format!(
-        "{}/.dvcs/origin/{}/commits/{}/.metadata/metadata.json",
-        path, branch, revision_id
+        "{}/.dvcs/commits/{}/.metadata/metadata.json",
+        path, revision_id
     );
     let metadata: RevisionMetadata = read_struct(&metadata_path)?;
     Ok(metadata)
@@ -77,59 +151,340 @@ pub fn load_revision_metadata(
 
 pub fn save_revision_metadata(
     path: &str,
-    branch: &str,
     revision_id: &str,
     metadata: &RevisionMetadata,
 ) -> Result<(), io::Error> {
-    let metadata_path = format!(
-        "{}/.dvcs/origin/{}/commits/{}/.metadata/metadata.json",
-        path, branch, revision_id
-    );
-    write_struct(&metadata_path, metadata)?;
+    let metadata_dir = format!("{}/.dvcs/commits/{}/.metadata", path, revision_id);
+
+    if !check_directory(&metadata_dir) {
+        create_directory(&metadata_dir)?;
+    }
+
+    write_struct(&format!("{}/metadata.json", metadata_dir), metadata)?;
     Ok(())
 }
 
+pub fn get_revision_file_mode(revision: &RevisionMetadata, file: &str) -> u32 {
+    revision
+        .modes
+        .get(file)
+        .copied()
+        .unwrap_or(REGULAR_FILE_MODE)
+}
+
+// Regular files are listed without their mode, so trees recorded before modes keep their hash
+pub fn hash_tree(files: &HashMap<String, String>, modes: &HashMap<String, u32>) -> String {
+    let mut entries: Vec<_> = files.iter().collect();
+    entries.sort();
+
+    let tree = entries
+        .iter()
+        .map(|(file, hash)| match modes.get(*file) {
+            Some(mode) if *mode != REGULAR_FILE_MODE => format!("{:o} {} {}\n", mode, hash, file),
+            _ => format!("{} {}\n", hash, file),
+        })
+        .collect::<String>();
+    hash_content(tree.as_bytes())
+}
+
+pub fn hash_revision(revision: &RevisionMetadata) -> String {
+    let mut content = format!("tree {}\n", hash_tree(&revision.files, &revision.modes));
+
+    for parent in &revision.parents {
+        content.push_str(&format!("parent {}\n", parent));
+    }
+
+    // Revisions recorded without identities keep their hash
+    if let Some(author) = &revision.author {
+        content.push_str(&format!("author {}\n", author));
+    }
+
+    if let Some(committer) = &revision.committer {
+        content.push_str(&format!("committer {}\n", committer));
+    }
+
+    let timestamp = revision
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    content.push_str(&format!(
+        "timestamp {}.{:09}\n\n{}",
+        timestamp.as_secs(),
+        timestamp.subsec_nanos(),
+        revision.message
+    ));
+    hash_content(content.as_bytes())
+}
+
+pub fn verify_revision(path: &str, revision_id: &str) -> Result<RevisionMetadata, io::Error> {
+    let revision_metadata = load_revision_metadata(path, revision_id)?;
+    let actual_id = hash_revision(&revision_metadata);
+
+    if revision_metadata.id != revision_id || actual_id != revision_id {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Revision '{}' is corrupt: content hashes to '{}'",
+                revision_id, actual_id
+            ),
+        ));
+    }
+
+    Ok(revision_metadata)
+}
+
+pub fn get_ancestors(path: &str, revision_id: &str) -> Result<Vec<String>, io::Error> {
+    collect_ancestors(path, revision_id, |_| false)
+}
+
+pub fn is_ancestor(path: &str, ancestor_id: &str, revision_id: &str) -> Result<bool, io::Error> {
+    if ancestor_id.is_empty() || revision_id.is_empty() {
+        return Ok(false);
+    }
+
+    Ok(get_ancestors(path, revision_id)?.contains(&ancestor_id.to_string()))
+}
+
+// Walks the commit graph from `revision_id`, returning parents before their children
+fn collect_ancestors(
+    path: &str,
+    revision_id: &str,
+    skip: impl Fn(&str) -> bool,
+) -> Result<Vec<String>, io::Error> {
+    let mut ancestors = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![(revision_id.to_string(), false)];
+
+    while let Some((current, expanded)) = stack.pop() {
+        if expanded {
+            ancestors.push(current);
+            continue;
+        }
+
+        if skip(&current) || !visited.insert(current.clone()) {
+            continue;
+        }
+
+        let revision_metadata = load_revision_metadata(path, &current).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "Revision '{}' not found in repository '{}': {}",
+                    current,
+                    get_filename(path),
+                    e
+                ),
+            )
+        })?;
+        stack.push((current, true));
+
+        for parent in revision_metadata.parents.iter().rev() {
+            if !visited.contains(parent) {
+                stack.push((parent.clone(), false));
+            }
+        }
+    }
+
+    Ok(ancestors)
+}
+
+pub fn copy_revisions(src: &str, dest: &str, revision_id: &str) -> Result<Vec<String>, io::Error> {
+    let src_objects_path = format!("{}/.dvcs/objects", src);
+    let dest_objects_path = format!("{}/.dvcs/objects", dest);
+    let missing_revisions =
+        collect_ancestors(src, revision_id, |id| is_revision(dest, id).is_ok())?;
+
+    // Every revision must hash to its id, and every object to its hash, before it is accepted
+    let mut revisions = Vec::new();
+
+    for missing_revision_id in missing_revisions.iter() {
+        revisions.push(verify_revision(src, missing_revision_id)?);
+    }
+
+    // The objects the destination lacks are transferred as a single pack
+    let missing_objects: Vec<_> = get_pack_objects(src, &missing_revisions)?
+        .into_iter()
+        .filter(|(hash, _)| !check_object(&dest_objects_path, hash))
+        .collect();
+    let pack = write_pack(&src_objects_path, &dest_objects_path, &missing_objects)?;
+
+    for (hash, _) in missing_objects.iter() {
+        if let Err(e) = verify_object(&dest_objects_path, hash) {
+            if let Some(pack) = &pack {
+                delete_pack(&dest_objects_path, pack)?;
+            }
+
+            return Err(e);
+        }
+    }
+
+    for revision_metadata in revisions.iter() {
+        save_revision_metadata(dest, &revision_metadata.id, revision_metadata)?;
+    }
+
+    Ok(missing_revisions)
+}
+
 pub fn commit(path: &str, message: &str) -> Result<String, io::Error> {
+    create_commit(path, message, &[], false, false, None)
+}
+
+pub fn commit_merge(path: &str, message: &str, merge_parent: &str) -> Result<String, io::Error> {
+    create_commit(
+        path,
+        message,
+        &[merge_parent.to_string()],
+        false,
+        false,
+        None,
+    )
+}
+
+// Replaces the head of the current branch with a revision of its tree plus the staged changes.
+// Without a message, the head's message is kept
+pub fn commit_amend(path: &str, message: &str, force: bool) -> Result<String, io::Error> {
+    create_commit(path, message, &[], true, force, None)
+}
+
+// Commits the staged changes on behalf of another author, e.g. when replaying their revision
+pub fn commit_as(
+    path: &str,
+    message: &str,
+    author: Option<&Signature>,
+) -> Result<String, io::Error> {
+    create_commit(path, message, &[], false, false, author)
+}
+
+// Checks whether the configured remote repository already holds a revision
+pub fn is_published(path: &str, revision_id: &str) -> bool {
+    get_remote_path(path).is_ok_and(|remote_path| is_revision(&remote_path, revision_id).is_ok())
+}
+
+fn create_commit(
+    path: &str,
+    message: &str,
+    merge_parents: &[String],
+    amend: bool,
+    force: bool,
+    author: Option<&Signature>,
+) -> Result<String, io::Error> {
     let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
 
     let mut repo_metadata = load_repo_metadata(path)?;
     let branch = &repo_metadata.head;
     let mut branch_metadata = load_branch_metadata(path, branch)?;
 
-    if branch_metadata.staging.is_empty() {
+    let amended_revision = if amend {
+        check_no_sequence(path)?;
+        let head_commit = branch_metadata.head_commit.clone().ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("No commit to amend in branch '{}'", branch),
+        ))?;
+
+        // Others may already have the head, so replacing it must be asked for explicitly
+        if !force && is_published(path, &head_commit) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Revision '{}' has already been pushed to the remote repository. Use --force to amend it anyway",
+                    head_commit
+                ),
+            ));
+        }
+
+        Some(load_revision_metadata(path, &head_commit)?)
+    } else if branch_metadata.staging.is_empty() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             "No changes to commit",
         ));
-    }
+    } else {
+        None
+    };
 
-    let revision_id = Uuid::new_v4().to_string();
+    let journal = begin_operation(
+        path,
+        if amend { "amend" } else { "commit" },
+        std::slice::from_ref(branch),
+    )?;
     let staged_path = format!("{}/.dvcs/origin/{}/staging", path, branch);
-    let commit_path = format!("{}/.dvcs/origin/{}/commits/{}", path, branch, revision_id);
+    let objects_path = format!("{}/.dvcs/objects", path);
 
-    // Create the commit directory
-    create_directory(&commit_path)?;
+    // Create new revision metadata, inheriting the parent's snapshot
+    let mut new_revision = RevisionMetadata {
+        id: String::new(),
+        files: HashMap::new(),
+        modes: HashMap::new(),
+        parents: Vec::new(),
+        author: Some(match author {
+            Some(author) => author.clone(),
+            None => get_signature(path, "author")?,
+        }),
+        committer: Some(get_signature(path, "committer")?),
+        // A commit made without a message gets the configured default
+        message: if message.is_empty() {
+            get_default_message(path)?
+        } else {
+            message.to_string()
+        },
+        timestamp: SystemTime::now(),
+    };
 
-    let mut files = HashMap::new();
+    if let Some(amended_revision) = amended_revision {
+        // The replacement takes the place of the head: same parents, same tree, same author
+        new_revision.files = amended_revision.files;
+        new_revision.modes = amended_revision.modes;
+        new_revision.parents = amended_revision.parents;
 
-    // Prepare files and compute their hashes
-    for file in &branch_metadata.staging {
-        let src_path = format!("{}/{}", staged_path, file);
-        let dest_path = format!("{}/{}", commit_path, file);
-        let dest_dir = get_parent(&dest_path);
+        if amended_revision.author.is_some() {
+            new_revision.author = amended_revision.author;
+        }
+
+        if message.is_empty() {
+            new_revision.message = amended_revision.message;
+        }
+    } else if let Some(parent_revision_id) = branch_metadata.head_commit.clone() {
+        let parent_revision_metadata = load_revision_metadata(path, &parent_revision_id)?;
+        new_revision.files = parent_revision_metadata.files;
+        new_revision.modes = parent_revision_metadata.modes;
+        new_revision.parents.push(parent_revision_id);
+    }
 
-        if !check_directory(&dest_dir) {
-            create_directory(&dest_dir)?;
+    for merge_parent in merge_parents {
+        if !new_revision.parents.contains(merge_parent) {
+            new_revision.parents.push(merge_parent.clone());
         }
+    }
+
+    // Apply the staged additions, modifications and deletions on top of the snapshot
+    for file in &branch_metadata.staging {
+        let src_path = format!("{}/{}", staged_path, file);
 
         if check_file(&src_path) {
-            let content = read_file(&src_path)?;
-            write_file(&dest_path, &content)?; // Copy the file content to the commit
-            let file_hash = format!(
-                "{:x}",
-                Uuid::new_v5(&Uuid::NAMESPACE_OID, &content.as_bytes())
-            );
-            files.insert(file.clone(), file_hash);
+            // A large file was already stored as chunks when it was staged
+            let file_hash = match read_chunk_manifest(&src_path) {
+                Some(manifest) if check_object(&objects_path, &manifest.hash) => manifest.hash,
+                Some(manifest) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!(
+                            "Object '{}' of staged file '{}' does not exist. Add the file again",
+                            manifest.hash, src_path
+                        ),
+                    ))
+                }
+                None => write_object(&objects_path, &read_entry(&src_path)?)?,
+            };
+            new_revision.files.insert(file.clone(), file_hash);
+
+            // The staged copy carries the mode of the working file
+            match get_file_mode(&src_path)? {
+                REGULAR_FILE_MODE => new_revision.modes.remove(file),
+                mode => new_revision.modes.insert(file.clone(), mode),
+            };
+        } else if new_revision.files.remove(file).is_some() {
+            new_revision.modes.remove(file);
         } else {
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
@@ -138,34 +493,16 @@ pub fn commit(path: &str, message: &str) -> Result<String, io::Error> {
         }
     }
 
-    // Create new revision metadata
-    let mut new_revision = RevisionMetadata {
-        id: revision_id.clone(),
-        files,
-        parents: Vec::new(),
-        message: message.to_string(),
-        timestamp: SystemTime::now(),
-    };
+    // The revision id is the hash of its tree, parents, message and timestamp
+    let revision_id = hash_revision(&new_revision);
+    new_revision.id = revision_id.clone();
 
-    if let Some(parent_revision_id) = branch_metadata.head_commit {
-        let parent_revision_metadata = load_revision_metadata(path, branch, &parent_revision_id)?;
-        new_revision
-            .parents
-            .extend(parent_revision_metadata.parents);
-        new_revision.parents.push(parent_revision_id);
-    }
-
-    // Save the revision metadata
-    create_directory(&format!("{}/.metadata", commit_path))?;
-    save_revision_metadata(path, &repo_metadata.head, &revision_id, &new_revision)?;
-
-    // Clear the staging area after committing
-    delete_directory(&staged_path, true)?;
-    create_directory(&staged_path)?;
+    // Save the revision metadata; objects and revisions are written before any ref points at them
+    save_revision_metadata(path, &revision_id, &new_revision)?;
+    journal.record_target(branch, &revision_id, true)?;
 
     // Update branch metadata
     branch_metadata.head_commit = Some(revision_id.clone());
-    branch_metadata.commits.push(revision_id.clone());
     branch_metadata.staging.clear();
     save_branch_metadata(path, branch, &branch_metadata)?;
 
@@ -180,10 +517,24 @@ pub fn commit(path: &str, message: &str) -> Result<String, io::Error> {
         &format!(
             "commit: {}\nref: {}/.dvcs/origin/{}",
             revision_id,
-            get_parent(&path),
+            get_parent(path),
             branch
         ),
     )?;
+
+    // Clear the staging area once the refs are updated; leftover copies are unreferenced
+    delete_directory(&staged_path, true)?;
+    create_directory(&staged_path)?;
+
+    let stat_cache_path = get_stat_cache_path(path);
+    let mut stat_cache = load_stat_cache(&stat_cache_path);
+    let staging_prefix = format!(".dvcs/origin/{}/staging/", branch);
+    stat_cache
+        .entries
+        .retain(|key, _| !key.starts_with(&staging_prefix));
+    save_stat_cache(&stat_cache_path, &stat_cache)?;
+
+    journal.finish()?;
     Ok(revision_id)
 }
 
@@ -192,53 +543,58 @@ pub fn log(path: &str) -> Result<String, io::Error> {
 
     let repo_metadata = load_repo_metadata(path)?;
     let head_branch = &repo_metadata.head;
+    let mut revision_ids = HashSet::new();
     let mut logs = Vec::new();
 
-    for (branch, _) in repo_metadata.branches.iter() {
-        let branch_metadata = load_branch_metadata(path, branch)?;
-
-        for revision_id in branch_metadata.commits.iter() {
-            let revision_metadata = load_revision_metadata(path, branch, revision_id)?;
-            let date_time: DateTime<chrono::Local> = revision_metadata.timestamp.into();
-
-            match branch_metadata.head_commit.clone() {
-                Some(head_commit) => {
-                    let header = if revision_id == &head_commit {
-                        let mut head = format!("\x1b[31morigin/{}\x1b[0m", branch);
+    for revision_id in repo_metadata.branches.values() {
+        if !revision_id.is_empty() {
+            revision_ids.extend(get_ancestors(path, revision_id)?);
+        }
+    }
 
-                        if branch == head_branch {
-                            head = format!("\x1b[1;36mHEAD\x1b[0m \x1b[1;36m->\x1b[0m \x1b[32m{}\x1b[0m, {}, \x1b[31morigin/HEAD\x1b[0m", branch, head);
-                        }
+    for revision_id in revision_ids.iter() {
+        let revision_metadata = load_revision_metadata(path, revision_id)?;
+        let date_time: DateTime<chrono::Local> = revision_metadata.timestamp.into();
+        let mut branches: Vec<&String> = repo_metadata
+            .branches
+            .iter()
+            .filter(|(_, branch_head)| *branch_head == revision_id)
+            .map(|(branch, _)| branch)
+            .collect();
+        branches.sort();
+
+        let header = if branches.is_empty() {
+            format!("\x1b[33mcommit {}\x1b[0m\n", revision_metadata.id)
+        } else {
+            let heads = branches
+                .iter()
+                .map(|branch| {
+                    let head = format!("\x1b[31morigin/{}\x1b[0m", branch);
 
-                        format!(
-                            "\x1b[33mcommit {} ({})\x1b[0m\n",
-                            revision_metadata.id, head
-                        )
+                    if *branch == head_branch {
+                        format!("\x1b[1;36mHEAD\x1b[0m \x1b[1;36m->\x1b[0m \x1b[32m{}\x1b[0m, {}, \x1b[31morigin/HEAD\x1b[0m", branch, head)
                     } else {
-                        format!("\x1b[33mcommit {}\x1b[0m\n", revision_metadata.id)
-                    };
-
-                    let content = format!(
-                        "\n\x1b[0m{}Date: {}\n\n\t{}\x1b[0m\n\n",
-                        header,
-                        format!("{}", date_time.format("%Y-%m-%d %H:%M:%S")),
-                        revision_metadata.message,
-                    );
+                        head
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!(
+                "\x1b[33mcommit {} ({})\x1b[0m\n",
+                revision_metadata.id, heads
+            )
+        };
+
+        let content = format!(
+            "\n\x1b[0m{}{}Date: {}\n\n\t{}\x1b[0m\n\n",
+            header,
+            format_signatures(&revision_metadata),
+            date_time.format("%Y-%m-%d %H:%M:%S"),
+            revision_metadata.message,
+        );
 
-                    logs.push((revision_metadata.timestamp, content));
-                }
-                None => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        format!(
-                            "In repository '{}': branch '{}' has commits but no head commit",
-                            get_filename(path),
-                            branch
-                        ),
-                    ));
-                }
-            };
-        }
+        logs.push((revision_metadata.timestamp, content));
     }
 
     logs.sort_by_key(|(timestamp, _)| *timestamp);
@@ -277,12 +633,9 @@ fn get_revision_id(
         ));
     }
 
-    for (branch, _) in repo_metadata.branches.iter() {
-        let branch_metadata = load_branch_metadata(path, branch)?;
-
-        if branch_metadata.commits.contains(&revision_id.to_string()) {
-            return Ok((repo_metadata, branch_metadata.name, revision_id.to_string()));
-        }
+    if is_revision(path, revision_id).is_ok() {
+        let branch = get_revision_branch(path, &repo_metadata, revision_id)?;
+        return Ok((repo_metadata, branch, revision_id.to_string()));
     }
 
     Err(io::Error::new(
@@ -295,6 +648,30 @@ fn get_revision_id(
     ))
 }
 
+// Prefers the current branch, then a branch pointing at the revision, then one containing it
+fn get_revision_branch(
+    path: &str,
+    repo_metadata: &RepositoryMetadata,
+    revision_id: &str,
+) -> Result<String, io::Error> {
+    let mut branches: Vec<&String> = repo_metadata.branches.keys().collect();
+    branches.sort_by_key(|branch| (*branch != &repo_metadata.head, branch.to_string()));
+
+    for branch in branches.iter() {
+        if repo_metadata.branches[*branch] == revision_id {
+            return Ok(branch.to_string());
+        }
+    }
+
+    for branch in branches.iter() {
+        if is_ancestor(path, revision_id, &repo_metadata.branches[*branch])? {
+            return Ok(branch.to_string());
+        }
+    }
+
+    Ok(repo_metadata.head.clone())
+}
+
 pub fn get_branch_or_revision_id(
     path: &str,
     branch_or_revision_id: &str,
@@ -319,18 +696,25 @@ pub fn get_branch_or_revision_id(
 
 pub fn cat(path: &str, revision_id: &str, file_name: &str) -> Result<String, io::Error> {
     let path = &is_repository(path)?;
-    let (_, branch, last_revision_id) = get_revision_id(path, revision_id)?;
+    let (_, _, last_revision_id) = get_revision_id(path, revision_id)?;
     if !last_revision_id.is_empty() {
-        let revision_metadata = load_revision_metadata(path, &branch, &last_revision_id)?;
-
-        if revision_metadata.files.contains_key(file_name) {
-            let file_path = format!(
-                "{}/.dvcs/origin/{}/commits/{}/{}",
-                path, branch, last_revision_id, file_name
-            );
-            let file_content = read_file(&file_path)?;
-
-            Ok(format!("\x1b[0m{}\x1b[0m", file_content))
+        let revision_metadata = load_revision_metadata(path, &last_revision_id)?;
+
+        if let Some(file_hash) = revision_metadata.files.get(file_name) {
+            let file_content = read_object(&format!("{}/.dvcs/objects", path), file_hash)?;
+
+            if is_binary_file(&file_content) {
+                Ok(format!(
+                    "\x1b[33mBinary file '{}' ({} bytes)\x1b[0m",
+                    file_name,
+                    file_content.len()
+                ))
+            } else {
+                Ok(format!(
+                    "\x1b[0m{}\x1b[0m",
+                    String::from_utf8_lossy(&file_content)
+                ))
+            }
         } else {
             Err(io::Error::new(
                 io::ErrorKind::NotFound,
@@ -344,27 +728,56 @@ pub fn cat(path: &str, revision_id: &str, file_name: &str) -> Result<String, io:
 
 pub fn checkout(path: &str, branch_or_revision_id: &str) -> Result<(), io::Error> {
     let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
     let (mut repo_metadata, branch, last_revision_id) =
         get_branch_or_revision_id(path, branch_or_revision_id)?;
+    check_sequence_branch(path, Some(&branch))?;
     let branch_metadata = load_branch_metadata(path, &branch)?;
     if !last_revision_id.is_empty() {
-        let revision_metadata = load_revision_metadata(path, &branch, &last_revision_id)?;
-        let commit_path = format!(
-            "{}/.dvcs/origin/{}/commits/{}",
-            path, branch, &last_revision_id
-        );
+        let revision_metadata = load_revision_metadata(path, &last_revision_id)?;
+        let objects_path = format!("{}/.dvcs/objects", path);
+
+        let stat_cache_path = get_stat_cache_path(path);
+        let mut stat_cache = load_stat_cache(&stat_cache_path);
 
         if !check_directory(path) {
             create_directory(path)?;
         }
 
-        for (file, _) in revision_metadata.files.iter() {
-            let src_path = format!("{}/{}", commit_path, file);
-            let dest_path = format!("{}/{}", path, file);
+        // Remove files tracked by the current head that the target revision does not contain
+        if let Some(current_revision_id) =
+            load_branch_metadata(path, &repo_metadata.head)?.head_commit
+        {
+            let current_revision_metadata = load_revision_metadata(path, &current_revision_id)?;
+
+            for file in current_revision_metadata.files.keys() {
+                let file_path = format!("{}/{}", path, file);
 
-            let content = read_file(&src_path)?;
-            write_file(&dest_path, &content)?;
+                if !revision_metadata.files.contains_key(file) && check_file(&file_path) {
+                    delete_file(&file_path)?;
+                    stat_cache.entries.remove(file);
+                }
+            }
         }
+
+        for (file, file_hash) in revision_metadata.files.iter() {
+            let dest_path = format!("{}/{}", path, file);
+            let dest_dir = get_parent(&dest_path);
+
+            if !check_directory(&dest_dir) {
+                create_directory(&dest_dir)?;
+            }
+
+            write_object_file(
+                &objects_path,
+                file_hash,
+                &dest_path,
+                get_revision_file_mode(&revision_metadata, file),
+            )?;
+            update_stat_cache(&mut stat_cache, &dest_path, file, file_hash);
+        }
+
+        save_stat_cache(&stat_cache_path, &stat_cache)?;
     }
 
     repo_metadata.head = branch.to_string();
@@ -375,7 +788,7 @@ pub fn checkout(path: &str, branch_or_revision_id: &str) -> Result<(), io::Error
         &format!(
             "commit: {}\nref: {}/.dvcs/origin/{}",
             branch_metadata.head_commit.unwrap_or("N/A".to_string()),
-            get_parent(&path),
+            get_parent(path),
             branch
         ),
     )?;
@@ -1,18 +1,32 @@
 // days_dvcs/src/a_3_repository_hiding/b_3_3_branch_management.rs
 //
 
-use super::b_3_1_repository_management::{is_repository, load_repo_metadata, save_repo_metadata};
-use super::b_3_2_revision_management::{init_revision_metadata, load_revision_metadata};
+use super::b_3_11_configuration_management::{
+    get_ignored_names, get_large_file_threshold, get_remote_path,
+};
+use super::b_3_12_history_management::check_no_sequence;
+use super::b_3_1_repository_management::{
+    get_stat_cache_path, is_repository, load_repo_metadata, lock_repository, save_repo_metadata,
+};
+use super::b_3_2_revision_management::{
+    format_signatures, get_ancestors, get_revision_file_mode, init_revision_metadata,
+    load_revision_metadata,
+};
 
 use crate::a_1_file_system_hiding::{
     b_1_1_file_interaction::{
-        check_file, delete_file, get_absolute_path, get_filename, get_parent, get_relative_path,
-        read_file, read_struct, write_file, write_struct,
+        check_file, check_link, delete_file, get_absolute_path, get_filename, get_parent,
+        get_relative_path, read_entry, read_struct, write_file, write_struct,
     },
     b_1_2_directory_interaction::{
         check_directory, create_directory, delete_directory, list_directory,
     },
-    REMOTE,
+    b_1_3_metadata_management::{
+        get_file_metadata, get_file_mode, hash_file_cached, load_stat_cache, save_stat_cache,
+        set_file_mode, update_stat_cache, write_entry, StatCache, SYMLINK_MODE,
+    },
+    b_1_4_object_management::hash_content,
+    b_1_7_chunk_management::{read_chunk_manifest, write_chunk_manifest, write_chunked_object},
 };
 
 use chrono::DateTime;
@@ -21,13 +35,11 @@ use std::collections::{HashSet, VecDeque};
 use std::fmt::Debug;
 use std::io;
 use std::time::SystemTime;
-use uuid::Uuid;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BranchMetadata {
     pub name: String,                // Branch name
     pub head_commit: Option<String>, // Latest commit on the branch
-    pub commits: Vec<String>,        // Base commit (for merges or new branches)
     pub staging: Vec<String>,        // Files staged for commit on this branch
 }
 
@@ -39,18 +51,13 @@ pub fn is_branch(path: &str) -> Result<String, io::Error> {
             let metadata_path = format!("{}/.metadata/metadata.json", branch_path);
 
             if check_file(&metadata_path) {
-                let commits_path = format!("{}/commits", branch_path);
                 let staging_path = format!("{}/staging", branch_path);
 
-                if !check_directory(&commits_path) {
-                    create_directory(&commits_path)?;
-                }
-
                 if !check_directory(&staging_path) {
                     create_directory(&staging_path)?;
                 }
 
-                return Ok(branch_path)
+                return Ok(branch_path);
             }
         }
 
@@ -65,15 +72,19 @@ pub fn is_branch(path: &str) -> Result<String, io::Error> {
 
     Err(io::Error::new(
         io::ErrorKind::NotFound,
-        format!("No branch found starting from '{}'", get_filename(&path)),
+        format!("No branch found starting from '{}'", get_filename(path)),
     ))
 }
 
 pub fn init_branch(test_path: &str, branch: &str, init_repo: bool) -> Result<(), io::Error> {
     let mut path = test_path.to_string();
+    let mut head_commit = None;
+    let mut _lock = None;
 
     if !init_repo {
         path = is_repository(test_path)?;
+        _lock = Some(lock_repository(&path)?);
+        check_no_sequence(&path)?; // Creating a branch also checks it out
 
         if load_branch_metadata(&path, branch).is_ok() {
             return Err(io::Error::new(
@@ -81,17 +92,19 @@ pub fn init_branch(test_path: &str, branch: &str, init_repo: bool) -> Result<(),
                 format!("Branch '{}' already exists.", branch),
             ));
         }
+
+        // A new branch starts as a pointer to the current head commit
+        let repo_metadata = load_repo_metadata(&path)?;
+        head_commit = load_branch_metadata(&path, &repo_metadata.head)?.head_commit;
     }
 
     let branch_path = format!("{}/.dvcs/origin/{}", path, branch);
-    create_directory(&format!("{}/commits", branch_path))?;
     create_directory(&format!("{}/staging", branch_path))?;
     create_directory(&format!("{}/.metadata", branch_path))?;
 
     let init_metadata = BranchMetadata {
         name: branch.to_string(),
-        head_commit: None,
-        commits: Vec::new(),
+        head_commit: head_commit.clone(),
         staging: Vec::new(),
     };
     save_branch_metadata(&path, branch, &init_metadata)?;
@@ -101,13 +114,17 @@ pub fn init_branch(test_path: &str, branch: &str, init_repo: bool) -> Result<(),
         repo_metadata.head = branch.to_string();
         repo_metadata
             .branches
-            .insert(branch.to_string(), String::new());
+            .insert(branch.to_string(), head_commit.clone().unwrap_or_default());
         save_repo_metadata(&path, &repo_metadata)?;
     }
 
     write_file(
         &format!("{}/.dvcs/HEAD", path),
-        &format!("commit: {}\nref: origin/{}", "N/A", branch),
+        &format!(
+            "commit: {}\nref: origin/{}",
+            head_commit.unwrap_or("N/A".to_string()),
+            branch
+        ),
     )?;
     Ok(())
 }
@@ -137,13 +154,39 @@ pub fn add(test_path: &str, files: Vec<String>) -> Result<(), io::Error> {
     }
 
     let path = &is_repository(test_path)?;
+    let _lock = lock_repository(path)?;
     let repo_metadata = load_repo_metadata(path)?;
     let branch = &repo_metadata.head;
     let mut branch_metadata = load_branch_metadata(path, branch)?;
+    let latest_revision = if let Some(head_commit) = &branch_metadata.head_commit {
+        load_revision_metadata(path, head_commit)?
+    } else {
+        init_revision_metadata()
+    };
+    let ignored = get_ignored_names(path)?;
     let mut files_to_stage = Vec::new();
+    let mut files_to_delete = Vec::new();
 
     for file in files.iter() {
-        let file_path = get_absolute_path(file, "")?;
+        // A link is added as a link, so only the directory containing it is resolved
+        let resolved_path = if check_link(file) {
+            get_link_path(file)
+        } else {
+            get_absolute_path(file, "")
+        };
+        let file_path = match resolved_path {
+            Ok(file_path) => file_path,
+            Err(e) => {
+                // A tracked file that no longer exists is staged as a deletion
+                match get_missing_path(path, file) {
+                    Some(relative_path) if latest_revision.files.contains_key(&relative_path) => {
+                        files_to_delete.push(relative_path);
+                        continue;
+                    }
+                    _ => return Err(e),
+                }
+            }
+        };
 
         if !file_path.starts_with(path) {
             return Err(io::Error::new(
@@ -153,19 +196,30 @@ pub fn add(test_path: &str, files: Vec<String>) -> Result<(), io::Error> {
         }
 
         if check_file(&file_path) {
-            files_to_stage.push(file_path.clone());
+            files_to_stage.push((file_path.clone(), true));
         } else if check_directory(&file_path) {
-            files_to_stage.extend(list_directory(&file_path, true, true)?.into_iter().filter(
-                |f| {
-                    !f.strip_prefix(path).unwrap_or_default().contains(".dvcs")
-                        && !f.strip_prefix(path).unwrap_or_default().contains(REMOTE)
-                        && !f.strip_prefix(path).unwrap_or_default().contains(".git")
-                        && !f
-                            .strip_prefix(path)
-                            .unwrap_or_default()
-                            .contains(".DS_Store")
-                },
-            ));
+            files_to_stage.extend(
+                list_directory(&file_path, true, true)?
+                    .into_iter()
+                    .filter(|f| {
+                        !get_relative_path(f, path, true)
+                            .split('/')
+                            .any(|name| ignored.iter().any(|ignored| ignored == name))
+                    })
+                    .map(|f| (f, false)),
+            );
+
+            let directory = get_relative_path(&file_path, path, true);
+            files_to_delete.extend(
+                latest_revision
+                    .files
+                    .keys()
+                    .filter(|f| {
+                        (directory.is_empty() || f.starts_with(&format!("{}/", directory)))
+                            && !check_file(&format!("{}/{}", path, f))
+                    })
+                    .cloned(),
+            );
         } else {
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
@@ -174,7 +228,7 @@ pub fn add(test_path: &str, files: Vec<String>) -> Result<(), io::Error> {
         }
     }
 
-    if files_to_stage.is_empty() {
+    if files_to_stage.is_empty() && files_to_delete.is_empty() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             format!(
@@ -188,7 +242,10 @@ pub fn add(test_path: &str, files: Vec<String>) -> Result<(), io::Error> {
         ));
     }
 
-    for file in files_to_stage {
+    let stat_cache_path = get_stat_cache_path(path);
+    let mut stat_cache = load_stat_cache(&stat_cache_path);
+
+    for (file, explicit) in files_to_stage {
         let relative_path = get_relative_path(&file, path, true);
         let staging_dir = format!(
             "{}/.dvcs/origin/{}/staging/{}",
@@ -198,15 +255,36 @@ pub fn add(test_path: &str, files: Vec<String>) -> Result<(), io::Error> {
         );
 
         let staging_path = format!("{}/{}", staging_dir, get_filename(&file));
-        let content = read_file(&file)?;
+        let staging_key = get_relative_path(&staging_path, path, true);
+
+        let mode = get_file_mode(&file)?;
+        let hash = hash_file_cached(&mut stat_cache, &file, &relative_path)?;
 
         if !branch_metadata.staging.contains(&relative_path) {
+            // Unchanged tracked files are recognized from the cache without being read
+            if !explicit
+                && latest_revision.files.get(&relative_path) == Some(&hash)
+                && get_revision_file_mode(&latest_revision, &relative_path) == mode
+            {
+                continue; // Skip tracked files that are unchanged since the latest revision
+            }
+
             create_directory(&staging_dir)?;
-            write_file(&staging_path, &content)?;
+            let staged_hash = stage_file(path, &file, &staging_path, mode)?;
+            update_stat_cache(&mut stat_cache, &staging_path, &staging_key, &staged_hash);
             branch_metadata.staging.push(relative_path);
-        } else if read_file(&file)? != content {
-            write_file(&staging_path, &content)?; // Overwrite only if content differs
-        } else {
+            continue;
+        }
+
+        if !check_file(&staging_path)
+            || hash_staged_file(&mut stat_cache, &staging_path, &staging_key)? != hash
+            || get_file_mode(&staging_path)? != mode
+        {
+            // Overwrite only if content or mode differs
+            create_directory(&staging_dir)?;
+            let staged_hash = stage_file(path, &file, &staging_path, mode)?;
+            update_stat_cache(&mut stat_cache, &staging_path, &staging_key, &staged_hash);
+        } else if explicit {
             return Err(io::Error::new(
                 io::ErrorKind::AlreadyExists,
                 format!("File '{}' is already staged for commit", relative_path),
@@ -214,12 +292,80 @@ pub fn add(test_path: &str, files: Vec<String>) -> Result<(), io::Error> {
         }
     }
 
+    for relative_path in files_to_delete {
+        let staging_path = format!("{}/.dvcs/origin/{}/staging/{}", path, branch, relative_path);
+
+        if check_file(&staging_path) {
+            delete_file(&staging_path)?;
+        }
+
+        stat_cache.entries.remove(&relative_path);
+        stat_cache
+            .entries
+            .remove(&get_relative_path(&staging_path, path, true));
+
+        if !branch_metadata.staging.contains(&relative_path) {
+            branch_metadata.staging.push(relative_path);
+        }
+    }
+
     save_branch_metadata(path, branch, &branch_metadata)?;
+    save_stat_cache(&stat_cache_path, &stat_cache)?;
     Ok(())
 }
 
+// Copies a file into the staging area with its mode and returns the hash of the staged content.
+// A large file goes straight into the object store as chunks and only its manifest is staged
+fn stage_file(path: &str, file: &str, staging_path: &str, mode: u32) -> Result<String, io::Error> {
+    if mode != SYMLINK_MODE && get_file_metadata(file)?.size >= get_large_file_threshold(path)? {
+        let manifest = write_chunked_object(&format!("{}/.dvcs/objects", path), file)?;
+        write_chunk_manifest(staging_path, &manifest)?;
+        set_file_mode(staging_path, mode)?;
+        return Ok(manifest.hash);
+    }
+
+    let content = read_entry(file)?;
+    write_entry(staging_path, &content, mode)?; // The staged copy carries the mode
+    Ok(hash_content(&content))
+}
+
+// Returns the hash of the content a staged file stands for, which for a large file is the hash
+// its manifest records
+fn hash_staged_file(
+    stat_cache: &mut StatCache,
+    staging_path: &str,
+    key: &str,
+) -> Result<String, io::Error> {
+    match read_chunk_manifest(staging_path) {
+        Some(manifest) => Ok(manifest.hash),
+        None => hash_file_cached(stat_cache, staging_path, key),
+    }
+}
+
+fn get_link_path(file: &str) -> Result<String, io::Error> {
+    let parent = get_parent(file);
+    let parent_path = get_absolute_path(if parent.is_empty() { "." } else { &parent }, "")?;
+    Ok(format!("{}/{}", parent_path, get_filename(file)))
+}
+
+fn get_missing_path(path: &str, file: &str) -> Option<String> {
+    let parent = get_parent(file);
+    let parent_path = get_absolute_path(if parent.is_empty() { "." } else { &parent }, "").ok()?;
+
+    if parent_path.starts_with(path) {
+        Some(get_relative_path(
+            &format!("{}/{}", parent_path, get_filename(file)),
+            path,
+            true,
+        ))
+    } else {
+        None
+    }
+}
+
 pub fn remove(test_path: &str, files: Vec<String>) -> Result<(), io::Error> {
     let path = &is_repository(test_path)?;
+    let _lock = lock_repository(path)?;
 
     let repo_metadata = load_repo_metadata(path)?;
     let branch = &repo_metadata.head;
@@ -240,6 +386,13 @@ pub fn remove(test_path: &str, files: Vec<String>) -> Result<(), io::Error> {
     } else {
         for file in files.iter() {
             let staging_path = format!("{}/.dvcs/origin/{}/staging", path, branch);
+
+            if branch_metadata.staging.contains(file)
+                && !check_file(&format!("{}/{}", staging_path, file))
+            {
+                continue; // Staged deletions have no copy in the staging area
+            }
+
             let file_path = get_absolute_path(file, &staging_path)?;
             let relative_path = get_relative_path(&file_path, &staging_path, true);
 
@@ -263,6 +416,14 @@ pub fn remove(test_path: &str, files: Vec<String>) -> Result<(), io::Error> {
 
         for file in files.iter() {
             let staging_path = format!("{}/.dvcs/origin/{}/staging", path, branch,);
+
+            if branch_metadata.staging.contains(file)
+                && !check_file(&format!("{}/{}", staging_path, file))
+            {
+                branch_metadata.staging.retain(|f| f != file);
+                continue;
+            }
+
             let file_path = get_absolute_path(file, &staging_path)?;
             let relative_path = get_relative_path(&file_path, &staging_path, true);
 
@@ -289,6 +450,7 @@ pub fn heads(path: &str) -> Result<String, io::Error> {
         let branch_metadata = load_branch_metadata(path, branch)?;
         let mut date_time: DateTime<chrono::Local> = SystemTime::now().into();
         let mut message = "No commits yet...".to_string();
+        let mut signatures = String::new();
         let mut header = format!("\x1b[31morigin/{}\x1b[0m", branch);
 
         if branch == &repo_metadata.head {
@@ -296,7 +458,8 @@ pub fn heads(path: &str) -> Result<String, io::Error> {
         }
 
         if let Some(revision_id) = &branch_metadata.head_commit {
-            let revision_metadata = load_revision_metadata(path, branch, revision_id)?;
+            let revision_metadata = load_revision_metadata(path, revision_id)?;
+            signatures = format_signatures(&revision_metadata);
             message = revision_metadata.message;
             date_time = revision_metadata.timestamp.into();
             header = format!(
@@ -308,9 +471,10 @@ pub fn heads(path: &str) -> Result<String, io::Error> {
         }
 
         let content = format!(
-            "\n\x1b[0m{}Date: {}\n\n\t{}\x1b[0m\n\n",
+            "\n\x1b[0m{}{}Date: {}\n\n\t{}\x1b[0m\n\n",
             header,
-            format!("{}", date_time.format("%Y-%m-%d %H:%M:%S")),
+            signatures,
+            date_time.format("%Y-%m-%d %H:%M:%S"),
             message,
         );
 
@@ -327,28 +491,30 @@ pub fn heads(path: &str) -> Result<String, io::Error> {
 
 pub fn get_common_ancestor_and_count(
     path_into: &str,
-    branch_into: &str,
     revision_id_into: &str,
     path_from: &str,
-    branch_from: &str,
     revision_id_from: &str,
 ) -> Result<(isize, Option<String>), io::Error> {
-    load_revision_metadata(path_into, branch_into, revision_id_into).map_err(|e| {
+    load_revision_metadata(path_into, revision_id_into).map_err(|e| {
         io::Error::new(
             io::ErrorKind::NotFound,
             format!(
-                "'{}' commit '{}' not found: {}",
-                branch_into, revision_id_into, e
+                "Commit '{}' not found in '{}': {}",
+                revision_id_into,
+                get_filename(path_into),
+                e
             ),
         )
     })?;
 
-    load_revision_metadata(path_from, branch_from, revision_id_from).map_err(|e| {
+    load_revision_metadata(path_from, revision_id_from).map_err(|e| {
         io::Error::new(
             io::ErrorKind::NotFound,
             format!(
-                "'{}' commit '{}' not found: {}",
-                branch_from, revision_id_from, e
+                "Commit '{}' not found in '{}': {}",
+                revision_id_from,
+                get_filename(path_from),
+                e
             ),
         )
     })?;
@@ -373,7 +539,7 @@ pub fn get_common_ancestor_and_count(
             }
 
             if visited_into.insert(current.clone()) {
-                if let Ok(metadata) = load_revision_metadata(path_into, branch_into, &current) {
+                if let Ok(metadata) = load_revision_metadata(path_into, &current) {
                     for parent in metadata.parents {
                         queue_into.push_back((parent, distance + 1)); // Increment distance
                     }
@@ -386,7 +552,7 @@ pub fn get_common_ancestor_and_count(
                 return Ok((0 - distance, Some(current)));
             }
             if visited_from.insert(current.clone()) {
-                if let Ok(metadata) = load_revision_metadata(path_from, branch_from, &current) {
+                if let Ok(metadata) = load_revision_metadata(path_from, &current) {
                     for parent in metadata.parents {
                         queue_from.push_back((parent, distance + 1));
                     }
@@ -398,40 +564,52 @@ pub fn get_common_ancestor_and_count(
     Ok((0, None))
 }
 
+// Lists the files of the working tree under `path`, leaving out ignored names such as the
+// repository data
+fn list_working_files(path: &str, ignored: &[String]) -> Result<Vec<String>, io::Error> {
+    let mut files = Vec::new();
+
+    for entry in list_directory(path, true, false)? {
+        if ignored.contains(&get_filename(&entry)) {
+            continue;
+        }
+
+        if !check_link(&entry) && check_directory(&entry) {
+            files.extend(list_working_files(&entry, ignored)?);
+        } else {
+            files.push(entry);
+        }
+    }
+
+    Ok(files)
+}
+
 pub fn status(path: &str) -> Result<String, io::Error> {
     let repo_root = is_repository(path)?; // Get the root of the repository
+                                          // Status only reads the repository, so it still runs while another process holds the lock,
+                                          // leaving the stat cache alone in that case
+    let lock = lock_repository(&repo_root).ok();
     let current_path = get_absolute_path(".", "")?; // Get current working directory
     let repo_metadata = load_repo_metadata(&repo_root)?;
     let branch = &repo_metadata.head;
     let local_branch_metadata = load_branch_metadata(&repo_root, branch)?;
-    let remote_repo_root = is_repository(&format!("{}/{}", repo_root, REMOTE))?;
+    let remote_repo_root = get_remote_path(&repo_root)?;
     let remote_branch_metadata = load_branch_metadata(&remote_repo_root, branch)?;
     let mut status_report = String::new();
     status_report.push_str(&format!("On branch {}\n", branch));
 
     // Ahead/Behind Status
     if let Some(local_commit) = &local_branch_metadata.head_commit {
-        let (count, _) = if let Some(upstream_commit) = &remote_branch_metadata.head_commit {
+        let count = if let Some(upstream_commit) = &remote_branch_metadata.head_commit {
             get_common_ancestor_and_count(
                 &repo_root,
-                branch,
                 local_commit,
                 &remote_repo_root,
-                branch,
                 upstream_commit,
             )?
+            .0
         } else {
-            get_common_ancestor_and_count(
-                &repo_root,
-                branch,
-                local_commit,
-                &repo_root,
-                branch,
-                local_branch_metadata
-                    .commits
-                    .first()
-                    .expect(&format!("No commits found in branch: {}", branch)),
-            )?
+            get_ancestors(&repo_root, local_commit)?.len() as isize
         };
 
         if count > 0 {
@@ -460,33 +638,45 @@ pub fn status(path: &str) -> Result<String, io::Error> {
 
     // Retrieve Latest Revision Metadata
     let latest_revision = if let Some(head_commit) = &local_branch_metadata.head_commit {
-        load_revision_metadata(&repo_root, branch, head_commit)?
+        load_revision_metadata(&repo_root, head_commit)?
     } else {
         init_revision_metadata()
     };
 
+    // Files whose size, modification time and mode are unchanged are not hashed again
+    let stat_cache_path = get_stat_cache_path(&repo_root);
+    let mut stat_cache = load_stat_cache(&stat_cache_path);
+
     // Changes to Be Committed
     if !local_branch_metadata.staging.is_empty() {
         status_report.push_str(
             "\n\nChanges to be committed:\n  (use \"cargo run remove <pathspec>...\" to unstage)\n",
         );
         for file in &local_branch_metadata.staging {
-            let staging_path = format!("{}/.dvcs/origin/{}/staging/{}", path, branch, file);
+            let staging_path = format!("{}/.dvcs/origin/{}/staging/{}", repo_root, branch, file);
             let relative_path = get_relative_path(&staging_path, &current_path, false);
 
             if check_file(&staging_path) {
                 if latest_revision.files.contains_key(file) {
                     // File exists in the latest revision
                     let revision_hash = &latest_revision.files[file];
-                    let staged_content = read_file(&staging_path)?;
-                    let staged_hash = format!(
-                        "{:x}",
-                        Uuid::new_v5(&Uuid::NAMESPACE_OID, staged_content.as_bytes())
-                    );
+                    let staged_hash = hash_staged_file(
+                        &mut stat_cache,
+                        &staging_path,
+                        &get_relative_path(&staging_path, &repo_root, true),
+                    )?;
+
+                    let staged_mode = get_file_mode(&staging_path)?;
+                    let revision_mode = get_revision_file_mode(&latest_revision, file);
 
                     if &staged_hash != revision_hash {
                         status_report
                             .push_str(&format!("\t\x1b[32mmodified:   {}\x1b[0m\n", relative_path));
+                    } else if staged_mode != revision_mode {
+                        status_report.push_str(&format!(
+                            "\t\x1b[32mmode changed:   {} ({:o} -> {:o})\x1b[0m\n",
+                            relative_path, revision_mode, staged_mode
+                        ));
                     }
                 } else {
                     // File is new
@@ -504,23 +694,45 @@ pub fn status(path: &str) -> Result<String, io::Error> {
     for (file, hash) in &latest_revision.files {
         let full_path = format!("{}/{}", repo_root, file);
         let relative_path = get_relative_path(&full_path, &current_path, false);
+        let staging_path = format!("{}/.dvcs/origin/{}/staging/{}", repo_root, branch, file);
+        let staged = local_branch_metadata.staging.contains(file);
 
         if !check_file(&full_path) {
-            not_staged.push((relative_path.clone(), "deleted".to_string()));
+            if !staged {
+                not_staged.push((relative_path.clone(), "deleted".to_string()));
+            }
         } else {
-            let content = read_file(&full_path)?;
-            let current_hash = format!(
-                "{:x}",
-                Uuid::new_v5(&Uuid::NAMESPACE_OID, content.as_bytes())
-            );
-            if current_hash != *hash {
+            let current_hash = hash_file_cached(&mut stat_cache, &full_path, file)?;
+            let current_mode = get_file_mode(&full_path)?;
+            let (expected_hash, expected_mode) = if staged && check_file(&staging_path) {
+                (
+                    hash_staged_file(
+                        &mut stat_cache,
+                        &staging_path,
+                        &get_relative_path(&staging_path, &repo_root, true),
+                    )?,
+                    get_file_mode(&staging_path)?,
+                )
+            } else {
+                (hash.clone(), get_revision_file_mode(&latest_revision, file))
+            };
+
+            if current_hash != expected_hash {
                 not_staged.push((relative_path.clone(), "modified".to_string()));
+            } else if current_mode != expected_mode {
+                not_staged.push((
+                    format!(
+                        "{} ({:o} -> {:o})",
+                        relative_path, expected_mode, current_mode
+                    ),
+                    "mode changed".to_string(),
+                ));
             }
         }
     }
 
     if local_branch_metadata.staging.is_empty() && !not_staged.is_empty() {
-        status_report.push_str("\n");
+        status_report.push('\n');
     }
 
     if !not_staged.is_empty() {
@@ -530,23 +742,28 @@ pub fn status(path: &str) -> Result<String, io::Error> {
         }
     }
 
+    // Entries of files that are no longer tracked are dropped; the cache is only an optimization,
+    // so failing to save it does not fail the status
+    if lock.is_some() {
+        stat_cache
+            .entries
+            .retain(|key, _| check_file(&format!("{}/{}", repo_root, key)));
+        let _ = save_stat_cache(&stat_cache_path, &stat_cache);
+    }
+
     // Untracked Files
-    let mut untracked_files = list_directory(&repo_root, true, true)?;
+    let mut untracked_files = list_working_files(&repo_root, &get_ignored_names(&repo_root)?)?;
     untracked_files.retain(|file| {
         let relative_path = get_relative_path(file, &current_path, false);
         !local_branch_metadata.staging.contains(&relative_path)
             && !latest_revision.files.contains_key(&relative_path)
-            && !file.contains(".dvcs")
-            && !file.contains(REMOTE)
-            && !file.contains(".git")
-            && !file.contains(".DS_Store")
     });
 
     if local_branch_metadata.staging.is_empty()
         && not_staged.is_empty()
         && !untracked_files.is_empty()
     {
-        status_report.push_str("\n");
+        status_report.push('\n');
     }
 
     if !untracked_files.is_empty() {
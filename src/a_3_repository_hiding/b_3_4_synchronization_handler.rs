@@ -1,15 +1,17 @@
 // days_dvcs/src/a_3_repository_hiding/b_3_4_synchronization_handler.rs
 //
 
-use super::b_3_1_repository_management::{is_repository, load_repo_metadata, save_repo_metadata};
-use super::b_3_2_revision_management::load_revision_metadata;
+use super::b_3_11_configuration_management::get_remote_path;
+use super::b_3_12_history_management::check_no_sequence;
+use super::b_3_1_repository_management::{
+    is_repository, load_repo_metadata, lock_repository, save_repo_metadata,
+};
+use super::b_3_2_revision_management::{copy_revisions, is_ancestor};
 use super::b_3_3_branch_management::{init_branch, load_branch_metadata, save_branch_metadata};
+use super::b_3_6_journal_management::begin_operation;
 
 use crate::a_1_file_system_hiding::{
     b_1_1_file_interaction::{get_filename, write_file},
-    b_1_2_directory_interaction::{
-        check_directory, copy_directory, create_directory, delete_directory, rename_directory,
-    },
     REMOTE,
 };
 
@@ -31,12 +33,14 @@ pub fn push(
     }
 
     let local_absolute_path = is_repository(path)?;
-    let local_repo_metadata = load_repo_metadata(&local_absolute_path)?;
     let remote_absolute_path = if remote_path == REMOTE {
-        is_repository(&format!("{}/{}", local_absolute_path, REMOTE))?
+        get_remote_path(&local_absolute_path)?
     } else {
         is_repository(remote_path)?
     };
+    let _local_lock = lock_repository(&local_absolute_path)?;
+    let _remote_lock = lock_repository(&remote_absolute_path)?;
+    let local_repo_metadata = load_repo_metadata(&local_absolute_path)?;
     let mut remote_repo_metadata = load_repo_metadata(&remote_absolute_path)?;
     let mut branches: HashMap<String, String> = HashMap::new();
     let mut push_report = String::new();
@@ -67,15 +71,28 @@ pub fn push(
         branches.extend(local_repo_metadata.branches.clone());
     }
 
+    let journal = begin_operation(
+        &remote_absolute_path,
+        "push",
+        &branches.keys().cloned().collect::<Vec<String>>(),
+    )?;
+
     for (branch_to_push, local_last_revision_id) in branches {
-        let local_branch_metadata = load_branch_metadata(&local_absolute_path, &branch_to_push)?;
-        let temp_commits_path = format!(
-            "{}/.dvcs/origin/{}/temp_commits",
-            remote_absolute_path, branch_to_push
-        );
+        let remote_last_revision_id = remote_repo_metadata
+            .branches
+            .get(&branch_to_push)
+            .cloned()
+            .unwrap_or_default();
 
         if !remote_repo_metadata.branches.contains_key(&branch_to_push) {
             init_branch(&remote_absolute_path, &branch_to_push, false)?;
+            // The metadata loaded above is saved at the end, so it must list the new branch too
+            remote_repo_metadata.branches.insert(
+                branch_to_push.clone(),
+                load_branch_metadata(&remote_absolute_path, &branch_to_push)?
+                    .head_commit
+                    .unwrap_or_default(),
+            );
             push_report.push_str(&format!(
                 "Branch '{}' created in '{}'\n",
                 branch_to_push,
@@ -91,146 +108,60 @@ pub fn push(
             continue;
         }
 
-        let mut remote_branch_metadata =
-            load_branch_metadata(&remote_absolute_path, &branch_to_push)?;
-        let mut extended_commits = Vec::new();
-
-        if check_directory(&temp_commits_path) {
-            delete_directory(&temp_commits_path, true)?;
+        if local_last_revision_id == remote_last_revision_id {
+            push_report.push_str(&format!(
+                "Branch '{}' is already up to date\n",
+                branch_to_push
+            ));
+            continue;
         }
 
-        create_directory(&temp_commits_path)?;
-
-        if let Some(remote_last_revision_id) = remote_branch_metadata.head_commit {
-            let local_last_revision_metadata = load_revision_metadata(
+        if !remote_last_revision_id.is_empty()
+            && !is_ancestor(
                 &local_absolute_path,
-                &branch_to_push,
-                &local_last_revision_id,
-            )?;
-            let remote_last_revision_metadata = load_revision_metadata(
-                &remote_absolute_path,
-                &branch_to_push,
                 &remote_last_revision_id,
-            )?;
-
-            if local_last_revision_id == remote_last_revision_id {
-                push_report.push_str(&format!(
-                    "Branch '{}' is already up to date\n",
-                    branch_to_push
-                ));
-                delete_directory(&temp_commits_path, true)?;
-                continue;
-            } else if !local_last_revision_metadata
-                .parents
-                .contains(&remote_last_revision_id)
-            {
-                if !force {
-                    return if local_last_revision_metadata.timestamp
-                        < remote_last_revision_metadata.timestamp
-                    {
-                        Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!(
-                                "Branch '{}' is ahead of local branch. Please pull changes from '{}' before pushing",
-                                branch_to_push, get_filename(&remote_absolute_path)
-                            ),
-                        ))
-                    } else {
-                        Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!(
-                                "Cannot push branch '{}': '{}' and '{}' have diverged",
-                                branch_to_push,
-                                get_filename(&remote_absolute_path),
-                                get_filename(&local_absolute_path)
-                            ),
-                        ))
-                    };
-                }
-
-                push_report.push_str(&format!(
-                    "Force pushing branch '{}' to overwrite '{}' changes\n",
-                    branch_to_push,
-                    get_filename(&remote_absolute_path)
-                ));
-            }
-
-            let mut commit_to_push = local_branch_metadata.commits.clone();
-
-            match local_branch_metadata
-                .commits
-                .iter()
-                .position(|id| *id == remote_last_revision_id)
-            {
-                Some(index) => {
-                    commit_to_push = commit_to_push[index + 1..].to_vec();
-                }
-                None => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::NotFound,
+                &local_last_revision_id,
+            )?
+        {
+            if !force {
+                return if is_ancestor(
+                    &remote_absolute_path,
+                    &local_last_revision_id,
+                    &remote_last_revision_id,
+                )? {
+                    Err(io::Error::other(
                         format!(
-                            "Revision '{}' not found in '{}' branch '{}'",
-                            remote_last_revision_id,
-                            get_filename(&local_absolute_path),
-                            branch_to_push
+                            "Branch '{}' is ahead of local branch. Please pull changes from '{}' before pushing",
+                            branch_to_push, get_filename(&remote_absolute_path)
                         ),
-                    ));
-                }
-            }
-
-            for revision_id_to_push in commit_to_push.iter() {
-                copy_directory(
-                    &format!(
-                        "{}/.dvcs/origin/{}/commits/{}",
-                        local_absolute_path, branch_to_push, revision_id_to_push
-                    ),
-                    &format!("{}/{}", temp_commits_path, revision_id_to_push),
-                )?;
+                    ))
+                } else {
+                    Err(io::Error::other(format!(
+                        "Cannot push branch '{}': '{}' and '{}' have diverged",
+                        branch_to_push,
+                        get_filename(&remote_absolute_path),
+                        get_filename(&local_absolute_path)
+                    )))
+                };
             }
 
-            extended_commits.extend(commit_to_push);
-        } else {
-            copy_directory(
-                &format!(
-                    "{}/.dvcs/origin/{}/commits",
-                    local_absolute_path, branch_to_push
-                ),
-                &temp_commits_path,
-            )?;
-
-            extended_commits.extend(local_branch_metadata.commits);
+            push_report.push_str(&format!(
+                "Force pushing branch '{}' to overwrite '{}' changes\n",
+                branch_to_push,
+                get_filename(&remote_absolute_path)
+            ));
         }
 
-        if !force {
-            for extended_revision_id in extended_commits.iter() {
-                copy_directory(
-                    &format!("{}/{}", temp_commits_path, extended_revision_id),
-                    &format!(
-                        "{}/.dvcs/origin/{}/commits/{}",
-                        remote_absolute_path, branch_to_push, extended_revision_id
-                    ),
-                )?;
-            }
-            delete_directory(&temp_commits_path, true)?;
-            remote_branch_metadata.commits.extend(extended_commits);
-        } else {
-            delete_directory(
-                &format!(
-                    "{}/.dvcs/origin/{}/commits",
-                    remote_absolute_path, branch_to_push
-                ),
-                true,
-            )?;
-            rename_directory(
-                &temp_commits_path,
-                &format!(
-                    "{}/.dvcs/origin/{}/commits",
-                    remote_absolute_path, branch_to_push
-                ),
-            )?;
-            remote_branch_metadata.commits = extended_commits;
-        }
+        // Copy the missing history first, then move the branch pointer
+        copy_revisions(
+            &local_absolute_path,
+            &remote_absolute_path,
+            &local_last_revision_id,
+        )?;
+        journal.record_target(&branch_to_push, &local_last_revision_id, false)?;
 
+        let mut remote_branch_metadata =
+            load_branch_metadata(&remote_absolute_path, &branch_to_push)?;
         remote_branch_metadata.head_commit = Some(local_last_revision_id.clone());
         save_branch_metadata(
             &remote_absolute_path,
@@ -261,6 +192,7 @@ pub fn push(
         ),
     )?;
     save_repo_metadata(&remote_absolute_path, &remote_repo_metadata)?;
+    journal.finish()?;
     Ok(push_report)
 }
 
@@ -280,10 +212,13 @@ pub fn pull(
 
     let local_absolute_path = is_repository(path)?;
     let remote_absolute_path = if remote_path == REMOTE {
-        is_repository(&format!("{}/{}", local_absolute_path, REMOTE))?
+        get_remote_path(&local_absolute_path)?
     } else {
         is_repository(remote_path)?
     };
+    let _local_lock = lock_repository(&local_absolute_path)?;
+    let _remote_lock = lock_repository(&remote_absolute_path)?;
+    check_no_sequence(&local_absolute_path)?;
     let mut local_repo_metadata = load_repo_metadata(&local_absolute_path)?;
     let remote_repo_metadata = load_repo_metadata(&remote_absolute_path)?;
     let mut branches: HashMap<String, String> = HashMap::new();
@@ -315,15 +250,28 @@ pub fn pull(
         );
     }
 
+    let journal = begin_operation(
+        &local_absolute_path,
+        "pull",
+        &branches.keys().cloned().collect::<Vec<String>>(),
+    )?;
+
     for (branch_to_pull, remote_last_revision_id) in branches {
-        let remote_branch_metadata = load_branch_metadata(&remote_absolute_path, &branch_to_pull)?;
-        let temp_commits_path = format!(
-            "{}/.dvcs/origin/{}/temp_commits",
-            local_absolute_path, branch_to_pull
-        );
+        let local_last_revision_id = local_repo_metadata
+            .branches
+            .get(&branch_to_pull)
+            .cloned()
+            .unwrap_or_default();
 
         if !local_repo_metadata.branches.contains_key(&branch_to_pull) {
             init_branch(&local_absolute_path, &branch_to_pull, false)?;
+            // The metadata loaded above is saved at the end, so it must list the new branch too
+            local_repo_metadata.branches.insert(
+                branch_to_pull.clone(),
+                load_branch_metadata(&local_absolute_path, &branch_to_pull)?
+                    .head_commit
+                    .unwrap_or_default(),
+            );
             pull_report.push_str(&format!(
                 "Branch '{}' created in '{}'\n",
                 branch_to_pull,
@@ -339,142 +287,60 @@ pub fn pull(
             continue;
         }
 
-        let mut local_branch_metadata =
-            load_branch_metadata(&local_absolute_path, &branch_to_pull)?;
-        let mut extended_commits = Vec::new();
-
-        if check_directory(&temp_commits_path) {
-            delete_directory(&temp_commits_path, true)?;
+        if local_last_revision_id == remote_last_revision_id {
+            pull_report.push_str(&format!(
+                "Branch '{}' is already up to date\n",
+                branch_to_pull
+            ));
+            continue;
         }
 
-        create_directory(&temp_commits_path)?;
-
-        if let Some(local_last_revision_id) = local_branch_metadata.head_commit {
-            let local_last_revision_metadata = load_revision_metadata(
-                &local_absolute_path,
-                &branch_to_pull,
-                &local_last_revision_id,
-            )?;
-            let remote_last_revision_metadata = load_revision_metadata(
+        if !local_last_revision_id.is_empty()
+            && !is_ancestor(
                 &remote_absolute_path,
-                &branch_to_pull,
+                &local_last_revision_id,
                 &remote_last_revision_id,
-            )?;
-
-            if local_last_revision_id == remote_last_revision_id {
-                pull_report.push_str(&format!(
-                    "Branch '{}' is already up to date\n",
-                    branch_to_pull
-                ));
-                delete_directory(&temp_commits_path, true)?;
-                continue;
-            } else if !remote_last_revision_metadata
-                .parents
-                .contains(&local_last_revision_id)
-            {
-                if !force {
-                    return if remote_last_revision_metadata.timestamp
-                        < local_last_revision_metadata.timestamp
-                    {
-                        Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!(
-                                "Branch '{}' is ahead of remote branch. Please push changes to '{}' before pulling",
-                                branch_to_pull, get_filename(&remote_absolute_path)
-                            ),
-                        ))
-                    } else {
-                        Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!(
-                                "Cannot pull branch '{}': '{}' and '{}' have diverged",
-                                branch_to_pull,
-                                get_filename(&local_absolute_path),
-                                get_filename(&remote_absolute_path)
-                            ),
-                        ))
-                    };
-                }
-
-                pull_report.push_str(&format!(
-                    "Force pulling branch '{}' to overwrite '{}' changes\n",
-                    branch_to_pull,
-                    get_filename(&local_absolute_path)
-                ));
-            }
-
-            let mut commit_to_pull = remote_branch_metadata.commits.clone();
-
-            if let Some(index) = remote_branch_metadata
-                .commits
-                .iter()
-                .position(|id| *id == local_last_revision_id)
-            {
-                commit_to_pull = commit_to_pull[index + 1..].to_vec();
-            } else {
-                return Err(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!(
-                        "Revision '{}' not found in '{}' branch '{}'",
-                        local_last_revision_id,
-                        get_filename(&remote_absolute_path),
-                        branch_to_pull
-                    ),
-                ));
-            }
-
-            for revision_id_to_pull in commit_to_pull.iter() {
-                copy_directory(
-                    &format!(
-                        "{}/.dvcs/origin/{}/commits/{}",
-                        remote_absolute_path, branch_to_pull, revision_id_to_pull
-                    ),
-                    &format!("{}/{}", temp_commits_path, revision_id_to_pull),
-                )?;
+            )?
+        {
+            if !force {
+                return if is_ancestor(
+                    &local_absolute_path,
+                    &remote_last_revision_id,
+                    &local_last_revision_id,
+                )? {
+                    Err(io::Error::other(
+                        format!(
+                            "Branch '{}' is ahead of remote branch. Please push changes to '{}' before pulling",
+                            branch_to_pull, get_filename(&remote_absolute_path)
+                        ),
+                    ))
+                } else {
+                    Err(io::Error::other(format!(
+                        "Cannot pull branch '{}': '{}' and '{}' have diverged",
+                        branch_to_pull,
+                        get_filename(&local_absolute_path),
+                        get_filename(&remote_absolute_path)
+                    )))
+                };
             }
 
-            extended_commits.extend(commit_to_pull);
-        } else {
-            copy_directory(
-                &format!(
-                    "{}/.dvcs/origin/{}/commits",
-                    remote_absolute_path, branch_to_pull
-                ),
-                &temp_commits_path,
-            )?;
-            extended_commits.extend(remote_branch_metadata.commits);
+            pull_report.push_str(&format!(
+                "Force pulling branch '{}' to overwrite '{}' changes\n",
+                branch_to_pull,
+                get_filename(&local_absolute_path)
+            ));
         }
 
-        if force {
-            delete_directory(
-                &format!(
-                    "{}/.dvcs/origin/{}/commits",
-                    local_absolute_path, branch_to_pull
-                ),
-                true,
-            )?;
-            rename_directory(
-                &temp_commits_path,
-                &format!(
-                    "{}/.dvcs/origin/{}/commits",
-                    local_absolute_path, branch_to_pull
-                ),
-            )?;
-            local_branch_metadata.commits = extended_commits;
-        } else {
-            for extended_revision_id in extended_commits.iter() {
-                copy_directory(
-                    &format!("{}/{}", temp_commits_path, extended_revision_id),
-                    &format!(
-                        "{}/.dvcs/origin/{}/commits/{}",
-                        local_absolute_path, branch_to_pull, extended_revision_id
-                    ),
-                )?;
-            }
-            delete_directory(&temp_commits_path, true)?;
-            local_branch_metadata.commits.extend(extended_commits);
-        }
+        // Copy the missing history first, then move the branch pointer
+        copy_revisions(
+            &remote_absolute_path,
+            &local_absolute_path,
+            &remote_last_revision_id,
+        )?;
+        journal.record_target(&branch_to_pull, &remote_last_revision_id, false)?;
 
+        let mut local_branch_metadata =
+            load_branch_metadata(&local_absolute_path, &branch_to_pull)?;
         local_branch_metadata.head_commit = Some(remote_last_revision_id.clone());
         save_branch_metadata(
             &local_absolute_path,
@@ -505,5 +371,6 @@ pub fn pull(
         ),
     )?;
     save_repo_metadata(&local_absolute_path, &local_repo_metadata)?;
+    journal.finish()?;
     Ok(pull_report)
 }
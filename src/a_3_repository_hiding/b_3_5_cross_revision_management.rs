@@ -1,20 +1,28 @@
 // days_dvcs/src/a_3_repository_hiding/b_3_5_cross_revision_management.rs
 //
 
-use super::b_3_1_repository_management::is_repository;
+use super::b_3_11_configuration_management::{get_context_lines, get_remote_path};
+use super::b_3_12_history_management::check_no_sequence;
+use super::b_3_1_repository_management::{is_repository, lock_repository};
 use super::b_3_2_revision_management::{
-    commit, get_branch_or_revision_id, load_revision_metadata, RevisionMetadata,
+    commit_merge, copy_revisions, get_branch_or_revision_id, get_revision_file_mode,
+    load_revision_metadata, RevisionMetadata,
 };
 use super::b_3_3_branch_management::{
     get_common_ancestor_and_count, load_branch_metadata, save_branch_metadata,
 };
+use super::b_3_6_journal_management::begin_operation;
 
 use crate::a_1_file_system_hiding::{
-    b_1_1_file_interaction::{get_filename, get_parent, is_binary_file, read_file, write_file},
+    b_1_1_file_interaction::{
+        get_filename, get_parent, is_binary_file, read_bytes, write_bytes, write_file,
+    },
     b_1_2_directory_interaction::{
         check_directory, copy_directory, create_directory, delete_directory,
     },
-    b_1_3_metadata_management::get_file_metadata,
+    b_1_3_metadata_management::{set_file_mode, write_entry, SYMLINK_MODE},
+    b_1_4_object_management::read_object,
+    b_1_7_chunk_management::{read_object_manifest, write_chunk_manifest},
     REMOTE,
 };
 
@@ -22,30 +30,27 @@ use crate::a_1_file_system_hiding::b_1_2_directory_interaction::rename_directory
 use std::collections::HashSet;
 use std::io;
 
-const CONTEXT_LINES: usize = 3;
+const EMPTY_FILE_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
 
-const EMPTY_FILE_HASH: &str = "00000000-0000-0000-0000-000000000000";
+type Revisions = (
+    String,
+    String,
+    String,
+    RevisionMetadata,
+    String,
+    String,
+    String,
+    RevisionMetadata,
+);
 
 fn get_revisions(
     path: &str,
     branch_or_revision_id_1: &str,
     branch_or_revision_id_2: &str,
-) -> Result<
-    (
-        String,
-        String,
-        String,
-        RevisionMetadata,
-        String,
-        String,
-        String,
-        RevisionMetadata,
-    ),
-    io::Error,
-> {
+) -> Result<Revisions, io::Error> {
     let path_2 = &is_repository(path)?;
     let path_1 = if branch_or_revision_id_1 == REMOTE {
-        &is_repository(&format!("{}/{}", path_2, REMOTE))?
+        &get_remote_path(path_2)?
     } else {
         path_2
     };
@@ -83,8 +88,8 @@ fn get_revisions(
         });
     }
 
-    let revision_metadata_1 = load_revision_metadata(path_1, &branch_1, &revision_id_1)?;
-    let revision_metadata_2 = load_revision_metadata(path_2, &branch_2, &revision_id_2)?;
+    let revision_metadata_1 = load_revision_metadata(path_1, &revision_id_1)?;
+    let revision_metadata_2 = load_revision_metadata(path_2, &revision_id_2)?;
 
     Ok((
         path_1.to_string(),
@@ -103,37 +108,18 @@ pub fn diff(
     branch_or_revision_id_1: &str,
     branch_or_revision_id_2: &str,
 ) -> Result<String, io::Error> {
-    let (
-        path_1,
-        branch_1,
-        revision_id_1,
-        revision_metadata_1,
-        path_2,
-        branch_2,
-        revision_id_2,
-        revision_metadata_2,
-    ) = get_revisions(path, branch_or_revision_id_1, branch_or_revision_id_2)?;
+    let (path_1, _, _, revision_metadata_1, path_2, _, _, revision_metadata_2) =
+        get_revisions(path, branch_or_revision_id_1, branch_or_revision_id_2)?;
+    let context_lines = get_context_lines(&path_2)?;
     let old_path = if revision_metadata_1.timestamp < revision_metadata_2.timestamp {
-        &format!(
-            "{}/.dvcs/origin/{}/commits/{}",
-            path_1, branch_1, &revision_id_1
-        )
+        &format!("{}/.dvcs/objects", path_1)
     } else {
-        &format!(
-            "{}/.dvcs/origin/{}/commits/{}",
-            path_2, branch_2, &revision_id_2
-        )
+        &format!("{}/.dvcs/objects", path_2)
     };
     let new_path = if revision_metadata_1.timestamp < revision_metadata_2.timestamp {
-        &format!(
-            "{}/.dvcs/origin/{}/commits/{}",
-            path_2, branch_2, &revision_id_2
-        )
+        &format!("{}/.dvcs/objects", path_2)
     } else {
-        &format!(
-            "{}/.dvcs/origin/{}/commits/{}",
-            path_1, branch_1, &revision_id_1
-        )
+        &format!("{}/.dvcs/objects", path_1)
     };
     let old_revision_metadata = if revision_metadata_1.timestamp < revision_metadata_2.timestamp {
         revision_metadata_1.clone()
@@ -155,6 +141,8 @@ pub fn diff(
     for file in files {
         let old_hash = old_revision_metadata.files.get(file);
         let new_hash = new_revision_metadata.files.get(file);
+        let old_mode = get_revision_file_mode(&old_revision_metadata, file);
+        let new_mode = get_revision_file_mode(&new_revision_metadata, file);
 
         match (old_hash, new_hash) {
             (Some(old), Some(new)) => {
@@ -165,10 +153,24 @@ pub fn diff(
                         &old_revision_metadata.id,
                         old_path,
                         old,
+                        old_mode,
                         &new_revision_metadata.id,
                         new_path,
                         new,
+                        new_mode,
+                        context_lines,
                     )?)
+                } else if old_mode != new_mode {
+                    // File exists in both revisions with the same content but a different mode
+                    diff_report.push_str(&format!(
+                        "diff --dvcs {}/{} {}/{}\nold mode {:o}\nnew mode {:o}\n\n",
+                        &old_revision_metadata.id,
+                        file,
+                        &new_revision_metadata.id,
+                        file,
+                        old_mode,
+                        new_mode
+                    ));
                 } else {
                     // File exists in both revisions and is identical
                     diff_report.push_str(&format!(
@@ -184,9 +186,12 @@ pub fn diff(
                     &old_revision_metadata.id,
                     old_path,
                     old,
+                    old_mode,
                     &new_revision_metadata.id,
                     "",
                     EMPTY_FILE_HASH,
+                    new_mode,
+                    context_lines,
                 )?)
             }
             (None, Some(new)) => {
@@ -196,9 +201,12 @@ pub fn diff(
                     &old_revision_metadata.id,
                     "",
                     EMPTY_FILE_HASH,
+                    old_mode,
                     &new_revision_metadata.id,
                     new_path,
                     new,
+                    new_mode,
+                    context_lines,
                 )?)
             }
             _ => unreachable!(), // This should never happen due to the union of files
@@ -208,31 +216,35 @@ pub fn diff(
     Ok(diff_report)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn diff_files(
     file: &str,
     revision_id_old: &str,
     old_path: &str,
     file_old_hash: &str,
+    mode_old: u32,
     revision_id_new: &str,
     new_path: &str,
     file_new_hash: &str,
+    mode_new: u32,
+    context_lines: usize,
 ) -> Result<String, io::Error> {
     let content_old = if old_path.is_empty() {
-        String::new()
+        Vec::new()
     } else {
-        read_file(&format!("{}/{}", old_path, file))?
+        read_object(old_path, file_old_hash)?
     };
     let content_new = if new_path.is_empty() {
-        String::new()
+        Vec::new()
     } else {
-        read_file(&format!("{}/{}", new_path, file))?
+        read_object(new_path, file_new_hash)?
     };
 
     if (is_binary_file(&content_old) || is_binary_file(&content_new))
         || (content_old.is_empty() && !content_new.is_empty())
         || (!content_old.is_empty() && content_new.is_empty())
-        || (file_old_hash == EMPTY_FILE_HASH && content_new != EMPTY_FILE_HASH)
-        || (content_old != EMPTY_FILE_HASH && file_new_hash == EMPTY_FILE_HASH)
+        || (file_old_hash == EMPTY_FILE_HASH && content_new != EMPTY_FILE_HASH.as_bytes())
+        || (content_old != EMPTY_FILE_HASH.as_bytes() && file_new_hash == EMPTY_FILE_HASH)
     {
         return Ok(format!(
             "\x1b[33mBinary files '{}/{}' and '{}/{}' differ\x1b[0m\n",
@@ -253,40 +265,36 @@ fn diff_files(
         revision_id_old,
         old_path,
         file_old_hash,
+        mode_old,
         revision_id_new,
         new_path,
         file_new_hash,
+        mode_new,
+    );
+    let body = diff_files_body(
+        &String::from_utf8_lossy(&content_old),
+        &String::from_utf8_lossy(&content_new),
+        context_lines,
     )?;
-    let body = diff_files_body(&content_old, &content_new)?;
 
     Ok(format!("{}{}\n", header, body))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn diff_files_header(
     file: &str,
     revision_id_old: &str,
     old_path: &str,
     file_old_hash: &str,
+    mode_old: u32,
     revision_id_new: &str,
     new_path: &str,
     file_new_hash: &str,
-) -> Result<String, io::Error> {
-    let mode_old = if old_path.is_empty() {
-        "100644"
-    } else {
-        &format!(
-            "{:o}",
-            get_file_metadata(&format!("{}/{}", old_path, file))?.mode
-        )
-    };
-    let mode_new = if new_path.is_empty() {
-        "100644"
-    } else {
-        &format!(
-            "{:o}",
-            get_file_metadata(&format!("{}/{}", new_path, file))?.mode
-        )
-    };
+    mode_new: u32,
+) -> String {
+    // Modes come from the revisions, since stored objects are shared between files
+    let mode_old = format!("{:o}", mode_old);
+    let mode_new = format!("{:o}", mode_new);
     let header_head = format!(
         "diff --dvcs {}/{} {}/{}\nindex {}..{}",
         revision_id_old, file, revision_id_new, file, file_old_hash, file_new_hash,
@@ -309,13 +317,17 @@ fn diff_files_header(
         format!("{}/{}", revision_id_new, file)
     };
 
-    Ok(format!(
+    format!(
         "{}{}--- {}\n+++ {}\n",
         header_head, header_mode, file_old, file_new,
-    ))
+    )
 }
 
-fn diff_files_body(content_old: &str, content_new: &str) -> Result<String, io::Error> {
+fn diff_files_body(
+    content_old: &str,
+    content_new: &str,
+    context_lines: usize,
+) -> Result<String, io::Error> {
     let mut all_lines = Vec::new();
 
     for diff in diff::lines(content_old, content_new) {
@@ -342,8 +354,8 @@ fn diff_files_body(content_old: &str, content_new: &str) -> Result<String, io::E
     for (i, line) in all_lines.iter().enumerate() {
         if line.starts_with("\x1b[38;5;214m-") || line.starts_with("\x1b[32m+") {
             if let Some(last_index) = last_change_index {
-                if i > last_index + CONTEXT_LINES {
-                    trim_context(&mut current_chunk);
+                if i > last_index + context_lines {
+                    trim_context(&mut current_chunk, context_lines);
                     diff_chunks.push(format_chunk(&current_chunk));
                     current_chunk.clear();
                 }
@@ -356,14 +368,14 @@ fn diff_files_body(content_old: &str, content_new: &str) -> Result<String, io::E
     }
 
     if !current_chunk.is_empty() {
-        trim_context(&mut current_chunk);
+        trim_context(&mut current_chunk, context_lines);
         diff_chunks.push(format_chunk(&current_chunk));
     }
 
     Ok(diff_chunks.join("\n"))
 }
 
-fn trim_context(chunk: &mut Vec<(usize, String)>) {
+fn trim_context(chunk: &mut Vec<(usize, String)>, context_lines: usize) {
     if let (Some(start), Some(end)) = (
         chunk.iter().position(|(_, line)| {
             line.starts_with("\x1b[38;5;214m-") || line.starts_with("\x1b[32m+")
@@ -372,13 +384,13 @@ fn trim_context(chunk: &mut Vec<(usize, String)>) {
             line.starts_with("\x1b[38;5;214m-") || line.starts_with("\x1b[32m+")
         }),
     ) {
-        let start = start.saturating_sub(CONTEXT_LINES);
-        let end = (end + CONTEXT_LINES + 1).min(chunk.len());
+        let start = start.saturating_sub(context_lines);
+        let end = (end + context_lines + 1).min(chunk.len());
         *chunk = chunk[start..end].to_vec();
     }
 }
 
-fn format_chunk(chunk: &Vec<(usize, String)>) -> String {
+fn format_chunk(chunk: &[(usize, String)]) -> String {
     let start_old = match chunk
         .iter()
         .find(|(_, line)| !line.starts_with("\x1b[38;5;214m-"))
@@ -421,13 +433,23 @@ pub fn merge(
     branch_or_revision_id_from: &str,
     message: &str,
 ) -> Result<String, io::Error> {
+    // Both branch heads are read under the lock of the repository merged into
+    let local_path = is_repository(path)?;
+    let locked_path = if branch_or_revision_id_into == REMOTE {
+        get_remote_path(&local_path)?
+    } else {
+        local_path
+    };
+    let _lock = lock_repository(&locked_path)?;
+    check_no_sequence(&locked_path)?;
+
     let (
         path_into,
         branch_into,
         revision_id_into,
         revision_metadata_into,
         path_from,
-        branch_from,
+        _,
         revision_id_from,
         revision_metadata_from,
     ) = get_revisions(path, branch_or_revision_id_into, branch_or_revision_id_from)?;
@@ -440,6 +462,8 @@ pub fn merge(
         return Ok("No files to merge yet...".to_string());
     }
 
+    let journal = begin_operation(&path_into, "merge", std::slice::from_ref(&branch_into))?;
+
     let mut branch_metadata = load_branch_metadata(&path_into, &branch_into)?;
 
     let merge_path = format!("{}/.dvcs/origin/{}/staging", path_into, branch_into);
@@ -447,14 +471,8 @@ pub fn merge(
         "{}/.dvcs/origin/{}/temp_staging_before_merge",
         path_into, branch_into
     );
-    let content_into_path = format!(
-        "{}/.dvcs/origin/{}/commits/{}",
-        path_into, branch_into, revision_id_into
-    );
-    let content_from_path = format!(
-        "{}/.dvcs/origin/{}/commits/{}",
-        path_from, branch_from, revision_id_from
-    );
+    let objects_into_path = format!("{}/.dvcs/objects", path_into);
+    let objects_from_path = format!("{}/.dvcs/objects", path_from);
     let mut merge_report = String::new();
 
     if check_directory(&stage_before_merge_path) {
@@ -463,6 +481,7 @@ pub fn merge(
 
     create_directory(&stage_before_merge_path)?;
     copy_directory(&merge_path, &stage_before_merge_path)?;
+    journal.record_staging_backup(&branch_into)?;
 
     let files: HashSet<&String> = revision_metadata_into
         .files
@@ -480,30 +499,29 @@ pub fn merge(
         branch_or_revision_id_from
     };
 
+    // Bring the merged history into the target repository so the merge commit can reference it
+    copy_revisions(&path_from, &path_into, &revision_id_from)?;
+
+    let (_, revision_id_ancestor) = get_common_ancestor_and_count(
+        &path_into,
+        &revision_id_into,
+        &path_into,
+        &revision_id_from,
+    )?;
+    let revision_metadata_ancestor = revision_id_ancestor
+        .and_then(|revision_id| load_revision_metadata(&path_into, &revision_id).ok());
+
     for file in files.clone() {
         let hash_into = revision_metadata_into.files.get(file);
         let hash_from = revision_metadata_from.files.get(file);
-        let content_into = read_file(&format!("{}/{}", content_into_path, file)).ok();
-        let content_from = read_file(&format!("{}/{}", content_from_path, file)).ok();
+        let content_into = hash_into.and_then(|hash| read_object(&objects_into_path, hash).ok());
+        let content_from = hash_from.and_then(|hash| read_object(&objects_from_path, hash).ok());
         let staging_dir = format!("{}/{}", merge_path, get_parent(file));
         let staging_path = format!("{}/{}", merge_path, file);
-        let (_, revision_id) = get_common_ancestor_and_count(
-            &path_into,
-            &branch_into,
-            &revision_id_into,
-            &path_from,
-            &branch_from,
-            &revision_id_from,
-        )?;
-        let ancestor_content = if let Some(revision_id_ancestor) = revision_id {
-            read_file(&format!(
-                "{}/.dvcs/origin/{}/commits/{}/{}",
-                path_into, branch_into, revision_id_ancestor, file
-            ))
-            .ok()
-        } else {
-            None
-        };
+        let ancestor_content = revision_metadata_ancestor
+            .as_ref()
+            .and_then(|metadata| metadata.files.get(file))
+            .and_then(|hash| read_object(&objects_into_path, hash).ok());
 
         match (content_into, content_from) {
             (Some(content_into), Some(content_from)) => {
@@ -514,7 +532,12 @@ pub fn merge(
                                 if !check_directory(&staging_dir) {
                                     create_directory(&staging_dir)?;
                                 }
-                                write_file(&staging_path, &content_into)?;
+                                stage_object(
+                                    &objects_into_path,
+                                    hash_into,
+                                    &content_into,
+                                    &staging_path,
+                                )?;
                                 merge_report.push_str(&format!("File unchanged: '{}'\n", file));
                             } else {
                                 return Err(io::Error::new(
@@ -537,16 +560,41 @@ pub fn merge(
                         }
                         _ => unreachable!(),
                     }
+                } else if is_binary_file(&content_into)
+                    || is_binary_file(&content_from)
+                    || ancestor_content
+                        .as_ref()
+                        .is_some_and(|ancestor| is_binary_file(ancestor))
+                {
+                    // Binary contents cannot be merged line by line, so keep the target version
+                    if !check_directory(&staging_dir) {
+                        create_directory(&staging_dir)?;
+                    }
+                    stage_object(&objects_into_path, hash_into, &content_into, &staging_path)?;
+                    merge_report.push_str(&format!(
+                        "Conflict in binary file (kept '{}' version): '{}'\n",
+                        into, file
+                    ));
                 } else if let Some(ancestor) = ancestor_content {
-                    let merged_content =
-                        merge_contents(&ancestor, into, &content_into, from, &content_from);
+                    let merged_content = merge_contents(
+                        &String::from_utf8_lossy(&ancestor),
+                        into,
+                        &String::from_utf8_lossy(&content_into),
+                        from,
+                        &String::from_utf8_lossy(&content_from),
+                    );
                     if !check_directory(&staging_dir) {
                         create_directory(&staging_dir)?;
                     }
                     write_file(&staging_path, &merged_content)?;
                     merge_report.push_str(&format!("File merged: '{}'\n", file));
                 } else {
-                    let merged_content = merge_conflict(into, &content_into, from, &content_from);
+                    let merged_content = merge_conflict(
+                        into,
+                        &String::from_utf8_lossy(&content_into),
+                        from,
+                        &String::from_utf8_lossy(&content_from),
+                    );
                     if !check_directory(&staging_dir) {
                         create_directory(&staging_dir)?;
                     }
@@ -561,7 +609,7 @@ pub fn merge(
                 if !check_directory(&staging_dir) {
                     create_directory(&staging_dir)?;
                 }
-                write_file(&staging_path, &content_into)?;
+                stage_object(&objects_into_path, hash_into, &content_into, &staging_path)?;
                 merge_report.push_str(&format!(
                     "File removed in revision '{}': '{}'\n",
                     revision_id_from, file
@@ -571,7 +619,7 @@ pub fn merge(
                 if !check_directory(&staging_dir) {
                     create_directory(&staging_dir)?;
                 }
-                write_file(&staging_path, &content_from)?;
+                stage_object(&objects_from_path, hash_from, &content_from, &staging_path)?;
                 merge_report.push_str(&format!(
                     "File added in revision '{}': '{}'\n",
                     revision_id_from, file
@@ -579,6 +627,27 @@ pub fn merge(
             }
             _ => unreachable!(),
         }
+
+        // A mode changed only in the merged revision is taken over, like a content change
+        let mode_into = get_revision_file_mode(&revision_metadata_into, file);
+        let mode_from = get_revision_file_mode(&revision_metadata_from, file);
+        let mode_ancestor = revision_metadata_ancestor
+            .as_ref()
+            .filter(|metadata| metadata.files.contains_key(file))
+            .map(|metadata| get_revision_file_mode(metadata, file));
+        let mode =
+            if hash_into.is_none() || (hash_from.is_some() && mode_ancestor == Some(mode_into)) {
+                mode_from
+            } else {
+                mode_into
+            };
+
+        if mode == SYMLINK_MODE {
+            // Links are staged as links, with the merged content as their target
+            write_entry(&staging_path, &read_bytes(&staging_path)?, mode)?;
+        } else {
+            set_file_mode(&staging_path, mode)?;
+        }
     }
 
     let new_staging: HashSet<String> = branch_metadata
@@ -591,23 +660,38 @@ pub fn merge(
     branch_metadata.staging = Vec::from_iter(new_staging);
     save_branch_metadata(&path_into, &branch_into, &branch_metadata)?;
 
-    if let Err(e) = commit(&path_into, message) {
+    if let Err(e) = commit_merge(&path_into, message, &revision_id_from) {
         delete_directory(&merge_path, true)?;
         rename_directory(&stage_before_merge_path, &merge_path)?;
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
             format!(
-                "Merge failed during commit. Reverting to previous staging area: {}", e
+                "Merge failed during commit. Reverting to previous staging area: {}",
+                e
             ),
         ));
     }
 
     delete_directory(&stage_before_merge_path, true)?;
+    journal.finish()?;
 
     Ok(merge_report)
 }
 
-fn merge_contents(
+// Stages the content of a stored object, as its manifest if the object is stored as chunks
+fn stage_object(
+    objects_path: &str,
+    hash: Option<&String>,
+    content: &[u8],
+    staging_path: &str,
+) -> Result<(), io::Error> {
+    match hash.and_then(|hash| read_object_manifest(objects_path, hash)) {
+        Some(manifest) => write_chunk_manifest(staging_path, &manifest),
+        None => write_bytes(staging_path, content),
+    }
+}
+
+pub fn merge_contents(
     ancestor: &str,
     into: &str,
     content_into: &str,
@@ -646,33 +730,33 @@ fn merge_contents(
 
         if agreed {
             if !conflicted_from_lines.is_empty() || !conflicted_into_lines.is_empty() {
-                merged_lines.extend(agreed_lines.drain(..));
+                merged_lines.append(&mut agreed_lines);
                 merged_lines.push(format_conflict(
                     into,
-                    conflicted_into_lines.drain(..).collect(),
+                    std::mem::take(&mut conflicted_into_lines),
                     from,
-                    conflicted_from_lines.drain(..).collect(),
+                    std::mem::take(&mut conflicted_from_lines),
                 ));
             }
             agreed_lines.push(new_line.to_string());
         }
     }
 
-    merged_lines.extend(agreed_lines.drain(..));
+    merged_lines.append(&mut agreed_lines);
 
     if !conflicted_from_lines.is_empty() || !conflicted_into_lines.is_empty() {
         merged_lines.push(format_conflict(
             into,
-            conflicted_into_lines.drain(..).collect(),
+            std::mem::take(&mut conflicted_into_lines),
             from,
-            conflicted_from_lines.drain(..).collect(),
+            std::mem::take(&mut conflicted_from_lines),
         ));
     }
 
     merged_lines.join("\n")
 }
 
-fn merge_conflict(into: &str, content_into: &str, from: &str, content_from: &str) -> String {
+pub fn merge_conflict(into: &str, content_into: &str, from: &str, content_from: &str) -> String {
     let mut merged_lines = Vec::new();
     let mut conflicted_into_lines: Vec<String> = Vec::new();
     let mut conflicted_from_lines: Vec<String> = Vec::new();
@@ -688,12 +772,12 @@ fn merge_conflict(into: &str, content_into: &str, from: &str, content_from: &str
             }
             diff::Result::Both(line, _) => {
                 if !conflicted_from_lines.is_empty() || !conflicted_into_lines.is_empty() {
-                    merged_lines.extend(unchanged_lines.drain(..));
+                    merged_lines.append(&mut unchanged_lines);
                     merged_lines.push(format_conflict(
                         into,
-                        conflicted_into_lines.drain(..).collect(),
+                        std::mem::take(&mut conflicted_into_lines),
                         from,
-                        conflicted_from_lines.drain(..).collect(),
+                        std::mem::take(&mut conflicted_from_lines),
                     ));
                 }
                 unchanged_lines.push(line.to_string());
@@ -701,14 +785,14 @@ fn merge_conflict(into: &str, content_into: &str, from: &str, content_from: &str
         }
     }
 
-    merged_lines.extend(unchanged_lines.drain(..));
+    merged_lines.append(&mut unchanged_lines);
 
     if !conflicted_from_lines.is_empty() || !conflicted_into_lines.is_empty() {
         merged_lines.push(format_conflict(
             into,
-            conflicted_into_lines.drain(..).collect(),
+            std::mem::take(&mut conflicted_into_lines),
             from,
-            conflicted_from_lines.drain(..).collect(),
+            std::mem::take(&mut conflicted_from_lines),
         ));
     }
 
@@ -724,8 +808,8 @@ fn format_conflict(
     format!(
         "<<<<<<< {}\n{}\n=======\n{}\n>>>>>>> {}",
         into,
-        conflicted_from_lines.join("\n"),
         conflicted_into_lines.join("\n"),
+        conflicted_from_lines.join("\n"),
         from
     )
 }
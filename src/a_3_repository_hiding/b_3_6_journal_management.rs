@@ -0,0 +1,283 @@
+// days_dvcs/src/a_3_repository_hiding/b_3_6_journal_management.rs
+//
+
+use super::b_3_1_repository_management::{
+    find_repository, get_lock_path, load_repo_metadata, save_repo_metadata, RepositoryMetadata,
+};
+use super::b_3_2_revision_management::is_revision;
+use super::b_3_3_branch_management::{load_branch_metadata, save_branch_metadata, BranchMetadata};
+
+use crate::a_1_file_system_hiding::{
+    b_1_1_file_interaction::{
+        check_file, delete_file, get_filename, get_parent, read_struct, write_file, write_struct,
+    },
+    b_1_2_directory_interaction::{
+        check_directory, create_directory, delete_directory, rename_directory,
+    },
+    b_1_5_lock_management::{acquire_lock, clear_lock, get_lock_holder, is_process_alive},
+};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::process;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub operation: String,                 // Name of the operation in flight
+    pub pid: u32,                          // Process running the operation
+    pub timestamp: SystemTime,             // When the operation started
+    pub repo_metadata: RepositoryMetadata, // Repository refs before the operation
+    pub branches: Vec<BranchMetadata>,     // Branch refs and staging before the operation
+    pub targets: HashMap<String, String>, // Branch -> new head, once everything it needs is written
+    pub committed_staging: Vec<String>,   // Branches whose staging area the operation consumes
+    pub staging_backup: Option<String>,   // Branch whose staging area was backed up
+}
+
+// Removes the journal when the operation finishes, or recovers from it if the operation fails
+#[derive(Debug)]
+pub struct OperationJournal {
+    path: String, // Repository the operation modifies
+    owned: bool,  // Whether this journal was started here rather than by an enclosing operation
+    finished: bool,
+}
+
+impl OperationJournal {
+    pub fn record_target(
+        &self,
+        branch: &str,
+        revision_id: &str,
+        commits_staging: bool,
+    ) -> Result<(), io::Error> {
+        let mut entry = load_journal(&self.path)?;
+        entry
+            .targets
+            .insert(branch.to_string(), revision_id.to_string());
+
+        if commits_staging && !entry.committed_staging.contains(&branch.to_string()) {
+            entry.committed_staging.push(branch.to_string());
+        }
+
+        save_journal(&self.path, &entry)
+    }
+
+    pub fn record_staging_backup(&self, branch: &str) -> Result<(), io::Error> {
+        let mut entry = load_journal(&self.path)?;
+        entry.staging_backup = Some(branch.to_string());
+        save_journal(&self.path, &entry)
+    }
+
+    pub fn finish(mut self) -> Result<(), io::Error> {
+        self.finished = true;
+
+        if self.owned {
+            delete_file(&get_journal_path(&self.path))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for OperationJournal {
+    fn drop(&mut self) {
+        if self.owned && !self.finished {
+            let _ = recover_operation(&self.path);
+        }
+    }
+}
+
+fn get_journal_path(path: &str) -> String {
+    format!("{}/.dvcs/journal.json", path)
+}
+
+fn get_staging_backup_path(path: &str, branch: &str) -> String {
+    format!("{}/.dvcs/origin/{}/temp_staging_before_merge", path, branch)
+}
+
+pub fn load_journal(path: &str) -> Result<JournalEntry, io::Error> {
+    let entry: JournalEntry = read_struct(&get_journal_path(path))?;
+    Ok(entry)
+}
+
+fn save_journal(path: &str, entry: &JournalEntry) -> Result<(), io::Error> {
+    write_struct(&get_journal_path(path), entry)?;
+    Ok(())
+}
+
+pub fn begin_operation(
+    path: &str,
+    operation: &str,
+    branches: &[String],
+) -> Result<OperationJournal, io::Error> {
+    if check_file(&get_journal_path(path)) {
+        if load_journal(path).is_ok_and(|entry| entry.pid == process::id()) {
+            // Nested inside another operation of this process, e.g. the commit of a merge
+            return Ok(OperationJournal {
+                path: path.to_string(),
+                owned: false,
+                finished: false,
+            });
+        }
+
+        recover_operation(path)?;
+    }
+
+    let mut branch_snapshots = Vec::new();
+
+    for branch in branches {
+        if let Ok(branch_metadata) = load_branch_metadata(path, branch) {
+            branch_snapshots.push(branch_metadata);
+        }
+    }
+
+    let entry = JournalEntry {
+        operation: operation.to_string(),
+        pid: process::id(),
+        timestamp: SystemTime::now(),
+        repo_metadata: load_repo_metadata(path)?,
+        branches: branch_snapshots,
+        targets: HashMap::new(),
+        committed_staging: Vec::new(),
+        staging_backup: None,
+    };
+    save_journal(path, &entry)?;
+
+    Ok(OperationJournal {
+        path: path.to_string(),
+        owned: true,
+        finished: false,
+    })
+}
+
+// Rolls an interrupted operation forward if all of its data was written, and back otherwise
+pub fn recover_operation(path: &str) -> Result<Option<String>, io::Error> {
+    let journal_path = get_journal_path(path);
+
+    if !check_file(&journal_path) {
+        return Ok(None);
+    }
+
+    let entry = match load_journal(path) {
+        Ok(entry) => entry,
+        Err(_) => {
+            // The journal is written before anything else, so an unreadable one means nothing changed
+            delete_file(&journal_path)?;
+            return Ok(Some(
+                "Discarded an incomplete journal; no changes were made".to_string(),
+            ));
+        }
+    };
+    let mut repo_metadata = load_repo_metadata(path)?;
+    let roll_forward = !entry.targets.is_empty()
+        && entry
+            .targets
+            .values()
+            .all(|revision_id| is_revision(path, revision_id).is_ok());
+
+    if roll_forward {
+        for (branch, revision_id) in entry.targets.iter() {
+            let mut branch_metadata = load_branch_metadata(path, branch)?;
+            branch_metadata.head_commit = Some(revision_id.clone());
+
+            if entry.committed_staging.contains(branch) {
+                let staging_path = format!("{}/.dvcs/origin/{}/staging", path, branch);
+                branch_metadata.staging.clear();
+                delete_directory(&staging_path, true)?;
+                create_directory(&staging_path)?;
+            }
+
+            save_branch_metadata(path, branch, &branch_metadata)?;
+            repo_metadata
+                .branches
+                .insert(branch.clone(), revision_id.clone());
+        }
+
+        if let Some(branch) = &entry.staging_backup {
+            let backup_path = get_staging_backup_path(path, branch);
+
+            if check_directory(&backup_path) {
+                delete_directory(&backup_path, true)?;
+            }
+        }
+    } else {
+        for branch_metadata in entry.branches.iter() {
+            save_branch_metadata(path, &branch_metadata.name, branch_metadata)?;
+        }
+
+        // Branches created by the interrupted operation are dropped again
+        for branch in repo_metadata.branches.keys() {
+            if !entry.repo_metadata.branches.contains_key(branch) {
+                let branch_path = format!("{}/.dvcs/origin/{}", path, branch);
+
+                if check_directory(&branch_path) {
+                    delete_directory(&branch_path, true)?;
+                }
+            }
+        }
+
+        if let Some(branch) = &entry.staging_backup {
+            let backup_path = get_staging_backup_path(path, branch);
+            let staging_path = format!("{}/.dvcs/origin/{}/staging", path, branch);
+
+            if check_directory(&backup_path) {
+                delete_directory(&staging_path, true)?;
+                rename_directory(&backup_path, &staging_path)?;
+            }
+        }
+
+        repo_metadata = entry.repo_metadata.clone();
+    }
+
+    save_repo_metadata(path, &repo_metadata)?;
+    write_file(
+        &format!("{}/.dvcs/HEAD", path),
+        &format!(
+            "commit: {}\nref: {}/.dvcs/origin/{}",
+            repo_metadata
+                .branches
+                .get(&repo_metadata.head)
+                .filter(|revision_id| !revision_id.is_empty())
+                .unwrap_or(&"N/A".to_string()),
+            get_parent(path),
+            repo_metadata.head
+        ),
+    )?;
+    delete_file(&journal_path)?;
+
+    Ok(Some(format!(
+        "{} interrupted '{}' started by process {}",
+        if roll_forward {
+            "Rolled forward"
+        } else {
+            "Rolled back"
+        },
+        entry.operation,
+        entry.pid
+    )))
+}
+
+pub fn recover(path: &str) -> Result<String, io::Error> {
+    let path = &find_repository(path)?;
+    let lock_path = get_lock_path(path);
+
+    // A lock left behind by the interrupted process is cleared as part of recovery
+    if check_file(&lock_path) {
+        match get_lock_holder(&lock_path) {
+            Some(pid) if pid != process::id() && is_process_alive(pid) => {}
+            _ => {
+                clear_lock(&lock_path, true)?;
+            }
+        }
+    }
+
+    let _lock = acquire_lock(&lock_path)?;
+
+    match recover_operation(path)? {
+        Some(report) => Ok(report),
+        None => Ok(format!(
+            "No interrupted operation found in repository '{}'",
+            get_filename(path)
+        )),
+    }
+}
@@ -0,0 +1,715 @@
+// days_dvcs/src/a_3_repository_hiding/b_3_7_integrity_management.rs
+//
+
+use super::b_3_11_configuration_management::find_remote_path;
+use super::b_3_1_repository_management::{
+    check_format_version, find_repository_root, get_lock_path, load_repo_metadata,
+    save_repo_metadata, RepositoryMetadata, FORMAT_VERSION,
+};
+use super::b_3_2_revision_management::{
+    hash_revision, is_ancestor, is_revision, load_revision_metadata, RevisionMetadata,
+};
+use super::b_3_3_branch_management::{load_branch_metadata, save_branch_metadata, BranchMetadata};
+use super::b_3_6_journal_management::{load_journal, recover_operation};
+
+use crate::a_1_file_system_hiding::{
+    b_1_1_file_interaction::{
+        check_file, delete_file, get_filename, get_parent, read_file, rename_file, write_file,
+    },
+    b_1_2_directory_interaction::{
+        check_directory, create_directory, delete_directory, list_directory, rename_directory,
+    },
+    b_1_4_object_management::{
+        check_object, check_stored_object, get_object_path, list_loose_objects, verify_object,
+        write_stored_object,
+    },
+    b_1_5_lock_management::{acquire_lock, clear_lock, get_lock_holder, is_process_alive},
+    b_1_6_pack_management::{
+        get_index_path, get_pack_path, list_packs, load_pack_index, read_pack_object,
+    },
+};
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::process;
+
+// Lists every problem found in a repository, each naming the file or directory at fault
+pub fn check_repository(path: &str) -> Result<Vec<String>, io::Error> {
+    let dvcs_path = format!("{}/.dvcs", path);
+    let repo_metadata_path = format!("{}/.metadata/metadata.json", dvcs_path);
+    let lock_path = get_lock_path(path);
+    let mut problems = Vec::new();
+
+    // Hold the lock so a running operation cannot show up as corruption, but report stale ones
+    let _lock = match get_lock_holder(&lock_path) {
+        Some(pid) if pid != process::id() && !is_process_alive(pid) => {
+            problems.push(format!(
+                "Stale lock '{}' is held by process {}, which is no longer running",
+                lock_path, pid
+            ));
+            None
+        }
+        None if check_file(&lock_path) => {
+            problems.push(format!("Lock '{}' does not name a process", lock_path));
+            None
+        }
+        _ => Some(acquire_lock(&lock_path)?),
+    };
+
+    if check_file(&format!("{}/journal.json", dvcs_path)) {
+        problems.push(match load_journal(path) {
+            Ok(entry) => format!(
+                "Interrupted '{}' by process {} is recorded in '{}/journal.json'",
+                entry.operation, entry.pid, dvcs_path
+            ),
+            Err(e) => format!("Journal '{}/journal.json' is unreadable: {}", dvcs_path, e),
+        });
+    }
+
+    // Repository refs must agree with every branch's own metadata
+    let mut branches = HashSet::new();
+
+    match load_repo_metadata(path) {
+        Ok(repo_metadata) => {
+            if !repo_metadata.branches.contains_key(&repo_metadata.head) {
+                problems.push(format!(
+                    "Repository metadata '{}' names head branch '{}', which is not one of its branches",
+                    repo_metadata_path, repo_metadata.head
+                ));
+            }
+
+            let mut branch_names: Vec<_> = repo_metadata.branches.keys().collect();
+            branch_names.sort();
+
+            for branch in branch_names {
+                let revision_id = &repo_metadata.branches[branch];
+                let branch_metadata_path =
+                    format!("{}/origin/{}/.metadata/metadata.json", dvcs_path, branch);
+                branches.insert(branch.clone());
+
+                if !revision_id.is_empty() && is_revision(path, revision_id).is_err() {
+                    problems.push(format!(
+                        "Repository metadata '{}' points branch '{}' at missing revision '{}/commits/{}'",
+                        repo_metadata_path, branch, dvcs_path, revision_id
+                    ));
+                }
+
+                let branch_metadata = match load_branch_metadata(path, branch) {
+                    Ok(branch_metadata) => branch_metadata,
+                    Err(e) => {
+                        problems.push(format!(
+                            "Branch metadata '{}' is unreadable: {}",
+                            branch_metadata_path, e
+                        ));
+                        continue;
+                    }
+                };
+
+                if &branch_metadata.name != branch {
+                    problems.push(format!(
+                        "Branch metadata '{}' names branch '{}' instead of '{}'",
+                        branch_metadata_path, branch_metadata.name, branch
+                    ));
+                }
+
+                let head_commit = branch_metadata.head_commit.unwrap_or_default();
+
+                if &head_commit != revision_id {
+                    problems.push(format!(
+                        "Branch '{}' is at '{}' in '{}' but at '{}' in '{}'",
+                        branch,
+                        if revision_id.is_empty() {
+                            "N/A"
+                        } else {
+                            revision_id
+                        },
+                        repo_metadata_path,
+                        if head_commit.is_empty() {
+                            "N/A"
+                        } else {
+                            &head_commit
+                        },
+                        branch_metadata_path
+                    ));
+                }
+            }
+        }
+        Err(e) => problems.push(format!(
+            "Repository metadata '{}' is unreadable: {}",
+            repo_metadata_path, e
+        )),
+    }
+
+    let origin_path = format!("{}/origin", dvcs_path);
+
+    if check_directory(&origin_path) && !branches.is_empty() {
+        let mut branch_dirs = list_directory(&origin_path, false, false)?;
+        branch_dirs.sort();
+
+        for branch in branch_dirs {
+            if !branches.contains(&branch) {
+                problems.push(format!(
+                    "Branch directory '{}/{}' is not listed in '{}'",
+                    origin_path, branch, repo_metadata_path
+                ));
+            }
+        }
+    }
+
+    // Every object, loose or packed, must hash to its name
+    let objects_path = format!("{}/objects", dvcs_path);
+    let mut hashes = list_loose_objects(&objects_path)?;
+
+    for pack in list_packs(&objects_path)? {
+        match load_pack_index(&objects_path, &pack) {
+            Ok(index) => {
+                let mut packed: Vec<String> = index.entries.into_keys().collect();
+                packed.sort();
+                hashes.extend(packed);
+            }
+            Err(e) => problems.push(format!(
+                "Index of pack '{}' is unreadable: {}",
+                get_pack_path(&objects_path, &pack),
+                e
+            )),
+        }
+    }
+
+    for hash in hashes {
+        if let Err(e) = verify_object(&objects_path, &hash) {
+            problems.push(e.to_string());
+        }
+    }
+
+    // Every revision must hash to its id, and its parents and objects must exist
+    let commits_path = format!("{}/commits", dvcs_path);
+
+    if check_directory(&commits_path) {
+        let mut revision_ids = list_directory(&commits_path, false, false)?;
+        revision_ids.sort();
+
+        for revision_id in revision_ids {
+            let metadata_path = format!("{}/{}/.metadata/metadata.json", commits_path, revision_id);
+
+            let revision_metadata = match load_revision_metadata(path, &revision_id) {
+                Ok(revision_metadata) => revision_metadata,
+                Err(e) => {
+                    problems.push(format!(
+                        "Revision metadata '{}' is unreadable: {}",
+                        metadata_path, e
+                    ));
+                    continue;
+                }
+            };
+
+            let actual_id = hash_revision(&revision_metadata);
+
+            if revision_metadata.id != revision_id || actual_id != revision_id {
+                problems.push(format!(
+                    "Revision '{}' is corrupt: content hashes to '{}'",
+                    metadata_path, actual_id
+                ));
+            }
+
+            for parent in revision_metadata.parents.iter() {
+                if is_revision(path, parent).is_err() {
+                    problems.push(format!(
+                        "Revision '{}' has missing parent '{}/{}'",
+                        metadata_path, commits_path, parent
+                    ));
+                }
+            }
+
+            let mut files: Vec<_> = revision_metadata.files.iter().collect();
+            files.sort();
+
+            for (file, hash) in files {
+                if !check_object(&objects_path, hash) {
+                    problems.push(format!(
+                        "Revision '{}' is missing object '{}' for '{}'",
+                        metadata_path,
+                        get_object_path(&objects_path, hash),
+                        file
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+// Moves a corrupt object or revision out of the way so it can be inspected without being used
+fn quarantine(path: &str, kind: &str, name: &str, source: &str) -> Result<String, io::Error> {
+    let quarantine_dir = format!("{}/.dvcs/quarantine/{}", path, kind);
+    let quarantine_path = format!("{}/{}", quarantine_dir, name);
+    create_directory(&quarantine_dir)?;
+
+    if check_directory(&quarantine_path) {
+        delete_directory(&quarantine_path, true)?;
+    } else if check_file(&quarantine_path) {
+        delete_file(&quarantine_path)?;
+    }
+
+    if check_directory(source) {
+        rename_directory(source, &quarantine_path)?;
+    } else {
+        rename_file(source, &quarantine_path)?;
+    }
+
+    Ok(quarantine_path)
+}
+
+// Picks the newer of two candidate heads, preferring the branch metadata when they diverged
+fn choose_head(
+    path: &str,
+    branch_head: Option<String>,
+    repo_head: Option<String>,
+) -> Option<String> {
+    match (branch_head, repo_head) {
+        (Some(branch_head), Some(repo_head)) => {
+            if branch_head != repo_head
+                && is_ancestor(path, &branch_head, &repo_head).unwrap_or(false)
+            {
+                Some(repo_head)
+            } else {
+                Some(branch_head)
+            }
+        }
+        (branch_head, repo_head) => branch_head.or(repo_head),
+    }
+}
+
+// Rebuilds branch and repository metadata from the revisions on disk, listing every change made
+pub fn repair_repository(path: &str) -> Result<Vec<String>, io::Error> {
+    let dvcs_path = format!("{}/.dvcs", path);
+    let repo_metadata_path = format!("{}/.metadata/metadata.json", dvcs_path);
+    let lock_path = get_lock_path(path);
+    let mut changes = Vec::new();
+
+    if check_file(&lock_path) {
+        match get_lock_holder(&lock_path) {
+            Some(pid) if pid == process::id() || is_process_alive(pid) => {}
+            holder => {
+                clear_lock(&lock_path, true)?;
+                changes.push(format!(
+                    "Cleared stale lock '{}' held by {}",
+                    lock_path,
+                    holder.map_or("an unknown process".to_string(), |pid| format!(
+                        "process {}",
+                        pid
+                    ))
+                ));
+            }
+        }
+    }
+
+    let _lock = acquire_lock(&lock_path)?;
+
+    match recover_operation(path) {
+        Ok(Some(report)) => changes.push(report),
+        Ok(None) => {}
+        Err(e) => {
+            delete_file(&format!("{}/journal.json", dvcs_path))?;
+            changes.push(format!(
+                "Discarded journal '{}/journal.json' that could not be recovered: {}",
+                dvcs_path, e
+            ));
+        }
+    }
+
+    // Quarantine loose objects that no longer hash to their name
+    let objects_path = format!("{}/objects", dvcs_path);
+
+    for hash in list_loose_objects(&objects_path)? {
+        if verify_object(&objects_path, &hash).is_err() {
+            let object_path = get_object_path(&objects_path, &hash);
+            let quarantine_path = quarantine(path, "objects", &hash, &object_path)?;
+            changes.push(format!(
+                "Quarantined corrupt object '{}' to '{}'",
+                object_path, quarantine_path
+            ));
+        }
+    }
+
+    // A pack with an unreadable index or corrupt entries is quarantined as a whole, after its
+    // intact entries are kept as loose objects
+    for pack in list_packs(&objects_path)? {
+        let pack_path = get_pack_path(&objects_path, &pack);
+        let index_path = get_index_path(&objects_path, &pack);
+        let (mut hashes, readable): (Vec<String>, bool) =
+            match load_pack_index(&objects_path, &pack) {
+                Ok(index) => (index.entries.into_keys().collect(), true),
+                Err(_) => (Vec::new(), false),
+            };
+        hashes.sort();
+        let corrupt = !readable
+            || hashes.iter().any(|hash| {
+                !read_pack_object(&objects_path, &pack, hash)
+                    .is_ok_and(|stored| check_stored_object(hash, &stored))
+            });
+
+        if !corrupt {
+            continue;
+        }
+
+        let mut kept = 0;
+
+        for hash in hashes.iter() {
+            if let Ok(stored) = read_pack_object(&objects_path, &pack, hash) {
+                if write_stored_object(&objects_path, hash, &stored).is_ok() {
+                    kept += 1;
+                }
+            }
+        }
+
+        // The index goes first so the pack is never read while it is being moved
+        quarantine(path, "packs", &get_filename(&index_path), &index_path)?;
+        let quarantine_path = quarantine(path, "packs", &get_filename(&pack_path), &pack_path)?;
+        changes.push(format!(
+            "Quarantined corrupt pack '{}' to '{}', keeping {} intact object(s) as loose objects",
+            pack_path, quarantine_path, kept
+        ));
+    }
+
+    // Quarantine revisions that cannot be read or do not hash to their id
+    let commits_path = format!("{}/commits", dvcs_path);
+    let mut revisions = HashMap::new();
+
+    if check_directory(&commits_path) {
+        let mut revision_ids = list_directory(&commits_path, false, false)?;
+        revision_ids.sort();
+
+        for revision_id in revision_ids {
+            match load_revision_metadata(path, &revision_id) {
+                Ok(revision_metadata)
+                    if revision_metadata.id == revision_id
+                        && hash_revision(&revision_metadata) == revision_id =>
+                {
+                    revisions.insert(revision_id, revision_metadata);
+                }
+                _ => {
+                    let revision_path = format!("{}/{}", commits_path, revision_id);
+                    let quarantine_path =
+                        quarantine(path, "commits", &revision_id, &revision_path)?;
+                    changes.push(format!(
+                        "Quarantined unreadable revision '{}' to '{}'",
+                        revision_path, quarantine_path
+                    ));
+                }
+            }
+        }
+    }
+
+    let original_repo_metadata = load_repo_metadata(path).ok();
+    let mut repo_metadata = match &original_repo_metadata {
+        Some(repo_metadata) => repo_metadata.clone(),
+        None => {
+            // The HEAD file still names the current branch as 'ref: .../origin/<branch>'
+            let head = read_file(&format!("{}/HEAD", dvcs_path))
+                .ok()
+                .and_then(|content| {
+                    content
+                        .lines()
+                        .find_map(|line| line.strip_prefix("ref: "))
+                        .map(get_filename)
+                })
+                .unwrap_or("main".to_string());
+            changes.push(format!(
+                "Rebuilt repository metadata '{}'",
+                repo_metadata_path
+            ));
+            RepositoryMetadata {
+                version: FORMAT_VERSION,
+                head,
+                branches: HashMap::new(),
+            }
+        }
+    };
+
+    let origin_path = format!("{}/origin", dvcs_path);
+    let mut branches: Vec<String> = repo_metadata.branches.keys().cloned().collect();
+
+    for branch in list_directory(&origin_path, false, false).unwrap_or_default() {
+        if check_directory(&format!("{}/{}", origin_path, branch)) && !branches.contains(&branch) {
+            branches.push(branch);
+        }
+    }
+
+    branches.sort();
+
+    // Each branch keeps the newest head that still resolves to a valid revision
+    let mut heads = HashMap::new();
+    let mut lost_heads = Vec::new();
+
+    for branch in branches.iter() {
+        let valid =
+            |revision_id: Option<String>| revision_id.filter(|id| revisions.contains_key(id));
+        let branch_head = load_branch_metadata(path, branch)
+            .ok()
+            .and_then(|branch_metadata| branch_metadata.head_commit);
+        let repo_head = repo_metadata
+            .branches
+            .get(branch)
+            .filter(|revision_id| !revision_id.is_empty())
+            .cloned();
+        let lost = branch_head.is_some()
+            || repo_head.is_some()
+            || load_branch_metadata(path, branch).is_err();
+        let head = choose_head(path, valid(branch_head), valid(repo_head));
+
+        if head.is_none() && lost {
+            lost_heads.push(branch.clone());
+        }
+
+        heads.insert(branch.clone(), head);
+    }
+
+    // Branches whose head was lost are reattached to the newest revision no branch reaches
+    if !lost_heads.is_empty() {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<String> = heads.values().flatten().cloned().collect();
+
+        while let Some(revision_id) = stack.pop() {
+            if reachable.insert(revision_id.clone()) {
+                if let Some(revision_metadata) = revisions.get(&revision_id) {
+                    stack.extend(revision_metadata.parents.iter().cloned());
+                }
+            }
+        }
+
+        let parents: HashSet<&String> = revisions
+            .values()
+            .flat_map(|revision_metadata| revision_metadata.parents.iter())
+            .collect();
+        let mut tips: Vec<&RevisionMetadata> = revisions
+            .values()
+            .filter(|revision_metadata| {
+                !reachable.contains(&revision_metadata.id)
+                    && !parents.contains(&revision_metadata.id)
+            })
+            .collect();
+        tips.sort_by_key(|revision_metadata| revision_metadata.timestamp);
+
+        for branch in lost_heads {
+            if let Some(tip) = tips.pop() {
+                changes.push(format!(
+                    "Reattached branch '{}' to unreferenced revision '{}'",
+                    branch, tip.id
+                ));
+                heads.insert(branch, Some(tip.id.clone()));
+            }
+        }
+    }
+
+    for branch in branches.iter() {
+        let branch_path = format!("{}/{}", origin_path, branch);
+        let branch_metadata_path = format!("{}/.metadata/metadata.json", branch_path);
+        let staging_path = format!("{}/staging", branch_path);
+        let head = heads[branch].clone();
+
+        if !check_directory(&branch_path) {
+            changes.push(format!(
+                "Recreated missing branch directory '{}'",
+                branch_path
+            ));
+        }
+
+        create_directory(&format!("{}/.metadata", branch_path))?;
+        create_directory(&staging_path)?;
+
+        let branch_metadata = match load_branch_metadata(path, branch) {
+            Ok(branch_metadata) => {
+                let mut repaired = branch_metadata.clone();
+                repaired.name = branch.clone();
+                repaired.head_commit = head.clone();
+
+                if branch_metadata.head_commit != head {
+                    changes.push(format!(
+                        "Moved branch '{}' from '{}' to '{}' in '{}'",
+                        branch,
+                        branch_metadata.head_commit.unwrap_or("N/A".to_string()),
+                        head.clone().unwrap_or("N/A".to_string()),
+                        branch_metadata_path
+                    ));
+                }
+
+                if branch_metadata.name != *branch {
+                    changes.push(format!(
+                        "Renamed branch '{}' to '{}' in '{}'",
+                        branch_metadata.name, branch, branch_metadata_path
+                    ));
+                }
+
+                repaired
+            }
+            Err(_) => {
+                // Staged deletions leave no copy behind, so only staged files can be recovered
+                let staging = list_directory(&staging_path, true, true)?
+                    .iter()
+                    .map(|file| file[staging_path.len() + 1..].to_string())
+                    .collect();
+                changes.push(format!(
+                    "Rebuilt branch metadata '{}' at '{}'",
+                    branch_metadata_path,
+                    head.clone().unwrap_or("N/A".to_string())
+                ));
+                BranchMetadata {
+                    name: branch.clone(),
+                    head_commit: head.clone(),
+                    staging,
+                }
+            }
+        };
+        save_branch_metadata(path, branch, &branch_metadata)?;
+
+        let revision_id = head.unwrap_or_default();
+
+        if repo_metadata.branches.get(branch) != Some(&revision_id)
+            && original_repo_metadata.is_some()
+        {
+            changes.push(format!(
+                "Set branch '{}' to '{}' in '{}'",
+                branch,
+                if revision_id.is_empty() {
+                    "N/A"
+                } else {
+                    &revision_id
+                },
+                repo_metadata_path
+            ));
+        }
+
+        repo_metadata.branches.insert(branch.clone(), revision_id);
+    }
+
+    if !repo_metadata.branches.contains_key(&repo_metadata.head) {
+        let head = if repo_metadata.branches.contains_key("main") {
+            "main".to_string()
+        } else {
+            branches.first().cloned().unwrap_or_default()
+        };
+        changes.push(format!(
+            "Moved head from missing branch '{}' to '{}' in '{}'",
+            repo_metadata.head, head, repo_metadata_path
+        ));
+        repo_metadata.head = head;
+    }
+
+    if original_repo_metadata.as_ref() != Some(&repo_metadata) {
+        save_repo_metadata(path, &repo_metadata)?;
+    }
+
+    let head_path = format!("{}/HEAD", dvcs_path);
+    let head_commit = repo_metadata
+        .branches
+        .get(&repo_metadata.head)
+        .filter(|revision_id| !revision_id.is_empty())
+        .cloned()
+        .unwrap_or("N/A".to_string());
+
+    // Only the commit and the branch name matter; the ref prefix differs between commands
+    let head_matches = read_file(&head_path).is_ok_and(|content| {
+        let mut lines = content.lines();
+        lines.next() == Some(&format!("commit: {}", head_commit))
+            && lines
+                .next()
+                .and_then(|line| line.strip_prefix("ref: "))
+                .map(get_filename)
+                == Some(repo_metadata.head.clone())
+    });
+
+    if !head_matches {
+        write_file(
+            &head_path,
+            &format!(
+                "commit: {}\nref: {}/.dvcs/origin/{}",
+                head_commit,
+                get_parent(path),
+                repo_metadata.head
+            ),
+        )?;
+        changes.push(format!("Rewrote '{}'", head_path));
+    }
+
+    Ok(changes)
+}
+
+// Checks a repository, and its remote with `remote`, repairing what it can first with `repair`.
+// Only the '.dvcs' directory is needed, so repositories with missing metadata can be repaired
+pub fn fsck(path: &str, remote: bool, repair: bool) -> Result<String, io::Error> {
+    let path = &find_repository_root(path)?;
+    check_format_version(path)?;
+    let mut repositories = vec![path.clone()];
+
+    if remote {
+        let remote_path = find_remote_path(path).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "No remote repository found for '{}': {}",
+                    get_filename(path),
+                    e
+                ),
+            )
+        })?;
+        check_format_version(&remote_path)?;
+        repositories.push(remote_path);
+    }
+
+    let mut report = Vec::new();
+    let mut failed = false;
+
+    for repository in repositories.iter() {
+        let mut changes = Vec::new();
+        let mut problems = Vec::new();
+
+        if repair {
+            for change in repair_repository(repository)? {
+                changes.push(format!("  {}", change));
+            }
+        }
+
+        for problem in check_repository(repository)? {
+            problems.push(format!("  {}", problem));
+        }
+
+        if !changes.is_empty() {
+            report.push(format!(
+                "Made {} repair(s) in repository '{}':\n{}",
+                changes.len(),
+                get_filename(repository),
+                changes.join("\n")
+            ));
+        }
+
+        if !problems.is_empty() {
+            report.push(format!(
+                "Found {} problem(s) in repository '{}':\n{}",
+                problems.len(),
+                get_filename(repository),
+                problems.join("\n")
+            ));
+            failed = true;
+        }
+    }
+
+    if failed {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            report.join("\n"),
+        ));
+    }
+
+    report.push(format!(
+        "No problems found in repository '{}'{}",
+        get_filename(path),
+        repositories
+            .get(1)
+            .map_or(String::new(), |remote_path| format!(
+                " or its remote '{}'",
+                get_filename(remote_path)
+            ))
+    ));
+    Ok(report.join("\n"))
+}
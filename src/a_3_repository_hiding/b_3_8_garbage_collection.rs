@@ -0,0 +1,268 @@
+// days_dvcs/src/a_3_repository_hiding/b_3_8_garbage_collection.rs
+//
+
+use super::b_3_12_history_management::get_sequence_revisions;
+use super::b_3_1_repository_management::{
+    get_lock_path, is_repository, load_repo_metadata, lock_repository,
+};
+use super::b_3_2_revision_management::{get_ancestors, load_revision_metadata};
+use super::b_3_3_branch_management::load_branch_metadata;
+use super::b_3_9_pack_management::repack;
+
+use crate::a_1_file_system_hiding::{
+    b_1_1_file_interaction::{check_file, delete_file, get_filename, is_temp_file},
+    b_1_2_directory_interaction::{
+        check_directory, delete_directory, is_empty_directory, list_directory,
+    },
+    b_1_3_metadata_management::get_file_metadata,
+    b_1_4_object_management::{get_object_path, list_loose_objects},
+    b_1_5_lock_management::acquire_lock,
+    b_1_6_pack_management::{get_pack_path, list_packs, load_pack_index},
+    b_1_7_chunk_management::{read_chunk_manifest, read_object_manifest},
+};
+
+use std::collections::HashSet;
+use std::io;
+
+// Directories that operations used to stage data in and may leave behind when interrupted
+const TEMP_DIRECTORIES: [&str; 2] = ["temp_staging_before_merge", "temp_commits"];
+
+pub fn get_size(path: &str) -> u64 {
+    if check_directory(path) {
+        list_directory(path, true, true)
+            .unwrap_or_default()
+            .iter()
+            .map(|file| get_file_metadata(file).map_or(0, |metadata| metadata.size))
+            .sum()
+    } else {
+        get_file_metadata(path).map_or(0, |metadata| metadata.size)
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    match size {
+        size if size >= 1024 * 1024 => format!("{:.1} MiB", size as f64 / (1024.0 * 1024.0)),
+        size if size >= 1024 => format!("{:.1} KiB", size as f64 / 1024.0),
+        size => format!("{} B", size),
+    }
+}
+
+// Collects every revision reachable from a branch head, in both repository and branch metadata,
+// or from a sequence in progress, listing parents before their children
+pub fn get_reachable_revisions(path: &str) -> Result<Vec<String>, io::Error> {
+    let repo_metadata = load_repo_metadata(path)?;
+    let mut branches: Vec<&String> = repo_metadata.branches.keys().collect();
+    branches.sort();
+
+    let mut heads = Vec::new();
+
+    for branch in branches {
+        heads.push(repo_metadata.branches[branch].clone());
+
+        if let Some(head_commit) = load_branch_metadata(path, branch)?.head_commit {
+            heads.push(head_commit);
+        }
+    }
+
+    // A rebase in progress still needs the revisions it replays and may restore
+    heads.extend(get_sequence_revisions(path)?);
+
+    let mut reachable = Vec::new();
+    let mut visited = HashSet::new();
+
+    for head in heads.iter().filter(|head| !head.is_empty()) {
+        if visited.contains(head) {
+            continue;
+        }
+
+        let ancestors = get_ancestors(path, head).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Cannot determine reachable revisions, run 'fsck' first: {}",
+                    e
+                ),
+            )
+        })?;
+
+        for ancestor in ancestors {
+            if visited.insert(ancestor.clone()) {
+                reachable.push(ancestor);
+            }
+        }
+    }
+
+    Ok(reachable)
+}
+
+pub fn gc(path: &str, dry_run: bool) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let dvcs_path = format!("{}/.dvcs", path);
+
+    // A dry run must not change anything, so it does not recover interrupted operations either
+    let _lock = if dry_run {
+        let lock = acquire_lock(&get_lock_path(path))?;
+
+        if check_file(&format!("{}/journal.json", dvcs_path)) {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!(
+                    "An interrupted operation is recorded in '{}/journal.json'. Run 'recover' first",
+                    dvcs_path
+                ),
+            ));
+        }
+
+        lock
+    } else {
+        lock_repository(path)?
+    };
+
+    let revision_ids = get_reachable_revisions(path)?;
+    let reachable: HashSet<&String> = revision_ids.iter().collect();
+    let mut referenced = HashSet::new();
+
+    for revision_id in revision_ids.iter() {
+        referenced.extend(
+            load_revision_metadata(path, revision_id)?
+                .files
+                .into_values(),
+        );
+    }
+
+    let objects_path = format!("{}/objects", dvcs_path);
+    let origin_path = format!("{}/origin", dvcs_path);
+
+    // Large files are stored as chunks as soon as they are staged, so staged manifests count too
+    for branch in list_directory(&origin_path, false, false)? {
+        let staging_path = format!("{}/{}/staging", origin_path, branch);
+
+        if check_directory(&staging_path) {
+            for file in list_directory(&staging_path, true, true)? {
+                if let Some(manifest) = read_chunk_manifest(&file) {
+                    referenced.insert(manifest.hash);
+                }
+            }
+        }
+    }
+
+    let mut chunks = Vec::new();
+
+    for hash in referenced.iter() {
+        if let Some(manifest) = read_object_manifest(&objects_path, hash) {
+            chunks.extend(manifest.chunks);
+        }
+    }
+
+    referenced.extend(chunks);
+
+    let mut revisions = Vec::new();
+    let mut objects = Vec::new();
+    let mut temp_files = Vec::new();
+
+    let commits_path = format!("{}/commits", dvcs_path);
+
+    if check_directory(&commits_path) {
+        for revision_id in list_directory(&commits_path, false, false)? {
+            if !reachable.contains(&revision_id) {
+                revisions.push(format!("{}/{}", commits_path, revision_id));
+            }
+        }
+    }
+
+    for hash in list_loose_objects(&objects_path)? {
+        if !referenced.contains(&hash) {
+            objects.push(get_object_path(&objects_path, &hash));
+        }
+    }
+
+    // Unused objects inside packs are dropped by rewriting the packs without them
+    let mut packed_garbage = 0;
+
+    for pack in list_packs(&objects_path)? {
+        for (hash, entry) in load_pack_index(&objects_path, &pack)?.entries {
+            if !referenced.contains(&hash) {
+                objects.push(format!("{}#{}", get_pack_path(&objects_path, &pack), hash));
+                packed_garbage += entry.length;
+            }
+        }
+    }
+
+    for branch in list_directory(&origin_path, false, false)? {
+        for temp_directory in TEMP_DIRECTORIES {
+            let temp_path = format!("{}/{}/{}", origin_path, branch, temp_directory);
+
+            if check_directory(&temp_path) {
+                temp_files.push(temp_path);
+            }
+        }
+    }
+
+    for file in list_directory(&dvcs_path, true, true)? {
+        if is_temp_file(&file) {
+            temp_files.push(file);
+        }
+    }
+
+    revisions.sort();
+    objects.sort();
+    temp_files.sort();
+
+    let mut report = Vec::new();
+    let mut reclaimed = packed_garbage;
+
+    for garbage in revisions
+        .iter()
+        .chain(objects.iter())
+        .chain(temp_files.iter())
+    {
+        report.push(format!("  {}", garbage));
+
+        if garbage.contains('#') {
+            continue; // Packed objects are removed by the repack below
+        }
+
+        reclaimed += get_size(garbage);
+
+        if dry_run {
+            continue;
+        }
+
+        if check_directory(garbage) {
+            delete_directory(garbage, true)?;
+        } else {
+            delete_file(garbage)?;
+        }
+    }
+
+    if !dry_run && packed_garbage > 0 {
+        repack(path, &revision_ids, |hash| referenced.contains(hash))?;
+    }
+
+    // Object prefix directories emptied by the collection are removed as well
+    if !dry_run && check_directory(&objects_path) {
+        for prefix in list_directory(&objects_path, true, false)? {
+            if check_directory(&prefix) && is_empty_directory(&prefix).is_ok() {
+                delete_directory(&prefix, false)?;
+            }
+        }
+    }
+
+    if report.is_empty() {
+        return Ok(format!(
+            "Nothing to collect in repository '{}'",
+            get_filename(path)
+        ));
+    }
+
+    Ok(format!(
+        "{} {} unreachable revision(s), {} orphaned object(s) and {} temporary file(s), {} {}:\n{}",
+        if dry_run { "Would remove" } else { "Removed" },
+        revisions.len(),
+        objects.len(),
+        temp_files.len(),
+        if dry_run { "reclaiming" } else { "reclaimed" },
+        format_size(reclaimed),
+        report.join("\n")
+    ))
+}
@@ -0,0 +1,149 @@
+// days_dvcs/src/a_3_repository_hiding/b_3_9_pack_management.rs
+//
+
+use super::b_3_1_repository_management::{is_repository, lock_repository};
+use super::b_3_2_revision_management::load_revision_metadata;
+use super::b_3_8_garbage_collection::{format_size, get_reachable_revisions, get_size};
+
+use crate::a_1_file_system_hiding::{
+    b_1_1_file_interaction::{check_file, delete_file, get_filename},
+    b_1_4_object_management::{get_object_path, list_loose_objects},
+    b_1_6_pack_management::{delete_pack, get_packs_path, list_packs, load_pack_index, write_pack},
+    b_1_7_chunk_management::read_object_manifest,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+// Lists the objects of the given revisions (parents first), each with the previous version of
+// the same file as its delta base. Chunked files are followed by their chunks, which have none
+pub fn get_pack_objects(
+    path: &str,
+    revision_ids: &[String],
+) -> Result<Vec<(String, Option<String>)>, io::Error> {
+    let objects_path = format!("{}/.dvcs/objects", path);
+    let mut objects = Vec::new();
+    let mut included = HashSet::new();
+    let mut previous_versions: HashMap<String, String> = HashMap::new();
+
+    for revision_id in revision_ids {
+        let mut files: Vec<_> = load_revision_metadata(path, revision_id)?
+            .files
+            .into_iter()
+            .collect();
+        files.sort();
+
+        for (file, hash) in files {
+            if included.insert(hash.clone()) {
+                objects.push((hash.clone(), previous_versions.get(&file).cloned()));
+
+                // Chunks are already shared between versions, so they get no delta base and are
+                // never searched for deltas or held in memory while packing
+                if let Some(manifest) = read_object_manifest(&objects_path, &hash) {
+                    for chunk in manifest.chunks {
+                        if included.insert(chunk.clone()) {
+                            objects.push((chunk, None));
+                        }
+                    }
+                }
+            }
+
+            previous_versions.insert(file, hash);
+        }
+    }
+
+    Ok(objects)
+}
+
+// Writes the objects of the given revisions into one new pack that replaces all loose objects
+// and packs holding them; other packed objects are dropped unless `keep` accepts them
+pub fn repack<F>(path: &str, revision_ids: &[String], keep: F) -> Result<Option<String>, io::Error>
+where
+    F: Fn(&str) -> bool,
+{
+    let objects_path = format!("{}/.dvcs/objects", path);
+    let old_packs = list_packs(&objects_path)?;
+    let mut objects = get_pack_objects(path, revision_ids)?;
+    let included: HashSet<String> = objects.iter().map(|(hash, _)| hash.clone()).collect();
+
+    for pack in old_packs.iter() {
+        let mut hashes: Vec<String> = load_pack_index(&objects_path, pack)?
+            .entries
+            .into_keys()
+            .filter(|hash| !included.contains(hash) && keep(hash))
+            .collect();
+        hashes.sort();
+        objects.extend(hashes.into_iter().map(|hash| (hash, None)));
+    }
+
+    let new_pack = write_pack(&objects_path, &objects_path, &objects)?;
+
+    // Only now that the new pack is complete are the old copies removed
+    for (hash, _) in objects.iter() {
+        let object_path = get_object_path(&objects_path, hash);
+
+        if check_file(&object_path) {
+            delete_file(&object_path)?;
+        }
+    }
+
+    for pack in old_packs {
+        if Some(&pack) != new_pack.as_ref() {
+            delete_pack(&objects_path, &pack)?;
+        }
+    }
+
+    Ok(new_pack)
+}
+
+pub fn pack(path: &str) -> Result<String, io::Error> {
+    let path = &is_repository(path)?;
+    let _lock = lock_repository(path)?;
+    let objects_path = format!("{}/.dvcs/objects", path);
+    let packs_path = get_packs_path(&objects_path);
+    let revision_ids = get_reachable_revisions(path)?;
+
+    let referenced: HashSet<String> = get_pack_objects(path, &revision_ids)?
+        .into_iter()
+        .map(|(hash, _)| hash)
+        .collect();
+    let loose_objects: Vec<String> = list_loose_objects(&objects_path)?
+        .into_iter()
+        .filter(|hash| referenced.contains(hash))
+        .collect();
+
+    if loose_objects.is_empty() && list_packs(&objects_path)?.len() <= 1 {
+        return Ok(format!(
+            "Nothing to pack in repository '{}'",
+            get_filename(path)
+        ));
+    }
+
+    let size_before = loose_objects
+        .iter()
+        .map(|hash| get_size(&get_object_path(&objects_path, hash)))
+        .sum::<u64>()
+        + get_size(&packs_path);
+
+    let Some(new_pack) = repack(path, &revision_ids, |_| true)? else {
+        return Ok(format!(
+            "Nothing to pack in repository '{}'",
+            get_filename(path)
+        ));
+    };
+    let index = load_pack_index(&objects_path, &new_pack)?;
+    let deltas = index
+        .entries
+        .values()
+        .filter(|entry| entry.base.is_some())
+        .count();
+
+    Ok(format!(
+        "Packed {} object(s), {} as deltas, into '{}' ({} before, {} after)",
+        index.entries.len(),
+        deltas,
+        new_pack,
+        format_size(size_before),
+        format_size(get_size(&packs_path))
+    ))
+}
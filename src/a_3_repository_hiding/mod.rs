@@ -9,3 +9,17 @@ pub mod b_3_3_branch_management;
 pub mod b_3_4_synchronization_handler;
 
 pub mod b_3_5_cross_revision_management;
+
+pub mod b_3_6_journal_management;
+
+pub mod b_3_7_integrity_management;
+
+pub mod b_3_8_garbage_collection;
+
+pub mod b_3_9_pack_management;
+
+pub mod b_3_10_migration_management;
+
+pub mod b_3_11_configuration_management;
+
+pub mod b_3_12_history_management;
@@ -9,6 +9,10 @@
 use days_dvcs::a_1_file_system_hiding::b_1_1_file_interaction::*;
 use days_dvcs::a_1_file_system_hiding::b_1_2_directory_interaction::*;
 use days_dvcs::a_1_file_system_hiding::b_1_3_metadata_management::*;
+use days_dvcs::a_1_file_system_hiding::b_1_4_object_management::*;
+use days_dvcs::a_1_file_system_hiding::b_1_5_lock_management::*;
+use days_dvcs::a_1_file_system_hiding::b_1_6_pack_management::*;
+use days_dvcs::a_1_file_system_hiding::b_1_7_chunk_management::*;
 
 #[cfg(test)]
 mod tests {
@@ -16,7 +20,7 @@ mod tests {
     use serde::{Deserialize, Serialize};
     use std::fs;
     use std::path::Path;
-    use std::time::SystemTime;
+    use std::time::{Duration, SystemTime};
 
     /// B.1.1 File Interaction
 
@@ -47,6 +51,26 @@ mod tests {
         delete_file(path).unwrap();
     }
 
+    #[test]
+    fn test_read_and_write_bytes() {
+        let path = "./src/test_files/test_bytes.bin";
+        let content = vec![0x89, b'P', b'N', b'G', 0x00, 0xff, 0xfe];
+        write_bytes(path, &content).unwrap();
+
+        assert_eq!(read_bytes(path).unwrap(), content);
+        assert!(read_file(path).is_err());
+
+        delete_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_is_binary_file() {
+        assert!(!is_binary_file(b"Hello,\tworld!\r\n"));
+        assert!(!is_binary_file("Grüße".as_bytes()));
+        assert!(is_binary_file(&[b'a', 0x00, b'b']));
+        assert!(is_binary_file(&[0xff, 0xfe, 0xfd]));
+    }
+
     #[test]
     fn test_read_struct() {
         let path = "test_struct.json";
@@ -75,6 +99,50 @@ mod tests {
         delete_file(path).unwrap();
     }
 
+    #[test]
+    fn test_write_file_replaces_atomically() {
+        let directory = "./test_dir_atomic_write";
+        let path = format!("{}/file.txt", directory);
+        create_directory(directory).unwrap();
+
+        write_file(&path, "First version").unwrap();
+        write_file(&path, "Second").unwrap();
+        copy_file(&path, &format!("{}/copy.txt", directory)).unwrap();
+
+        assert_eq!(read_file(&path).unwrap(), "Second");
+        let mut entries = list_directory(directory, false, false).unwrap();
+        entries.sort();
+        assert_eq!(entries, vec!["copy.txt", "file.txt"]); // No temporary files left behind
+
+        delete_directory(directory, true).unwrap();
+    }
+
+    #[test]
+    fn test_create_file() {
+        let directory = "./test_dir_create_file";
+        let path = format!("{}/file.txt", directory);
+        create_directory(directory).unwrap();
+
+        create_file(&path, "First").unwrap();
+        let err = create_file(&path, "Second").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+
+        assert_eq!(read_file(&path).unwrap(), "First");
+        assert_eq!(
+            list_directory(directory, false, false).unwrap(),
+            vec!["file.txt"]
+        );
+
+        delete_directory(directory, true).unwrap();
+    }
+
+    #[test]
+    fn test_is_temp_file() {
+        assert!(is_temp_file("./dir/.metadata.json.tmp.4242.0"));
+        assert!(!is_temp_file("./dir/metadata.json"));
+        assert!(!is_temp_file("./dir/.notes.tmp.txt"));
+    }
+
     #[test]
     fn test_write_struct() {
         let path = "test_write_struct.json";
@@ -132,8 +200,41 @@ mod tests {
         assert_eq!(get_filename(path), "test_file.txt");
     }
 
+    #[test]
+    fn test_write_and_read_link() {
+        let path = "./test_link";
+        write_link(path, "missing_target.txt").unwrap();
+
+        assert!(check_link(path));
+        assert!(check_file(path));
+        assert_eq!(read_link(path).unwrap(), "missing_target.txt");
+        assert_eq!(read_entry(path).unwrap(), b"missing_target.txt");
+
+        delete_file(path).unwrap();
+        assert!(!check_link(path));
+    }
+
     /// B.1.2 Directory Interaction
 
+    #[test]
+    fn test_list_directory_does_not_follow_links() {
+        let path = "./test_dir_links";
+        create_directory(&format!("{}/sub", path)).unwrap();
+        write_file(&format!("{}/sub/file.txt", path), "content").unwrap();
+        write_link(&format!("{}/sub/loop", path), "..").unwrap();
+        write_link(&format!("{}/sub_link", path), "sub").unwrap();
+
+        let mut files = list_directory(path, false, true).unwrap();
+        files.sort();
+        assert_eq!(files, vec!["file.txt", "loop", "sub_link"]);
+
+        copy_directory(path, "./test_dir_links_copy").unwrap();
+        assert_eq!(read_link("./test_dir_links_copy/sub/loop").unwrap(), "..");
+
+        delete_directory(path, true).unwrap();
+        delete_directory("./test_dir_links_copy", true).unwrap();
+    }
+
     #[test]
     fn test_create_directory() {
         let path = "./test_dir_create";
@@ -193,7 +294,7 @@ mod tests {
 
         assert!(check_file(&format!("{}/file.txt", dest_dir)));
         assert_eq!(
-            fs::read_to_string(&format!("{}/file.txt", dest_dir)).unwrap(),
+            fs::read_to_string(format!("{}/file.txt", dest_dir)).unwrap(),
             "beta_tests content"
         );
 
@@ -215,4 +316,331 @@ mod tests {
 
         delete_file(path).unwrap();
     }
+
+    #[test]
+    fn test_get_and_set_file_mode() {
+        let path = "./test_file_mode.sh";
+        write_file(path, "#!/bin/sh\n").unwrap();
+        assert_eq!(get_file_mode(path).unwrap(), REGULAR_FILE_MODE);
+
+        set_file_mode(path, EXECUTABLE_FILE_MODE).unwrap();
+        assert_eq!(get_file_mode(path).unwrap(), EXECUTABLE_FILE_MODE);
+        assert_eq!(get_file_metadata(path).unwrap().mode & 0o111, 0o111);
+
+        set_file_mode(path, REGULAR_FILE_MODE).unwrap();
+        assert_eq!(get_file_mode(path).unwrap(), REGULAR_FILE_MODE);
+
+        delete_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_hash_file_cached() {
+        let path = "./test_stat_cache.txt";
+        let mut cache = StatCache::default();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+
+        write_file(path, "first").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(an_hour_ago)
+            .unwrap();
+        let hash = hash_file_cached(&mut cache, path, "file").unwrap();
+        assert_eq!(hash, hash_content(b"first"));
+
+        // Same size, time and mode: the cached hash is trusted without reading the file
+        write_file(path, "other").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(an_hour_ago)
+            .unwrap();
+        assert_eq!(hash_file_cached(&mut cache, path, "file").unwrap(), hash);
+
+        // A file modified just before it was cached is rehashed
+        write_file(path, "third").unwrap();
+        update_stat_cache(&mut cache, path, "file", "stale");
+        assert_eq!(
+            hash_file_cached(&mut cache, path, "file").unwrap(),
+            hash_content(b"third")
+        );
+
+        let cache_path = "./test_stat_cache.json";
+        save_stat_cache(cache_path, &cache).unwrap();
+        assert_eq!(load_stat_cache(cache_path), cache);
+
+        delete_file(path).unwrap();
+        delete_file(cache_path).unwrap();
+        assert_eq!(load_stat_cache(cache_path), StatCache::default());
+    }
+
+    /// B.1.4 Object Management
+
+    #[test]
+    fn test_write_and_read_object() {
+        let objects_path = "./test_objects_write";
+        let hash = write_object(objects_path, b"Hello, world!").unwrap();
+
+        assert!(check_object(objects_path, &hash));
+        assert_eq!(read_object(objects_path, &hash).unwrap(), b"Hello, world!");
+        assert_eq!(write_object(objects_path, b"Hello, world!").unwrap(), hash);
+        assert_eq!(list_directory(objects_path, false, true).unwrap().len(), 1);
+
+        delete_directory(objects_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_objects_are_compressed() {
+        let objects_path = "./test_objects_compressed";
+        let content = "Hello, world!\n".repeat(100).into_bytes();
+        let hash = write_object(objects_path, &content).unwrap();
+
+        let stored = read_bytes(&get_object_path(objects_path, &hash)).unwrap();
+        assert!(stored.len() < content.len());
+        assert_eq!(read_object(objects_path, &hash).unwrap(), content);
+
+        // Objects stored uncompressed by older versions are still readable
+        let legacy_hash = hash_content(b"Legacy");
+        create_directory(&format!("{}/{}", objects_path, &legacy_hash[..2])).unwrap();
+        write_bytes(&get_object_path(objects_path, &legacy_hash), b"Legacy").unwrap();
+        assert_eq!(read_object(objects_path, &legacy_hash).unwrap(), b"Legacy");
+        assert!(verify_object(objects_path, &legacy_hash).is_ok());
+
+        delete_directory(objects_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_hash_content_is_sha256() {
+        assert_eq!(
+            hash_content(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_verify_object() {
+        let objects_path = "./test_objects_verify";
+        let hash = write_object(objects_path, b"Original").unwrap();
+        assert!(verify_object(objects_path, &hash).is_ok());
+
+        write_bytes(&get_object_path(objects_path, &hash), b"Tampered").unwrap();
+        let err = verify_object(objects_path, &hash).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        delete_directory(objects_path, true).unwrap();
+    }
+
+    /// B.1.5 Lock Management
+
+    #[test]
+    fn test_acquire_lock() {
+        let lock_path = "./src/test_files/test_acquire.lock";
+
+        {
+            let _lock = acquire_lock(lock_path).unwrap();
+            assert_eq!(get_lock_holder(lock_path), Some(std::process::id()));
+
+            // The holding process may acquire the lock again without releasing it early
+            drop(acquire_lock(lock_path).unwrap());
+            assert!(check_file(lock_path));
+        }
+
+        assert!(!check_file(lock_path));
+    }
+
+    #[test]
+    fn test_clear_stale_lock() {
+        let lock_path = "./src/test_files/test_stale.lock";
+        write_file(lock_path, "999999999").unwrap();
+
+        let err = acquire_lock(lock_path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        assert!(err.to_string().contains("999999999"));
+
+        assert_eq!(clear_lock(lock_path, false).unwrap(), Some(999999999));
+        assert!(!check_file(lock_path));
+        assert!(clear_lock(lock_path, false).is_err());
+    }
+
+    /// B.1.6 Pack Management
+
+    #[test]
+    fn test_create_and_apply_delta() {
+        let base = "The quick brown fox jumps over the lazy dog.\n".repeat(20);
+        let target = format!("{}One more line.\n", base.replacen("lazy", "sleepy", 1));
+        let delta = create_delta(base.as_bytes(), target.as_bytes());
+
+        assert!(delta.len() < target.len() / 2);
+        assert_eq!(
+            apply_delta(base.as_bytes(), &delta).unwrap(),
+            target.as_bytes()
+        );
+        assert!(apply_delta(b"Another base", &delta).is_err());
+    }
+
+    #[test]
+    fn test_write_and_read_pack() {
+        let src = "./test_objects_pack_src";
+        let dest = "./test_objects_pack_dest";
+        let first = "Line of a growing log file\n".repeat(50);
+        let second = format!("{}Another line\n", first);
+        let first_hash = write_object(src, first.as_bytes()).unwrap();
+        let second_hash = write_object(src, second.as_bytes()).unwrap();
+
+        let pack = write_pack(
+            src,
+            dest,
+            &[
+                (first_hash.clone(), None),
+                (second_hash.clone(), Some(first_hash.clone())),
+            ],
+        )
+        .unwrap()
+        .unwrap();
+
+        let index = load_pack_index(dest, &pack).unwrap();
+        assert_eq!(index.entries[&second_hash].base, Some(first_hash.clone()));
+        assert!(check_object(dest, &second_hash));
+        assert_eq!(read_object(dest, &second_hash).unwrap(), second.as_bytes());
+        assert!(verify_object(dest, &first_hash).is_ok());
+        assert_eq!(list_packs(dest).unwrap(), vec![pack.clone()]);
+
+        // Lookups reuse parsed indexes, but never one whose pack was removed or written again
+        delete_pack(dest, &pack).unwrap();
+        assert!(!check_object(dest, &second_hash));
+        let objects = [(second_hash.clone(), None)];
+        let pack = write_pack(src, dest, &objects).unwrap().unwrap();
+        assert_eq!(list_packs(dest).unwrap(), vec![pack]);
+        assert!(check_object(dest, &second_hash));
+        assert!(!check_object(dest, &first_hash));
+        assert_eq!(read_object(dest, &second_hash).unwrap(), second.as_bytes());
+
+        // A base serves every later entry naming it, a base listed later is not used, duplicates
+        // are packed once, and the pack is written without leaving temporary files behind
+        let third = format!("{}One more line\n", second);
+        let third_hash = write_object(src, third.as_bytes()).unwrap();
+        let fourth = format!("{}Last line\n", third);
+        let fourth_hash = write_object(src, fourth.as_bytes()).unwrap();
+        let other_dest = "./test_objects_pack_other_dest";
+        let other_pack = write_pack(
+            src,
+            other_dest,
+            &[
+                (third_hash.clone(), Some(fourth_hash.clone())),
+                (first_hash.clone(), None),
+                (second_hash.clone(), Some(first_hash.clone())),
+                (fourth_hash.clone(), Some(first_hash.clone())),
+                (second_hash.clone(), None),
+            ],
+        )
+        .unwrap()
+        .unwrap();
+        let index = load_pack_index(other_dest, &other_pack).unwrap();
+        assert_eq!(index.entries.len(), 4);
+        assert_eq!(index.entries[&third_hash].base, None);
+        assert_eq!(index.entries[&fourth_hash].base, Some(first_hash.clone()));
+        assert_eq!(
+            read_object(other_dest, &fourth_hash).unwrap(),
+            fourth.as_bytes()
+        );
+        assert_eq!(
+            read_object(other_dest, &third_hash).unwrap(),
+            third.as_bytes()
+        );
+        assert!(
+            list_directory(&format!("{}/pack", other_dest), false, false)
+                .unwrap()
+                .iter()
+                .all(|file| !is_temp_file(file))
+        );
+
+        delete_directory(src, true).unwrap();
+        delete_directory(dest, true).unwrap();
+        delete_directory(other_dest, true).unwrap();
+    }
+
+    /// B.1.7 Chunk Management
+
+    #[test]
+    fn test_write_and_read_chunked_object() {
+        let objects_path = "./test_objects_chunked";
+        let file_path = "./src/test_files/test_large_file.bin";
+        let mut state: u64 = 42;
+        let content: Vec<u8> = (0..3 * 1024 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        write_bytes(file_path, &content).unwrap();
+
+        let manifest = write_chunked_object(objects_path, file_path).unwrap();
+        assert_eq!(manifest.hash, hash_content(&content));
+        assert_eq!(manifest.hash, hash_file(file_path).unwrap());
+        assert_eq!(manifest.size, content.len() as u64);
+        assert!(manifest.chunks.len() > 1);
+        assert_eq!(
+            read_object_manifest(objects_path, &manifest.hash),
+            Some(manifest.clone())
+        );
+        assert_eq!(read_object(objects_path, &manifest.hash).unwrap(), content);
+        assert!(verify_object(objects_path, &manifest.hash).is_ok());
+
+        let restored_path = "./src/test_files/test_large_file_restored.bin";
+        write_object_file(
+            objects_path,
+            &manifest.hash,
+            restored_path,
+            REGULAR_FILE_MODE,
+        )
+        .unwrap();
+        assert_eq!(read_bytes(restored_path).unwrap(), content);
+
+        // An edit in the middle only changes the chunks around it
+        let mut edited = content.clone();
+        edited[1536 * 1024..1536 * 1024 + 16].copy_from_slice(b"Edited content!!");
+        write_bytes(file_path, &edited).unwrap();
+        let edited_manifest = write_chunked_object(objects_path, file_path).unwrap();
+        let shared = edited_manifest
+            .chunks
+            .iter()
+            .filter(|chunk| manifest.chunks.contains(chunk))
+            .count();
+        assert!(shared >= manifest.chunks.len() - 2);
+        assert_eq!(
+            read_object(objects_path, &edited_manifest.hash).unwrap(),
+            edited
+        );
+
+        // A missing chunk makes the whole object fail verification
+        delete_file(&get_object_path(objects_path, &manifest.chunks[0])).unwrap();
+        assert!(verify_object(objects_path, &manifest.hash).is_err());
+
+        delete_file(file_path).unwrap();
+        delete_file(restored_path).unwrap();
+        delete_directory(objects_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_read_chunk_manifest() {
+        let manifest_path = "./src/test_files/test_chunk_manifest";
+        let manifest = ChunkManifest {
+            hash: hash_content(b"content"),
+            size: 7,
+            chunks: vec![hash_content(b"content")],
+        };
+
+        write_chunk_manifest(manifest_path, &manifest).unwrap();
+        assert_eq!(read_chunk_manifest(manifest_path), Some(manifest));
+
+        write_file(manifest_path, "Not a manifest").unwrap();
+        assert_eq!(read_chunk_manifest(manifest_path), None);
+        assert_eq!(decode_chunk_manifest(b"DVCSCHUNKS"), None);
+
+        delete_file(manifest_path).unwrap();
+    }
 }
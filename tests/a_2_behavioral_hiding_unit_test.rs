@@ -199,6 +199,42 @@ mod tests {
             command,
             ValidCommand::Commit {
                 msg: "message".to_string(),
+                amend: false,
+                force: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_commit_amend() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "commit".to_string(),
+            "--amend".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Commit {
+                msg: String::new(),
+                amend: true,
+                force: false,
+            }
+        );
+
+        let args = vec![
+            "days_dvcs".to_string(),
+            "commit".to_string(),
+            "--amend".to_string(),
+            "-f".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Commit {
+                msg: String::new(),
+                amend: true,
+                force: true,
             }
         );
     }
@@ -228,7 +264,7 @@ mod tests {
             ValidCommand::Merge {
                 branch_or_revision_from: "branch".to_string(),
                 branch_or_revision_into: String::new(),
-                msg: "N/A".to_string(),
+                msg: String::new(),
             }
         );
     }
@@ -246,7 +282,7 @@ mod tests {
             ValidCommand::Merge {
                 branch_or_revision_from: "feature-branch".to_string(),
                 branch_or_revision_into: String::new(),
-                msg: "N/A".to_string(),
+                msg: String::new(),
             }
         );
     }
@@ -340,6 +376,263 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_unlock() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "unlock".to_string(),
+            "--force".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Unlock {
+                repo: ".".to_string(),
+                force: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_recover() {
+        let args = vec!["days_dvcs".to_string(), "recover".to_string()];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Recover {
+                repo: ".".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_fsck() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "fsck".to_string(),
+            "--remote".to_string(),
+            "--repair".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Fsck {
+                repo: ".".to_string(),
+                remote: true,
+                repair: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_gc() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "gc".to_string(),
+            "--dry-run".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Gc {
+                repo: ".".to_string(),
+                dry_run: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_pack() {
+        let args = vec!["days_dvcs".to_string(), "pack".to_string()];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Pack {
+                repo: ".".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_migrate() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "migrate".to_string(),
+            "repo".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Migrate {
+                repo: "repo".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_revert() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "revert".to_string(),
+            "1234abcd".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Revert {
+                revision: "1234abcd".to_string(),
+                resume: false,
+                abort: false,
+            }
+        );
+
+        let args = vec![
+            "days_dvcs".to_string(),
+            "revert".to_string(),
+            "--abort".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Revert {
+                revision: String::new(),
+                resume: false,
+                abort: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_cherry_pick() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "cherry-pick".to_string(),
+            "1234abcd".to_string(),
+            "5678efgh".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::CherryPick {
+                revisions: vec!["1234abcd".to_string(), "5678efgh".to_string()],
+                resume: false,
+                abort: false,
+            }
+        );
+
+        let args = vec![
+            "days_dvcs".to_string(),
+            "cherry-pick".to_string(),
+            "--continue".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::CherryPick {
+                revisions: Vec::new(),
+                resume: true,
+                abort: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rebase() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "rebase".to_string(),
+            "main".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Rebase {
+                upstream: "main".to_string(),
+                resume: false,
+                skip: false,
+                abort: false,
+            }
+        );
+
+        let args = vec![
+            "days_dvcs".to_string(),
+            "rebase".to_string(),
+            "--skip".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Rebase {
+                upstream: String::new(),
+                resume: false,
+                skip: true,
+                abort: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_squash() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "squash".to_string(),
+            "1234abcd..5678efgh".to_string(),
+            "-m".to_string(),
+            "One change".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Squash {
+                range: "1234abcd..5678efgh".to_string(),
+                last: 0,
+                msg: "One change".to_string(),
+                force: false,
+            }
+        );
+
+        let args = vec![
+            "days_dvcs".to_string(),
+            "squash".to_string(),
+            "--last".to_string(),
+            "3".to_string(),
+            "--force".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Squash {
+                range: String::new(),
+                last: 3,
+                msg: String::new(),
+                force: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let args = vec![
+            "days_dvcs".to_string(),
+            "config".to_string(),
+            "set".to_string(),
+            "user.name".to_string(),
+            "Ada Lovelace".to_string(),
+            "--user".to_string(),
+        ];
+        let command = parse_command(args).unwrap();
+        assert_eq!(
+            command,
+            ValidCommand::Config {
+                action: "set".to_string(),
+                key: "user.name".to_string(),
+                value: "Ada Lovelace".to_string(),
+                user: true,
+                repo: ".".to_string(),
+            }
+        );
+    }
+
     /// B.2.3 Output Formatter: beta_tests OutputFormatter::display() function
     /// Run with `cargo beta_tests -- --nocapture` to make colored output visible
 
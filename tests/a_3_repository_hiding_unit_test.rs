@@ -8,15 +8,44 @@
 
 use days_dvcs::a_1_file_system_hiding::b_1_1_file_interaction::*;
 use days_dvcs::a_1_file_system_hiding::b_1_2_directory_interaction::*;
+use days_dvcs::a_1_file_system_hiding::b_1_3_metadata_management::*;
+use days_dvcs::a_1_file_system_hiding::b_1_4_object_management::*;
+use days_dvcs::a_1_file_system_hiding::b_1_6_pack_management::{
+    find_packed_object, get_pack_path, list_packs, load_pack_index,
+};
+use days_dvcs::a_1_file_system_hiding::b_1_7_chunk_management::read_chunk_manifest;
+use days_dvcs::a_3_repository_hiding::b_3_10_migration_management::*;
+use days_dvcs::a_3_repository_hiding::b_3_11_configuration_management::*;
+use days_dvcs::a_3_repository_hiding::b_3_12_history_management::*;
 use days_dvcs::a_3_repository_hiding::b_3_1_repository_management::*;
 #[allow(unused_imports)]
 use days_dvcs::a_3_repository_hiding::b_3_2_revision_management::*;
 use days_dvcs::a_3_repository_hiding::b_3_3_branch_management::*;
+use days_dvcs::a_3_repository_hiding::b_3_4_synchronization_handler::*;
+use days_dvcs::a_3_repository_hiding::b_3_5_cross_revision_management::*;
+use days_dvcs::a_3_repository_hiding::b_3_6_journal_management::*;
+use days_dvcs::a_3_repository_hiding::b_3_7_integrity_management::*;
+use days_dvcs::a_3_repository_hiding::b_3_8_garbage_collection::*;
+use days_dvcs::a_3_repository_hiding::b_3_9_pack_management::*;
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io;
+    use std::process::Command;
+
+    // Runs days_dvcs inside a repository with extra environment variables. Tests run in parallel
+    // threads of one process, so environment overrides are only ever given to a child process
+    fn run_dvcs(repo_path: &str, args: &[&str], vars: &[(&str, &str)]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_days_dvcs"))
+            .args(args)
+            .current_dir(repo_path)
+            .envs(vars.iter().copied())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
 
     /// B.3.1 Repository Management
 
@@ -170,7 +199,364 @@ mod tests {
         delete_directory("test_repo_save_and_load_repo", true).unwrap();
     }
 
+    #[test]
+    fn test_locked_repository() {
+        let repo_path = "test_locked_repo";
+        init_repository(repo_path, true).unwrap();
+        write_file(&format!("{}/.dvcs/index.lock", repo_path), "999999999").unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        let err = add(repo_path, vec![repo_path.to_string()]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(err.to_string().contains("999999999"));
+
+        unlock_repository(repo_path, false).unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        commit(repo_path, "Add a.txt").unwrap();
+        assert!(!check_file(&format!("{}/.dvcs/index.lock", repo_path)));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
     /// B.3.2 Revision Management
+
+    #[test]
+    fn test_commit_stores_objects() {
+        let repo_path = "test_commit_objects_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "Same content").unwrap();
+        write_file(&format!("{}/b.txt", repo_path), "Same content").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let revision_id = commit(repo_path, "Add files").unwrap();
+
+        let revision_metadata = load_revision_metadata(repo_path, &revision_id).unwrap();
+        assert_eq!(
+            revision_metadata.files["a.txt"],
+            revision_metadata.files["b.txt"]
+        );
+
+        let objects = list_directory(&format!("{}/.dvcs/objects", repo_path), false, true).unwrap();
+        assert_eq!(objects.len(), 1);
+        assert!(cat(repo_path, &revision_id, "a.txt")
+            .unwrap()
+            .contains("Same content"));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_commit_id_is_content_hash() {
+        let repo_path = "test_commit_id_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let revision_id = commit(repo_path, "Add a.txt").unwrap();
+
+        let mut revision_metadata = verify_revision(repo_path, &revision_id).unwrap();
+        assert_eq!(revision_id.len(), 64);
+        assert_eq!(hash_revision(&revision_metadata), revision_id);
+
+        revision_metadata.message = "Rewritten".to_string();
+        assert_ne!(hash_revision(&revision_metadata), revision_id);
+        save_revision_metadata(repo_path, &revision_id, &revision_metadata).unwrap();
+        let err = verify_revision(repo_path, &revision_id).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_commit_inherits_parent_snapshot() {
+        let repo_path = "test_commit_snapshot_repo";
+        init_repository(repo_path, true).unwrap();
+
+        let file_a = format!("{}/a.txt", repo_path);
+        let file_b = format!("{}/b.txt", repo_path);
+        write_file(&file_a, "A").unwrap();
+        add(repo_path, vec![file_a.clone()]).unwrap();
+        commit(repo_path, "Add a.txt").unwrap();
+
+        write_file(&file_b, "B").unwrap();
+        add(repo_path, vec![file_b]).unwrap();
+        let revision_id = commit(repo_path, "Add b.txt").unwrap();
+
+        let revision_metadata = load_revision_metadata(repo_path, &revision_id).unwrap();
+        assert!(revision_metadata.files.contains_key("a.txt"));
+        assert!(revision_metadata.files.contains_key("b.txt"));
+
+        delete_file(&file_a).unwrap();
+        add(repo_path, vec![file_a]).unwrap();
+        let revision_id = commit(repo_path, "Delete a.txt").unwrap();
+
+        let revision_metadata = load_revision_metadata(repo_path, &revision_id).unwrap();
+        assert!(!revision_metadata.files.contains_key("a.txt"));
+        assert!(revision_metadata.files.contains_key("b.txt"));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_commit_and_checkout_file_mode() {
+        let repo_path = "test_file_mode_repo";
+        init_repository(repo_path, true).unwrap();
+
+        let file_path = format!("{}/run.sh", repo_path);
+        write_file(&file_path, "#!/bin/sh\necho hi\n").unwrap();
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        let regular_id = commit(repo_path, "Add script").unwrap();
+
+        set_file_mode(&file_path, EXECUTABLE_FILE_MODE).unwrap();
+        assert!(status(repo_path).unwrap().contains("mode changed"));
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        let executable_id = commit(repo_path, "Make script executable").unwrap();
+        let revision_metadata = load_revision_metadata(repo_path, &executable_id).unwrap();
+        let regular_metadata = load_revision_metadata(repo_path, &regular_id).unwrap();
+        assert!(regular_metadata.modes.is_empty());
+        assert_eq!(
+            revision_metadata.files["run.sh"],
+            regular_metadata.files["run.sh"]
+        );
+        assert_eq!(
+            get_revision_file_mode(&revision_metadata, "run.sh"),
+            EXECUTABLE_FILE_MODE
+        );
+        assert!(diff(repo_path, &regular_id, &executable_id)
+            .unwrap()
+            .contains("old mode 100644\nnew mode 100755"));
+
+        checkout(repo_path, &regular_id).unwrap();
+        assert_eq!(get_file_mode(&file_path).unwrap(), REGULAR_FILE_MODE);
+        checkout(repo_path, "main").unwrap();
+        assert_eq!(get_file_mode(&file_path).unwrap(), EXECUTABLE_FILE_MODE);
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_commit_amend() {
+        let repo_path = "test_amend_repo";
+        init_repository(repo_path, true).unwrap();
+        assert!(commit_amend(repo_path, "Nothing yet", false).is_err());
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let first_id = commit(repo_path, "Add a.txt").unwrap();
+        write_file(&format!("{}/b.txt", repo_path), "B").unwrap();
+        add(repo_path, vec![format!("{}/b.txt", repo_path)]).unwrap();
+        let typo_id = commit(repo_path, "Add b.tx").unwrap();
+
+        // Fixing the message keeps the tree and the parents
+        let fixed_id = commit_amend(repo_path, "Add b.txt", false).unwrap();
+        let typo_revision = load_revision_metadata(repo_path, &typo_id).unwrap();
+        let fixed_revision = load_revision_metadata(repo_path, &fixed_id).unwrap();
+        assert_ne!(fixed_id, typo_id);
+        assert_eq!(fixed_revision.message, "Add b.txt");
+        assert_eq!(fixed_revision.files, typo_revision.files);
+        assert_eq!(fixed_revision.parents, vec![first_id.clone()]);
+
+        // A forgotten file is added to the amended commit, which keeps its message
+        write_file(&format!("{}/c.txt", repo_path), "C").unwrap();
+        add(repo_path, vec![format!("{}/c.txt", repo_path)]).unwrap();
+        let amended_id = commit_amend(repo_path, "", false).unwrap();
+        let amended_revision = load_revision_metadata(repo_path, &amended_id).unwrap();
+        assert_eq!(amended_revision.message, "Add b.txt");
+        assert_eq!(amended_revision.parents, vec![first_id]);
+        assert!(amended_revision.files.contains_key("c.txt"));
+        assert!(load_branch_metadata(repo_path, "main")
+            .unwrap()
+            .staging
+            .is_empty());
+        assert_eq!(
+            load_repo_metadata(repo_path).unwrap().branches["main"],
+            amended_id
+        );
+        assert!(!log(repo_path).unwrap().contains(&fixed_id));
+
+        // A pushed commit is only amended when forced
+        push(repo_path, ".remote", "", false, false).unwrap();
+        let error = commit_amend(repo_path, "Add b.txt and c.txt", false).unwrap_err();
+        assert!(error.to_string().contains("already been pushed"));
+        let forced_id = commit_amend(repo_path, "Add b.txt and c.txt", true).unwrap();
+        assert_eq!(
+            load_branch_metadata(repo_path, "main").unwrap().head_commit,
+            Some(forced_id)
+        );
+        assert!(fsck(repo_path, true, false).is_ok());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_commit_records_author_and_committer() {
+        let repo_path = "test_identity_repo";
+        init_repository(repo_path, true).unwrap();
+        write_file(
+            &get_repo_config_path(repo_path),
+            r#"{"user.name": "Ada Lovelace", "user.email": "ada@example.com"}"#,
+        )
+        .unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let first_id = commit(repo_path, "Add a.txt").unwrap();
+        let revision_metadata = load_revision_metadata(repo_path, &first_id).unwrap();
+        let ada = Signature {
+            name: "Ada Lovelace".to_string(),
+            email: "ada@example.com".to_string(),
+        };
+        assert_eq!(revision_metadata.author, Some(ada.clone()));
+        assert_eq!(revision_metadata.committer, Some(ada.clone()));
+        assert!(log(repo_path)
+            .unwrap()
+            .contains("Author: Ada Lovelace <ada@example.com>"));
+
+        // Environment variables take precedence over the configuration
+        write_file(&format!("{}/b.txt", repo_path), "B").unwrap();
+        add(repo_path, vec![format!("{}/b.txt", repo_path)]).unwrap();
+        run_dvcs(
+            repo_path,
+            &["commit", "Add b.txt"],
+            &[("DVCS_COMMITTER_NAME", "Charles Babbage")],
+        );
+        let second_id = load_branch_metadata(repo_path, "main")
+            .unwrap()
+            .head_commit
+            .unwrap();
+
+        let revision_metadata = load_revision_metadata(repo_path, &second_id).unwrap();
+        assert_eq!(revision_metadata.author, Some(ada));
+        assert_eq!(
+            revision_metadata.committer.unwrap().to_string(),
+            "Charles Babbage <ada@example.com>"
+        );
+        assert!(heads(repo_path)
+            .unwrap()
+            .contains("Committer: Charles Babbage <ada@example.com>"));
+
+        // Identities are part of the revision id, so they survive a push unchanged
+        push(repo_path, ".remote", "", false, false).unwrap();
+        let remote_path = format!("{}/.remote", repo_path);
+        assert_eq!(
+            load_revision_metadata(&remote_path, &second_id).unwrap(),
+            load_revision_metadata(repo_path, &second_id).unwrap()
+        );
+        assert!(verify_revision(&remote_path, &second_id).is_ok());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_commit_checkout_and_push_large_file() {
+        let repo_path = "test_large_file_repo";
+        init_repository(repo_path, true).unwrap();
+        set_config_value(repo_path, "core.large_file_threshold", "1048576", false).unwrap();
+
+        let mut state: u64 = 7;
+        let content: Vec<u8> = (0..3 * 1024 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let file_path = format!("{}/asset.bin", repo_path);
+        write_bytes(&file_path, &content).unwrap();
+        add(repo_path, vec![file_path.clone()]).unwrap();
+
+        // Only the manifest of a large file is copied into the staging area
+        let staging_path = format!("{}/.dvcs/origin/main/staging/asset.bin", repo_path);
+        let manifest = read_chunk_manifest(&staging_path).unwrap();
+        assert_eq!(manifest.hash, hash_content(&content));
+        assert!(!status(repo_path).unwrap().contains("modified"));
+        let first_id = commit(repo_path, "Add asset").unwrap();
+        push(repo_path, ".remote", "", false, false).unwrap();
+
+        let mut edited = content.clone();
+        edited[2 * 1024 * 1024..2 * 1024 * 1024 + 5].copy_from_slice(b"Edit!");
+        write_bytes(&file_path, &edited).unwrap();
+        assert!(status(repo_path).unwrap().contains("modified"));
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        commit(repo_path, "Edit asset").unwrap();
+        push(repo_path, ".remote", "", false, false).unwrap();
+
+        // The second push only transfers the new manifest and the chunks the remote lacks
+        let remote_objects_path = format!("{}/.remote/.dvcs/objects", repo_path);
+        let edited_hash = hash_content(&edited);
+        let pack = find_packed_object(&remote_objects_path, &edited_hash).unwrap();
+        let index = load_pack_index(&remote_objects_path, &pack).unwrap();
+        assert!(index.entries.len() < manifest.chunks.len());
+        assert_eq!(
+            read_object(&remote_objects_path, &edited_hash).unwrap(),
+            edited
+        );
+
+        checkout(repo_path, &first_id).unwrap();
+        assert_eq!(read_bytes(&file_path).unwrap(), content);
+        checkout(repo_path, "main").unwrap();
+        assert_eq!(read_bytes(&file_path).unwrap(), edited);
+
+        // Chunks are referenced through their manifests, so collecting garbage keeps them
+        gc(repo_path, false).unwrap();
+        assert!(fsck(repo_path, true, false).is_ok());
+        assert!(verify_object(&format!("{}/.dvcs/objects", repo_path), &edited_hash).is_ok());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_commit_and_checkout_symlink() {
+        let repo_path = "test_symlink_repo";
+        init_repository(repo_path, true).unwrap();
+
+        let link_path = format!("{}/link", repo_path);
+        write_file(&format!("{}/target.txt", repo_path), "Target").unwrap();
+        write_link(&link_path, "target.txt").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let revision_id = commit(repo_path, "Add link").unwrap();
+
+        let revision_metadata = load_revision_metadata(repo_path, &revision_id).unwrap();
+        assert_eq!(
+            get_revision_file_mode(&revision_metadata, "link"),
+            SYMLINK_MODE
+        );
+        assert_eq!(revision_metadata.files["link"], hash_content(b"target.txt"));
+
+        write_link(&link_path, "elsewhere.txt").unwrap();
+        assert!(status(repo_path).unwrap().contains("modified"));
+
+        delete_file(&link_path).unwrap();
+        checkout(repo_path, "main").unwrap();
+        assert!(check_link(&link_path));
+        assert_eq!(read_link(&link_path).unwrap(), "target.txt");
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_commit_and_checkout_binary_file() {
+        let repo_path = "test_binary_repo";
+        init_repository(repo_path, true).unwrap();
+
+        let file_path = format!("{}/image.bin", repo_path);
+        let content = vec![0x89, b'P', b'N', b'G', 0x00, 0xff, 0xfe];
+        write_bytes(&file_path, &content).unwrap();
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        let revision_id = commit(repo_path, "Add binary file").unwrap();
+
+        assert!(cat(repo_path, &revision_id, "image.bin")
+            .unwrap()
+            .contains("Binary file 'image.bin' (7 bytes)"));
+
+        delete_file(&file_path).unwrap();
+        checkout(repo_path, "main").unwrap();
+        assert_eq!(read_bytes(&file_path).unwrap(), content);
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
     /// B.3.3 Branch Management
 
     #[test]
@@ -186,6 +572,26 @@ mod tests {
         delete_directory(repo_path, true).unwrap();
     }
 
+    #[test]
+    fn test_init_branch_points_at_head() {
+        let repo_path = "test_branch_head_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let revision_id = commit(repo_path, "Add a.txt").unwrap();
+        init_branch(repo_path, "feature", false).unwrap();
+
+        let branch_metadata = load_branch_metadata(repo_path, "feature").unwrap();
+        assert_eq!(branch_metadata.head_commit, Some(revision_id.clone()));
+        assert_eq!(
+            load_repo_metadata(repo_path).unwrap().branches["feature"],
+            revision_id
+        );
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
     #[test]
     fn test_heads() {
         let repo_path = "test_heads_repo";
@@ -209,6 +615,45 @@ mod tests {
         delete_directory(repo_path, true).unwrap();
     }
 
+    #[test]
+    fn test_status_uses_stat_cache() {
+        let repo_path = "test_status_cache_repo";
+        init_repository(repo_path, true).unwrap();
+        let file_path = format!("{}/file.txt", repo_path);
+        write_file(&file_path, "Test content").unwrap();
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        commit(repo_path, "Add file").unwrap();
+
+        let stat_cache_path = get_stat_cache_path(&get_absolute_path(repo_path, "").unwrap());
+        status(repo_path).unwrap();
+        let stat_cache = load_stat_cache(&stat_cache_path);
+        assert_eq!(
+            stat_cache.entries["file.txt"].hash,
+            hash_content(b"Test content")
+        );
+        assert!(!stat_cache.entries.keys().any(|key| key.contains("staging")));
+
+        write_file(&file_path, "Changed content").unwrap();
+        assert!(status(repo_path).unwrap().contains("modified"));
+        assert_eq!(
+            load_stat_cache(&stat_cache_path).entries["file.txt"].hash,
+            hash_content(b"Changed content")
+        );
+
+        // While another process holds the lock, status still reports but leaves the cache alone
+        let lock_path = format!("{}/.dvcs/index.lock", repo_path);
+        write_file(&lock_path, "999999999").unwrap();
+        write_file(&file_path, "Locked content").unwrap();
+        assert!(status(repo_path).unwrap().contains("modified"));
+        assert_eq!(
+            load_stat_cache(&stat_cache_path).entries["file.txt"].hash,
+            hash_content(b"Changed content")
+        );
+        delete_file(&lock_path).unwrap();
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
     #[test]
     fn test_add() {
         let repo_path = "test_add_repo";
@@ -268,4 +713,1068 @@ mod tests {
 
         delete_directory(repo_path, true).unwrap();
     }
+
+    /// B.3.4 Synchronization Handler
+
+    #[test]
+    fn test_push_fast_forward_and_diverged() {
+        let repo_path = "test_push_graph_repo";
+        let remote_path = format!("{}/.remote", repo_path);
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let first_id = commit(repo_path, "Add a.txt").unwrap();
+        push(repo_path, ".remote", "", false, false).unwrap();
+
+        write_file(&format!("{}/b.txt", repo_path), "B").unwrap();
+        add(repo_path, vec![format!("{}/b.txt", repo_path)]).unwrap();
+        let second_id = commit(repo_path, "Add b.txt").unwrap();
+        push(repo_path, ".remote", "", false, false).unwrap();
+
+        assert!(is_revision(&remote_path, &first_id).is_ok());
+        assert_eq!(
+            load_repo_metadata(&remote_path).unwrap().branches["main"],
+            second_id
+        );
+
+        // Commit on both sides so the histories diverge
+        write_file(&format!("{}/remote.txt", remote_path), "Remote").unwrap();
+        add(&remote_path, vec![format!("{}/remote.txt", remote_path)]).unwrap();
+        commit(&remote_path, "Remote change").unwrap();
+
+        write_file(&format!("{}/c.txt", repo_path), "C").unwrap();
+        add(repo_path, vec![format!("{}/c.txt", repo_path)]).unwrap();
+        let third_id = commit(repo_path, "Add c.txt").unwrap();
+
+        assert!(push(repo_path, ".remote", "", false, false).is_err());
+        push(repo_path, ".remote", "", false, true).unwrap();
+        assert_eq!(
+            load_repo_metadata(&remote_path).unwrap().branches["main"],
+            third_id
+        );
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_push_and_pull_branch_without_commits() {
+        let repo_path = "test_sync_empty_branch_repo";
+        let remote_path = format!("{}/.remote", repo_path);
+        init_repository(repo_path, true).unwrap();
+
+        // A branch created by push or pull is recorded even when there is nothing to copy
+        init_branch(repo_path, "local_empty", false).unwrap();
+        assert!(push(repo_path, ".remote", "local_empty", false, false)
+            .unwrap()
+            .contains("No commits in branch 'local_empty' yet"));
+        assert!(load_repo_metadata(&remote_path)
+            .unwrap()
+            .branches
+            .contains_key("local_empty"));
+
+        init_branch(&remote_path, "remote_empty", false).unwrap();
+        pull(repo_path, ".remote", "remote_empty", false, false).unwrap();
+        assert!(load_repo_metadata(repo_path)
+            .unwrap()
+            .branches
+            .contains_key("remote_empty"));
+        assert!(fsck(repo_path, true, false).is_ok());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    /// B.3.5 Cross Revision Management
+
+    #[test]
+    fn test_merge_records_both_parents() {
+        let repo_path = "test_merge_graph_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let base_id = commit(repo_path, "Add a.txt").unwrap();
+
+        init_branch(repo_path, "feature", false).unwrap();
+        write_file(&format!("{}/b.txt", repo_path), "B").unwrap();
+        add(repo_path, vec![format!("{}/b.txt", repo_path)]).unwrap();
+        let feature_id = commit(repo_path, "Add b.txt").unwrap();
+
+        checkout(repo_path, "main").unwrap();
+        write_file(&format!("{}/c.txt", repo_path), "C").unwrap();
+        add(repo_path, vec![format!("{}/c.txt", repo_path)]).unwrap();
+        let main_id = commit(repo_path, "Add c.txt").unwrap();
+
+        merge(repo_path, "main", "feature", "Merge feature").unwrap();
+
+        let merge_id = load_repo_metadata(repo_path).unwrap().branches["main"].clone();
+        let merge_metadata = load_revision_metadata(repo_path, &merge_id).unwrap();
+        assert_eq!(merge_metadata.parents, vec![main_id, feature_id.clone()]);
+        assert!(merge_metadata.files.contains_key("b.txt"));
+        assert!(is_ancestor(repo_path, &base_id, &merge_id).unwrap());
+        assert!(is_ancestor(repo_path, &feature_id, &merge_id).unwrap());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    /// B.3.6 Journal Management
+
+    #[test]
+    fn test_interrupted_operation_rolls_back() {
+        let repo_path = "test_journal_rollback_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let revision_id = commit(repo_path, "Add a.txt").unwrap();
+
+        // Simulate a process that died after touching the branch but before writing a new revision
+        let journal = begin_operation(repo_path, "merge", &["main".to_string()]).unwrap();
+        let mut branch_metadata = load_branch_metadata(repo_path, "main").unwrap();
+        branch_metadata.staging.push("a.txt".to_string());
+        save_branch_metadata(repo_path, "main", &branch_metadata).unwrap();
+        std::mem::forget(journal);
+
+        // The next mutating operation recovers before doing its own work
+        init_branch(repo_path, "feature", false).unwrap();
+        let branch_metadata = load_branch_metadata(repo_path, "main").unwrap();
+        assert!(branch_metadata.staging.is_empty());
+        assert_eq!(branch_metadata.head_commit, Some(revision_id));
+        assert!(!check_file(&format!("{}/.dvcs/journal.json", repo_path)));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_commit_rolls_forward() {
+        let repo_path = "test_journal_rollforward_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let first_id = commit(repo_path, "Add a.txt").unwrap();
+
+        // Simulate a commit that wrote its revision and branch head but not the repository refs
+        let journal = begin_operation(repo_path, "commit", &["main".to_string()]).unwrap();
+        let mut revision_metadata = load_revision_metadata(repo_path, &first_id).unwrap();
+        revision_metadata.parents = vec![first_id.clone()];
+        revision_metadata.message = "Second".to_string();
+        let second_id = hash_revision(&revision_metadata);
+        revision_metadata.id = second_id.clone();
+        save_revision_metadata(repo_path, &second_id, &revision_metadata).unwrap();
+        journal.record_target("main", &second_id, true).unwrap();
+
+        let mut branch_metadata = load_branch_metadata(repo_path, "main").unwrap();
+        branch_metadata.head_commit = Some(second_id.clone());
+        save_branch_metadata(repo_path, "main", &branch_metadata).unwrap();
+        std::mem::forget(journal);
+
+        assert!(recover(repo_path).unwrap().contains("Rolled forward"));
+        assert_eq!(
+            load_repo_metadata(repo_path).unwrap().branches["main"],
+            second_id
+        );
+        assert!(recover(repo_path)
+            .unwrap()
+            .contains("No interrupted operation"));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    /// B.3.7 Integrity Management
+
+    #[test]
+    fn test_fsck_reports_corruption() {
+        let repo_path = "test_fsck_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let revision_id = commit(repo_path, "Add a.txt").unwrap();
+        assert!(fsck(repo_path, true, false).is_ok());
+
+        // Tamper with the stored content and point the branch somewhere else
+        let revision_metadata = load_revision_metadata(repo_path, &revision_id).unwrap();
+        let object_path = format!(
+            "{}/.dvcs/objects/{}/{}",
+            repo_path,
+            &revision_metadata.files["a.txt"][..2],
+            &revision_metadata.files["a.txt"][2..]
+        );
+        write_file(&object_path, "Tampered").unwrap();
+        let mut branch_metadata = load_branch_metadata(repo_path, "main").unwrap();
+        branch_metadata.head_commit = None;
+        save_branch_metadata(repo_path, "main", &branch_metadata).unwrap();
+
+        let report = fsck(repo_path, false, false).unwrap_err().to_string();
+        assert!(report.contains(&format!(
+            "Object '{}' is corrupt",
+            get_absolute_path(&object_path, "").unwrap()
+        )));
+        assert!(report.contains("Branch 'main' is at"));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_fsck_repair() {
+        let repo_path = "test_fsck_repair_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let first_id = commit(repo_path, "Add a.txt").unwrap();
+        write_file(&format!("{}/a.txt", repo_path), "B").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let second_id = commit(repo_path, "Change a.txt").unwrap();
+
+        // Leave the repository refs behind the branch and add an unreadable revision
+        let mut repo_metadata = load_repo_metadata(repo_path).unwrap();
+        repo_metadata
+            .branches
+            .insert("main".to_string(), first_id.clone());
+        save_repo_metadata(repo_path, &repo_metadata).unwrap();
+        create_directory(&format!("{}/.dvcs/commits/broken/.metadata", repo_path)).unwrap();
+        write_file(
+            &format!("{}/.dvcs/commits/broken/.metadata/metadata.json", repo_path),
+            "{",
+        )
+        .unwrap();
+        assert!(fsck(repo_path, false, false).is_err());
+
+        let report = fsck(repo_path, false, true).unwrap();
+        assert!(report.contains("Quarantined unreadable revision"));
+        assert!(report.contains(&format!("Set branch 'main' to '{}'", second_id)));
+        assert!(check_directory(&format!(
+            "{}/.dvcs/quarantine/commits/broken",
+            repo_path
+        )));
+        assert_eq!(
+            load_repo_metadata(repo_path).unwrap().branches["main"],
+            second_id
+        );
+        assert!(fsck(repo_path, false, false).is_ok());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_fsck_repair_missing_metadata() {
+        let repo_path = "test_fsck_missing_repo";
+        let remote_path = format!("{}/.remote", repo_path);
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let revision_id = commit(repo_path, "Add a.txt").unwrap();
+
+        // Without its metadata files the directory is no longer recognized as a repository
+        delete_file(&format!("{}/.dvcs/.metadata/metadata.json", repo_path)).unwrap();
+        delete_file(&format!(
+            "{}/.dvcs/origin/main/.metadata/metadata.json",
+            repo_path
+        ))
+        .unwrap();
+        assert!(is_repository(repo_path).is_err());
+        assert!(fsck(repo_path, false, false)
+            .unwrap_err()
+            .to_string()
+            .contains("Repository metadata"));
+
+        let report = fsck(repo_path, false, true).unwrap();
+        assert!(report.contains("Rebuilt repository metadata"));
+        assert!(report.contains("Rebuilt branch metadata"));
+        assert_eq!(
+            load_repo_metadata(repo_path).unwrap().branches["main"],
+            revision_id
+        );
+        assert!(is_repository(repo_path).is_ok());
+
+        // Problems are reported under the name of the repository they were found in
+        assert!(fsck(repo_path, true, false)
+            .unwrap()
+            .contains("or its remote '.remote'"));
+        write_file(
+            &format!("{}/.dvcs/.metadata/metadata.json", remote_path),
+            "{",
+        )
+        .unwrap();
+        let report = fsck(repo_path, true, false).unwrap_err().to_string();
+        assert!(report.contains("problem(s) in repository '.remote'"));
+        assert!(!report.contains(&format!("repository '{}'", repo_path)));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_fsck_repair_corrupt_pack() {
+        let repo_path = "test_fsck_pack_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "Alpha content").unwrap();
+        write_file(&format!("{}/b.txt", repo_path), "Beta content").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let revision_id = commit(repo_path, "Add a.txt and b.txt").unwrap();
+        pack(repo_path).unwrap();
+
+        // Overwrite the entry of a.txt inside the pack
+        let objects_path = format!("{}/.dvcs/objects", repo_path);
+        let pack_name = list_packs(&objects_path).unwrap()[0].clone();
+        let pack_path = get_pack_path(&objects_path, &pack_name);
+        let files = load_revision_metadata(repo_path, &revision_id)
+            .unwrap()
+            .files;
+        let entry =
+            load_pack_index(&objects_path, &pack_name).unwrap().entries[&files["a.txt"]].clone();
+        let mut bytes = read_bytes(&pack_path).unwrap();
+        bytes[entry.offset as usize..(entry.offset + entry.length) as usize].fill(0);
+        write_bytes(&pack_path, &bytes).unwrap();
+        assert!(fsck(repo_path, false, false).is_err());
+
+        // The pack is set aside and its intact entries become loose objects again
+        let report = fsck(repo_path, false, true).unwrap_err().to_string();
+        assert!(report.contains("Quarantined corrupt pack"));
+        assert!(report.contains("keeping 1 intact object(s)"));
+        assert!(report.contains("is missing object"));
+        assert!(list_packs(&objects_path).unwrap().is_empty());
+        assert!(check_file(&format!(
+            "{}/.dvcs/quarantine/packs/{}.pack",
+            repo_path, pack_name
+        )));
+        assert!(check_file(&get_object_path(&objects_path, &files["b.txt"])));
+        assert!(cat(repo_path, &revision_id, "b.txt")
+            .unwrap()
+            .contains("Beta content"));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    /// B.3.8 Garbage Collection
+
+    #[test]
+    fn test_gc_removes_unreachable_revisions() {
+        let repo_path = "test_gc_repo";
+        init_repository(repo_path, true).unwrap();
+
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let first_id = commit(repo_path, "Add a.txt").unwrap();
+        write_file(&format!("{}/a.txt", repo_path), "Abandoned").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let abandoned_id = commit(repo_path, "Change a.txt").unwrap();
+
+        // Move the branch back so the second revision and its object become unreachable
+        let mut branch_metadata = load_branch_metadata(repo_path, "main").unwrap();
+        branch_metadata.head_commit = Some(first_id.clone());
+        save_branch_metadata(repo_path, "main", &branch_metadata).unwrap();
+        let mut repo_metadata = load_repo_metadata(repo_path).unwrap();
+        repo_metadata
+            .branches
+            .insert("main".to_string(), first_id.clone());
+        save_repo_metadata(repo_path, &repo_metadata).unwrap();
+        create_directory(&format!(
+            "{}/.dvcs/origin/main/temp_staging_before_merge",
+            repo_path
+        ))
+        .unwrap();
+
+        let report = gc(repo_path, true).unwrap();
+        assert!(report.starts_with("Would remove 1 unreachable revision(s), 1 orphaned object(s)"));
+        assert!(is_revision(repo_path, &abandoned_id).is_ok());
+
+        gc(repo_path, false).unwrap();
+        assert!(is_revision(repo_path, &abandoned_id).is_err());
+        assert!(is_revision(repo_path, &first_id).is_ok());
+        assert!(cat(repo_path, &first_id, "a.txt").unwrap().contains('A'));
+        assert!(!check_directory(&format!(
+            "{}/.dvcs/origin/main/temp_staging_before_merge",
+            repo_path
+        )));
+        assert!(gc(repo_path, false)
+            .unwrap()
+            .starts_with("Nothing to collect"));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    /// B.3.9 Pack Management
+
+    #[test]
+    fn test_pack_and_push_packs() {
+        let repo_path = "test_pack_repo";
+        init_repository(repo_path, true).unwrap();
+        let mut content = String::new();
+        let mut revision_ids = Vec::new();
+
+        for version in 0..5 {
+            content.push_str(&"Another line of the growing log\n".repeat(20));
+            write_file(&format!("{}/log.txt", repo_path), &content).unwrap();
+            add(repo_path, vec![repo_path.to_string()]).unwrap();
+            revision_ids.push(commit(repo_path, &format!("Version {}", version)).unwrap());
+        }
+
+        assert!(pack(repo_path)
+            .unwrap()
+            .starts_with("Packed 5 object(s), 4 as deltas"));
+        let first_hash = &load_revision_metadata(repo_path, &revision_ids[0])
+            .unwrap()
+            .files["log.txt"];
+        assert!(!check_file(&format!(
+            "{}/.dvcs/objects/{}/{}",
+            repo_path,
+            &first_hash[..2],
+            &first_hash[2..]
+        )));
+        assert!(cat(repo_path, &revision_ids[2], "log.txt").is_ok());
+        assert!(fsck(repo_path, false, false).is_ok());
+
+        // Pushing transfers the history as a pack rather than as individual objects
+        push(repo_path, ".remote", "", false, false).unwrap();
+        let remote_path = format!("{}/.remote", repo_path);
+        assert_eq!(
+            list_directory(&format!("{}/.dvcs/objects/pack", remote_path), false, false)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(fsck(repo_path, true, false).is_ok());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    /// B.3.10 Migration Management
+
+    #[test]
+    fn test_migrate_legacy_repository() {
+        let repo_path = "test_migrate_repo";
+        create_directory(repo_path).unwrap();
+        copy_directory("beta_tests/.dvcs", &format!("{}/.dvcs", repo_path)).unwrap();
+
+        let error = is_repository(repo_path).unwrap_err();
+        assert!(error.to_string().contains("Run 'migrate'"));
+
+        assert!(migrate(repo_path)
+            .unwrap()
+            .contains("from format version 1 to 2"));
+        assert!(is_repository(repo_path).is_ok());
+        assert_eq!(
+            load_repo_metadata(repo_path).unwrap().version,
+            FORMAT_VERSION
+        );
+        assert!(!check_directory(&format!(
+            "{}/.dvcs/origin/main/commits",
+            repo_path
+        )));
+        assert!(fsck(repo_path, false, false).is_ok());
+
+        // Files committed in earlier revisions are carried into the later snapshots
+        let head = load_branch_metadata(repo_path, "beta")
+            .unwrap()
+            .head_commit
+            .unwrap();
+        let files = load_revision_metadata(repo_path, &head).unwrap().files;
+        assert!(files.contains_key("README.md"));
+        assert!(files.contains_key("acceptance_tests/test_7.txt"));
+
+        assert!(migrate(repo_path).unwrap().contains("already uses"));
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_long_history() {
+        let repo_path = "test_migrate_long_repo";
+        let dvcs_path = format!("{}/.dvcs", repo_path);
+        let revisions = 1000;
+
+        // A version 1 branch that only names its head, so the whole chain is walked from there
+        for index in 0..revisions {
+            let metadata_path = format!(
+                "{}/origin/main/commits/legacy-{}/.metadata",
+                dvcs_path, index
+            );
+            let parents = if index == 0 {
+                String::new()
+            } else {
+                format!("\"legacy-{}\"", index - 1)
+            };
+            create_directory(&metadata_path).unwrap();
+            write_file(
+                &format!("{}/metadata.json", metadata_path),
+                &format!(
+                    r#"{{"id": "legacy-{}", "files": {{}}, "parents": [{}], "message": "Revision {}", "timestamp": {{"secs_since_epoch": {}, "nanos_since_epoch": 0}}}}"#,
+                    index, parents, index, 1733642862 + index
+                ),
+            )
+            .unwrap();
+        }
+
+        let head = format!("legacy-{}", revisions - 1);
+        create_directory(&format!("{}/origin/main/.metadata", dvcs_path)).unwrap();
+        write_file(
+            &format!("{}/origin/main/.metadata/metadata.json", dvcs_path),
+            &format!(r#"{{"name": "main", "head_commit": "{}"}}"#, head),
+        )
+        .unwrap();
+        create_directory(&format!("{}/.metadata", dvcs_path)).unwrap();
+        write_file(
+            &format!("{}/.metadata/metadata.json", dvcs_path),
+            &format!(r#"{{"head": "main", "branches": {{"main": "{}"}}}}"#, head),
+        )
+        .unwrap();
+        write_file(
+            &format!("{}/HEAD", dvcs_path),
+            &format!("commit: {}\nref: origin/main", head),
+        )
+        .unwrap();
+
+        assert!(migrate(repo_path)
+            .unwrap()
+            .contains(&format!("{} revision(s)", revisions)));
+        let head_id = load_branch_metadata(repo_path, "main")
+            .unwrap()
+            .head_commit
+            .unwrap();
+        assert_eq!(
+            load_revision_metadata(repo_path, &head_id).unwrap().message,
+            format!("Revision {}", revisions - 1)
+        );
+        assert_eq!(get_ancestors(repo_path, &head_id).unwrap().len(), revisions);
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_refuse_newer_format_version() {
+        let repo_path = "test_format_version_repo";
+        init_repository(repo_path, false).unwrap();
+        let mut repo_metadata = load_repo_metadata(repo_path).unwrap();
+        assert_eq!(repo_metadata.version, FORMAT_VERSION);
+
+        repo_metadata.version = FORMAT_VERSION + 1;
+        save_repo_metadata(repo_path, &repo_metadata).unwrap();
+        let error = is_repository(repo_path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+        assert!(error.to_string().contains("Upgrade days_dvcs"));
+        assert!(migrate(repo_path).is_err());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    /// B.3.11 Configuration Management
+
+    #[test]
+    fn test_config_precedence_and_defaults() {
+        let repo_path = "test_config_repo";
+        let remote_path = "test_config_remote_repo";
+        init_repository(repo_path, false).unwrap();
+        init_repository(remote_path, false).unwrap();
+
+        // Without any configuration, the built-in defaults apply
+        assert_eq!(get_context_lines(repo_path).unwrap(), 3);
+        assert_eq!(
+            config(repo_path, "get", "commit.default_message", "", false).unwrap(),
+            "N/A"
+        );
+
+        // The repository configuration overrides the user's, and the environment overrides both
+        let home_path = get_absolute_path(repo_path, "").unwrap();
+        let home = [("HOME", home_path.as_str())];
+        let variable = get_config_variable("user.email");
+        let get = ["config", "get", "user.email"];
+        assert!(run_dvcs(repo_path, &get, &home).contains("is not set"));
+        run_dvcs(
+            repo_path,
+            &["config", "set", "user.email", "home@example.com", "--user"],
+            &home,
+        );
+        assert!(run_dvcs(repo_path, &get, &home).contains("home@example.com"));
+        run_dvcs(
+            repo_path,
+            &["config", "set", "user.email", "repo@example.com"],
+            &home,
+        );
+        assert!(run_dvcs(repo_path, &get, &home).contains("repo@example.com"));
+        assert!(run_dvcs(
+            repo_path,
+            &get,
+            &[home[0], (variable.as_str(), "env@example.com")]
+        )
+        .contains("env@example.com"));
+        run_dvcs(repo_path, &["config", "unset", "user.email"], &home);
+        assert!(run_dvcs(repo_path, &["config", "list"], &home)
+            .contains("user.email=home@example.com (user)"));
+        run_dvcs(
+            repo_path,
+            &["config", "unset", "user.email", "--user"],
+            &home,
+        );
+        assert!(!check_file(&format!("{}/{}", home_path, USER_CONFIG_FILE)));
+
+        // Values are checked when they are set
+        assert!(config(repo_path, "set", "diff.context_lines", "many", false).is_err());
+        assert!(config(repo_path, "set", "context_lines", "1", false).is_err());
+        assert!(config(repo_path, "set", "diff.context_line", "1", false)
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown configuration key"));
+        assert!(config(repo_path, "set", "diff.context_lines", "", false).is_err());
+        assert!(config(repo_path, "set", "core.large_file_threshold", "0", false).is_err());
+
+        // Configured values replace the hard-coded ones
+        config(repo_path, "set", "commit.default_message", "WIP", false).unwrap();
+        config(repo_path, "set", "core.ignore", "build", false).unwrap();
+        config(
+            repo_path,
+            "set",
+            "remote.path",
+            "../test_config_remote_repo",
+            false,
+        )
+        .unwrap();
+        create_directory(&format!("{}/build", repo_path)).unwrap();
+        write_file(&format!("{}/build/out.txt", repo_path), "out").unwrap();
+        write_file(&format!("{}/a.txt", repo_path), "A").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        assert_eq!(
+            load_branch_metadata(repo_path, "main").unwrap().staging,
+            vec!["a.txt".to_string()]
+        );
+
+        let revision_id = commit(repo_path, "").unwrap();
+        assert_eq!(
+            load_revision_metadata(repo_path, &revision_id)
+                .unwrap()
+                .message,
+            "WIP"
+        );
+        push(repo_path, ".remote", "", false, false).unwrap();
+        assert_eq!(
+            load_branch_metadata(remote_path, "main")
+                .unwrap()
+                .head_commit,
+            Some(revision_id)
+        );
+
+        // The remote must be a repository of its own, not a directory inside the local one
+        config(repo_path, "set", "remote.path", "build", false).unwrap();
+        assert!(get_remote_path(repo_path)
+            .unwrap_err()
+            .to_string()
+            .contains("no '.dvcs' directory"));
+        config(repo_path, "set", "remote.path", ".", false).unwrap();
+        assert!(get_remote_path(repo_path)
+            .unwrap_err()
+            .to_string()
+            .contains("the local repository itself"));
+        assert!(push(repo_path, ".remote", "", false, false).is_err());
+
+        delete_directory(repo_path, true).unwrap();
+        delete_directory(remote_path, true).unwrap();
+    }
+
+    /// B.3.12 History Management
+
+    #[test]
+    fn test_revert() {
+        let repo_path = "test_revert_repo";
+        let file_path = format!("{}/a.txt", repo_path);
+        init_repository(repo_path, false).unwrap();
+
+        write_file(&file_path, "one\ntwo\nthree\n").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        commit(repo_path, "Add a.txt").unwrap();
+        write_file(&file_path, "one\nTWO\nthree\n").unwrap();
+        write_file(&format!("{}/c.txt", repo_path), "C").unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let bad_id = commit(repo_path, "Shout two").unwrap();
+        write_file(&format!("{}/d.txt", repo_path), "D").unwrap();
+        add(repo_path, vec![format!("{}/d.txt", repo_path)]).unwrap();
+        commit(repo_path, "Add d.txt").unwrap();
+
+        // The inverse change is committed on top, leaving later changes alone
+        let report = revert(repo_path, &bad_id).unwrap();
+        assert!(report.contains("File removed: 'c.txt'"));
+        let head_id = load_branch_metadata(repo_path, "main")
+            .unwrap()
+            .head_commit
+            .unwrap();
+        let head = load_revision_metadata(repo_path, &head_id).unwrap();
+        assert!(head.message.contains(&bad_id));
+        assert!(head.files.contains_key("d.txt"));
+        assert!(!head.files.contains_key("c.txt"));
+        assert_eq!(read_file(&file_path).unwrap(), "one\ntwo\nthree\n");
+        assert!(!check_file(&format!("{}/c.txt", repo_path)));
+        assert!(revert(repo_path, &bad_id).is_err());
+
+        // Local changes to the files a revert touches are not overwritten
+        write_file(&file_path, "one\n2\nthree\n").unwrap();
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        let conflicting_id = commit(repo_path, "Number two").unwrap();
+        write_file(&file_path, "one\nII\nthree\n").unwrap();
+        assert!(revert(repo_path, &conflicting_id)
+            .unwrap_err()
+            .to_string()
+            .contains("Local changes"));
+
+        // A revert that conflicts with a later change is reported and not committed
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        let later_id = commit(repo_path, "Roman two").unwrap();
+        let report = revert(repo_path, &conflicting_id).unwrap();
+        assert!(report.contains("Conflict in file: 'a.txt'"));
+        assert!(report.contains("revert --continue"));
+        assert_eq!(
+            load_branch_metadata(repo_path, "main").unwrap().head_commit,
+            Some(later_id.clone())
+        );
+        assert!(read_file(&file_path)
+            .unwrap()
+            .contains("<<<<<<< main\nII\n=======\ntwo"));
+        assert!(revert_continue(repo_path)
+            .unwrap_err()
+            .to_string()
+            .contains("not resolved"));
+
+        // It can be aborted, which restores the branch
+        assert!(revert_abort(repo_path).is_ok());
+        assert_eq!(
+            load_branch_metadata(repo_path, "main").unwrap().head_commit,
+            Some(later_id.clone())
+        );
+        assert_eq!(read_file(&file_path).unwrap(), "one\nII\nthree\n");
+        assert!(revert_abort(repo_path).is_err());
+
+        // Or, once resolved and added, committed with the message naming the reverted revision
+        revert(repo_path, &conflicting_id).unwrap();
+        write_file(&file_path, "one\ntwo\nthree\n").unwrap();
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        assert!(revert_continue(repo_path)
+            .unwrap()
+            .contains("Finished revert"));
+        let head_id = load_branch_metadata(repo_path, "main")
+            .unwrap()
+            .head_commit
+            .unwrap();
+        let head = load_revision_metadata(repo_path, &head_id).unwrap();
+        assert!(head.message.contains(&conflicting_id));
+        assert_eq!(head.parents, vec![later_id]);
+        assert!(revert_continue(repo_path).is_err());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_cherry_pick() {
+        let repo_path = "test_cherry_pick_repo";
+        let file_path = format!("{}/a.txt", repo_path);
+        let commit_file = |name: &str, content: &str, message: &str| {
+            write_file(&format!("{}/{}", repo_path, name), content).unwrap();
+            add(repo_path, vec![format!("{}/{}", repo_path, name)]).unwrap();
+            commit(repo_path, message).unwrap()
+        };
+        init_repository(repo_path, false).unwrap();
+        set_config_value(repo_path, "user.name", "Grace Hopper", false).unwrap();
+        commit_file("a.txt", "one\ntwo\nthree\n", "Add a.txt");
+
+        init_branch(repo_path, "feature", false).unwrap();
+        checkout(repo_path, "feature").unwrap();
+        let add_f_id = commit_file("f.txt", "F", "Add f.txt");
+        let shout_id = commit_file("a.txt", "ONE\ntwo\nthree\n", "Shout one");
+        let add_g_id = commit_file("g.txt", "G", "Add g.txt");
+        checkout(repo_path, "main").unwrap();
+
+        // A picked revision keeps its message and author
+        set_config_value(repo_path, "user.name", "Alan Turing", false).unwrap();
+        assert!(cherry_pick(repo_path, std::slice::from_ref(&add_f_id))
+            .unwrap()
+            .contains("Finished cherry-pick"));
+        let head_id = load_branch_metadata(repo_path, "main")
+            .unwrap()
+            .head_commit
+            .unwrap();
+        let head = load_revision_metadata(repo_path, &head_id).unwrap();
+        assert_ne!(head_id, add_f_id);
+        assert_eq!(head.message, "Add f.txt");
+        assert_eq!(head.author.unwrap().name, "Grace Hopper");
+        assert_eq!(head.committer.unwrap().name, "Alan Turing");
+        assert!(!head.files.contains_key("g.txt"));
+        assert_eq!(read_file(&format!("{}/f.txt", repo_path)).unwrap(), "F");
+
+        // A conflict stops the sequence, which can be aborted
+        let before_id = commit_file("a.txt", "uno\ntwo\nthree\n", "Spanish one");
+        let picks = [shout_id.clone(), add_g_id.clone()];
+        let report = cherry_pick(repo_path, &picks).unwrap();
+        assert!(report.contains("Conflict in file: 'a.txt'"));
+        assert!(read_file(&file_path).unwrap().contains("<<<<<<< main"));
+        assert!(cherry_pick(repo_path, &picks).is_err());
+
+        // Nothing may switch the branch or move its head while the cherry-pick is stopped
+        assert!(checkout(repo_path, "feature")
+            .unwrap_err()
+            .to_string()
+            .contains("cherry-pick --abort"));
+        assert!(init_branch(repo_path, "other", false).is_err());
+        assert!(load_branch_metadata(repo_path, "other").is_err());
+        assert!(merge(repo_path, "main", "feature", "").is_err());
+        assert!(revert(repo_path, &before_id).is_err());
+        assert!(commit_amend(repo_path, "", false).is_err());
+
+        // Nor is it aborted from another branch, which would write its files there
+        let mut repo_metadata = load_repo_metadata(repo_path).unwrap();
+        repo_metadata.head = "feature".to_string();
+        save_repo_metadata(repo_path, &repo_metadata).unwrap();
+        assert!(cherry_pick_abort(repo_path)
+            .unwrap_err()
+            .to_string()
+            .contains("Check it out again"));
+        repo_metadata.head = "main".to_string();
+        save_repo_metadata(repo_path, &repo_metadata).unwrap();
+        assert!(cherry_pick_abort(repo_path).is_ok());
+        assert_eq!(
+            load_branch_metadata(repo_path, "main").unwrap().head_commit,
+            Some(before_id.clone())
+        );
+        assert_eq!(read_file(&file_path).unwrap(), "uno\ntwo\nthree\n");
+
+        // Once resolved and added, the conflicts are committed and the rest is picked
+        cherry_pick(repo_path, &picks).unwrap();
+        assert!(cherry_pick_continue(repo_path)
+            .unwrap_err()
+            .to_string()
+            .contains("not resolved"));
+        write_file(&file_path, "UNO\ntwo\nthree\n").unwrap();
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        assert!(cherry_pick_continue(repo_path)
+            .unwrap()
+            .contains("Finished cherry-pick"));
+        let head_id = load_branch_metadata(repo_path, "main")
+            .unwrap()
+            .head_commit
+            .unwrap();
+        let head = load_revision_metadata(repo_path, &head_id).unwrap();
+        assert_eq!(head.message, "Add g.txt");
+        let resolved = load_revision_metadata(repo_path, &head.parents[0]).unwrap();
+        assert_eq!(resolved.message, "Shout one");
+        assert_eq!(resolved.parents, vec![before_id]);
+        assert_eq!(read_file(&file_path).unwrap(), "UNO\ntwo\nthree\n");
+        assert!(cherry_pick_continue(repo_path).is_err());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_rebase() {
+        let repo_path = "test_rebase_repo";
+        let file_path = format!("{}/a.txt", repo_path);
+        let commit_file = |name: &str, content: &str, message: &str| {
+            write_file(&format!("{}/{}", repo_path, name), content).unwrap();
+            add(repo_path, vec![format!("{}/{}", repo_path, name)]).unwrap();
+            commit(repo_path, message).unwrap()
+        };
+        let head_of = |branch: &str| {
+            load_branch_metadata(repo_path, branch)
+                .unwrap()
+                .head_commit
+                .unwrap()
+        };
+        init_repository(repo_path, false).unwrap();
+        commit_file("a.txt", "one\ntwo\nthree\n", "Add a.txt");
+
+        init_branch(repo_path, "feature", false).unwrap();
+        checkout(repo_path, "feature").unwrap();
+        commit_file("f.txt", "F", "Add f.txt");
+        let feature_id = commit_file("a.txt", "ONE\ntwo\nthree\n", "Shout one");
+        checkout(repo_path, "main").unwrap();
+        let main_id = commit_file("m.txt", "M", "Add m.txt");
+
+        // The branch's commits are replayed, oldest first, on top of the upstream head
+        checkout(repo_path, "feature").unwrap();
+        assert!(rebase(repo_path, "main")
+            .unwrap()
+            .contains("Rebasing 2 revision(s)"));
+        let head = load_revision_metadata(repo_path, &head_of("feature")).unwrap();
+        assert_eq!(head.message, "Shout one");
+        let first = load_revision_metadata(repo_path, &head.parents[0]).unwrap();
+        assert_eq!(first.message, "Add f.txt");
+        assert_eq!(first.parents, vec![main_id.clone()]);
+        assert_eq!(read_file(&format!("{}/m.txt", repo_path)).unwrap(), "M");
+        assert_eq!(read_file(&file_path).unwrap(), "ONE\ntwo\nthree\n");
+        assert!(rebase(repo_path, "main")
+            .unwrap()
+            .contains("already up to date"));
+
+        // A conflict stops the rebase, which can be aborted, even after a garbage collection
+        checkout(repo_path, "main").unwrap();
+        let main_id = commit_file("a.txt", "uno\ntwo\nthree\n", "Spanish one");
+        checkout(repo_path, "feature").unwrap();
+        commit_file("g.txt", "G", "Add g.txt");
+        let before_id = head_of("feature");
+        assert!(rebase(repo_path, "main")
+            .unwrap()
+            .contains("Conflict in file: 'a.txt'"));
+        // The upstream side of the conflict is labelled with the upstream, not the branch
+        assert!(read_file(&file_path)
+            .unwrap()
+            .contains("<<<<<<< main\nuno\n=======\nONE"));
+        gc(repo_path, false).unwrap();
+
+        // Aborting from another branch would write the branch's files there, so it is refused
+        assert!(checkout(repo_path, "main").is_err());
+        let mut repo_metadata = load_repo_metadata(repo_path).unwrap();
+        repo_metadata.head = "main".to_string();
+        save_repo_metadata(repo_path, &repo_metadata).unwrap();
+        assert!(rebase_abort(repo_path).is_err());
+        repo_metadata.head = "feature".to_string();
+        save_repo_metadata(repo_path, &repo_metadata).unwrap();
+        assert!(rebase_abort(repo_path).is_ok());
+        assert_eq!(head_of("feature"), before_id);
+        assert_eq!(read_file(&file_path).unwrap(), "ONE\ntwo\nthree\n");
+
+        // Once resolved and added, the conflicting commit is kept and the rest is replayed
+        rebase(repo_path, "main").unwrap();
+        assert!(rebase_continue(repo_path)
+            .unwrap_err()
+            .to_string()
+            .contains("not resolved"));
+        write_file(&file_path, "UNO\ntwo\nthree\n").unwrap();
+        add(repo_path, vec![file_path.clone()]).unwrap();
+        assert!(rebase_continue(repo_path)
+            .unwrap()
+            .contains("Finished rebase"));
+        let head = load_revision_metadata(repo_path, &head_of("feature")).unwrap();
+        assert_eq!(head.message, "Add g.txt");
+        let resolved = load_revision_metadata(repo_path, &head.parents[0]).unwrap();
+        assert_eq!(resolved.message, "Shout one");
+        assert_ne!(resolved.id, feature_id);
+        assert!(is_ancestor(repo_path, &main_id, &head.id).unwrap());
+        assert_eq!(read_file(&file_path).unwrap(), "UNO\ntwo\nthree\n");
+        assert!(rebase_continue(repo_path).is_err());
+
+        // Skipping drops the conflicting commit and replays the rest
+        checkout(repo_path, "main").unwrap();
+        commit_file("a.txt", "1\ntwo\nthree\n", "Number one");
+        checkout(repo_path, "feature").unwrap();
+        rebase(repo_path, "main").unwrap();
+        assert!(rebase_skip(repo_path).unwrap().contains("Finished rebase"));
+        let head = load_revision_metadata(repo_path, &head_of("feature")).unwrap();
+        assert_eq!(head.message, "Add g.txt");
+        assert_eq!(
+            load_revision_metadata(repo_path, &head.parents[0])
+                .unwrap()
+                .message,
+            "Add f.txt"
+        );
+        assert_eq!(read_file(&file_path).unwrap(), "1\ntwo\nthree\n");
+        assert!(rebase_skip(repo_path).is_err());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_rebase_failed_replay() {
+        let repo_path = "test_rebase_failed_repo";
+        let x_path = format!("{}/x.txt", repo_path);
+        let commit_file = |name: &str, content: &str, message: &str| {
+            write_file(&format!("{}/{}", repo_path, name), content).unwrap();
+            add(repo_path, vec![format!("{}/{}", repo_path, name)]).unwrap();
+            commit(repo_path, message).unwrap()
+        };
+        init_repository(repo_path, false).unwrap();
+        commit_file("a.txt", "A", "Add a.txt");
+
+        // The branch adds a file and removes it again, so an untracked copy clashes with the first pick
+        init_branch(repo_path, "feature", false).unwrap();
+        checkout(repo_path, "feature").unwrap();
+        commit_file("x.txt", "X", "Add x.txt");
+        delete_file(&x_path).unwrap();
+        add(repo_path, vec![repo_path.to_string()]).unwrap();
+        let feature_id = commit(repo_path, "Remove x.txt").unwrap();
+        checkout(repo_path, "main").unwrap();
+        commit_file("m.txt", "M", "Add m.txt");
+        checkout(repo_path, "feature").unwrap();
+        write_file(&x_path, "untracked").unwrap();
+
+        assert!(rebase(repo_path, "main")
+            .unwrap_err()
+            .to_string()
+            .contains("Local changes to 'x.txt'"));
+
+        // The original head is still recorded, so gc keeps it and the rebase can be aborted
+        assert!(get_sequence_revisions(repo_path)
+            .unwrap()
+            .contains(&feature_id));
+        gc(repo_path, false).unwrap();
+        assert!(rebase(repo_path, "main").is_err());
+        delete_file(&x_path).unwrap();
+        assert!(rebase_abort(repo_path).is_ok());
+        assert_eq!(
+            load_branch_metadata(repo_path, "feature")
+                .unwrap()
+                .head_commit,
+            Some(feature_id.clone())
+        );
+        assert!(load_revision_metadata(repo_path, &feature_id).is_ok());
+
+        delete_directory(repo_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_squash() {
+        let repo_path = "test_squash_repo";
+        let commit_file = |name: &str, content: &str, message: &str| {
+            write_file(&format!("{}/{}", repo_path, name), content).unwrap();
+            add(repo_path, vec![format!("{}/{}", repo_path, name)]).unwrap();
+            commit(repo_path, message).unwrap()
+        };
+        let head_of = || {
+            load_branch_metadata(repo_path, "main")
+                .unwrap()
+                .head_commit
+                .unwrap()
+        };
+        init_repository(repo_path, true).unwrap();
+        let base_id = commit_file("a.txt", "A", "Add a.txt");
+        push(repo_path, ".remote", "", false, false).unwrap();
+        let wip_id = commit_file("b.txt", "B", "WIP b");
+        commit_file("b.txt", "BB", "WIP b again");
+        let done_id = commit_file("b.txt", "BBB", "Finish b");
+        let later_id = commit_file("c.txt", "C", "Add c.txt");
+
+        // A range becomes one revision with the combined messages, and later ones are rewritten
+        let report = squash(repo_path, &format!("{}..{}", wip_id, done_id), 0, "", false).unwrap();
+        assert!(report.contains("Squashed 3 revision(s)"));
+        let head = load_revision_metadata(repo_path, &head_of()).unwrap();
+        let later = load_revision_metadata(repo_path, &later_id).unwrap();
+        assert_ne!(head.id, later_id);
+        assert_eq!(head.message, "Add c.txt");
+        assert_eq!(head.files, later.files);
+        let squashed = load_revision_metadata(repo_path, &head.parents[0]).unwrap();
+        assert_eq!(squashed.message, "WIP b\nWIP b again\nFinish b");
+        assert_eq!(squashed.parents, vec![base_id.clone()]);
+        assert_eq!(
+            squashed.files,
+            load_revision_metadata(repo_path, &done_id).unwrap().files
+        );
+        assert_eq!(read_file(&format!("{}/b.txt", repo_path)).unwrap(), "BBB");
+
+        // The last revisions are squashed with a supplied message
+        squash(repo_path, "", 2, "Add b.txt and c.txt", false).unwrap();
+        let head = load_revision_metadata(repo_path, &head_of()).unwrap();
+        assert_eq!(head.message, "Add b.txt and c.txt");
+        assert_eq!(head.parents, vec![base_id.clone()]);
+        assert!(squash(repo_path, "", 1, "", false).is_err());
+        assert!(squash(repo_path, &format!("{}..", wip_id), 0, "", false).is_err());
+
+        // Revisions already on the remote are only rewritten when forced
+        assert!(squash(repo_path, "", 2, "", false)
+            .unwrap_err()
+            .to_string()
+            .contains("already been pushed"));
+        squash(repo_path, "", 2, "Add files", true).unwrap();
+        let head = load_revision_metadata(repo_path, &head_of()).unwrap();
+        assert!(head.parents.is_empty());
+        assert_eq!(head.files.len(), 3);
+
+        delete_directory(repo_path, true).unwrap();
+    }
 }